@@ -0,0 +1,276 @@
+use am_synth::bench::{
+    fast_sin, AmBridge, AmSynth, AmSynthParams, Envelope, OperatorParams, ResonantFilter,
+    SineOscillator, BLOCK_SIZE,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use nih_plug::prelude::NoteEvent;
+use nih_plug::util;
+use std::f32::consts::TAU;
+
+const SAMPLE_RATE: f32 = 44100.0;
+const SAMPLES: usize = 4096;
+
+const VOICE_COUNTS: [usize; 4] = [1, 8, 32, 64];
+const BUFFER_SIZES: [usize; 3] = [64, 512, 2048];
+/// The voice counts that per-sample and block rendering are compared at.
+const BLOCK_VOICE_COUNTS: [usize; 3] = [8, 32, 64];
+
+/// Deterministic white noise, so every run filters the same input.
+fn noise(len: usize) -> Vec<f32> {
+    let mut state = 0x1234_5678_u32;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 * 2.0 - 1.0
+        })
+        .collect()
+}
+
+fn sine(c: &mut Criterion) {
+    let mut group = c.benchmark_group("SineOscillator::generate");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    group.bench_function("440 Hz", |b| {
+        let mut oscillator = SineOscillator::new(SAMPLE_RATE);
+        oscillator.set_frequency(440.0);
+        b.iter(|| {
+            for _ in 0..SAMPLES {
+                black_box(oscillator.generate());
+            }
+        });
+    });
+    group.finish();
+
+    // Phases over many periods, so the range reduction is exercised as well
+    let phases: Vec<f32> = (0..SAMPLES)
+        .map(|n| n as f32 * std::f32::consts::TAU / 1000.0)
+        .collect();
+    let mut output = vec![0.0; SAMPLES];
+    let mut group = c.benchmark_group("sin");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    for (name, sin) in [
+        ("f32::sin", f32::sin as fn(f32) -> f32),
+        ("fast_sin", fast_sin),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for (output, &phase) in output.iter_mut().zip(&phases) {
+                    *output = sin(black_box(phase));
+                }
+                black_box(&mut output);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn filter(c: &mut Criterion) {
+    let input = noise(SAMPLES);
+
+    let mut group = c.benchmark_group("ResonantFilter::process");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    for (name, filter_type) in [("Moog", true), ("Roland", false)] {
+        group.bench_function(name, |b| {
+            let mut filter = ResonantFilter::new(SAMPLE_RATE);
+            filter.set_filter_type(filter_type);
+            filter.set_params(1000.0, 0.7);
+            b.iter(|| {
+                for &sample in &input {
+                    black_box(filter.process(sample));
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn am_bridge(c: &mut Criterion) {
+    let carrier = noise(SAMPLES);
+    let modulator: Vec<f32> = carrier.iter().rev().copied().collect();
+
+    let mut group = c.benchmark_group("AmBridge::process");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    group.bench_function("depth 0.5", |b| {
+        let bridge = AmBridge::new();
+        b.iter(|| {
+            for (&carrier, &modulator) in carrier.iter().zip(&modulator) {
+                black_box(bridge.process(carrier, modulator, black_box(0.5)));
+            }
+        });
+    });
+    group.finish();
+}
+
+fn envelope(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Envelope::process");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    // Go through every stage: the attack and decay, the sustain, and the release
+    group.bench_function("ADSR", |b| {
+        let mut envelope = Envelope::new(SAMPLE_RATE);
+        envelope.set_parameters(0.01, 0.02, 0.5, 0.03);
+        b.iter(|| {
+            envelope.trigger();
+            for sample in 0..SAMPLES {
+                if sample == SAMPLES / 2 {
+                    envelope.release();
+                }
+                black_box(envelope.process());
+            }
+        });
+    });
+    group.finish();
+}
+
+/// A voice of the scalar baseline for [`AmSynth::render()`], with a carrier that's amplitude
+/// modulated by a single modulator.
+struct ScalarVoice {
+    note_freq: f32,
+    carrier_phase: f32,
+    modulator_phase: f32,
+    envelope: Envelope,
+}
+
+/// The same notes as [`playing_synth()`] for the scalar baseline.
+fn playing_scalar_voices(num_voices: usize) -> Vec<ScalarVoice> {
+    (0..num_voices)
+        .map(|voice| {
+            let mut envelope = Envelope::new(SAMPLE_RATE);
+            envelope.set_parameters(0.01, 0.1, 0.7, 0.3);
+            envelope.trigger();
+            ScalarVoice {
+                note_freq: util::midi_note_to_freq(36 + voice as u8),
+                carrier_phase: 0.0,
+                modulator_phase: 0.0,
+                envelope,
+            }
+        })
+        .collect()
+}
+
+/// Render one sample at a time and one voice at a time with `f32::sin`, reading the parameters
+/// again for every voice and every sample. This is what rendering in blocks and in lanes is
+/// measured against.
+fn render_scalar(
+    params: &AmSynthParams,
+    bridge: &AmBridge,
+    voices: &mut [ScalarVoice],
+    output: &mut [f32],
+) {
+    for output in output.iter_mut() {
+        *output = 0.0;
+        for voice in voices.iter_mut() {
+            let note_freq = voice.note_freq;
+            let freq = |operator: &OperatorParams| {
+                if operator.keyboard.value() {
+                    note_freq * operator.ratio.value()
+                } else {
+                    operator.freq.value()
+                }
+            };
+            let carrier_freq = freq(&params.carrier);
+            let modulator_freq = freq(&params.modulator);
+
+            let modulator = voice.modulator_phase.sin() * params.modulator.level.value();
+            let carrier = voice.carrier_phase.sin() * params.carrier.level.value();
+            *output += bridge.process(carrier, modulator, params.mod_depth.value())
+                * voice.envelope.process();
+
+            voice.carrier_phase = (voice.carrier_phase + TAU * carrier_freq / SAMPLE_RATE) % TAU;
+            voice.modulator_phase =
+                (voice.modulator_phase + TAU * modulator_freq / SAMPLE_RATE) % TAU;
+        }
+    }
+}
+
+/// A synth with `num_voices` voices that are all playing a note.
+fn playing_synth(num_voices: usize, max_block: usize) -> AmSynth {
+    let mut synth = AmSynth::default();
+    synth.prepare(SAMPLE_RATE, max_block);
+    synth.set_polyphony(num_voices);
+    synth.reset_smoothers();
+    for voice in 0..num_voices {
+        synth.handle_event(NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note: 36 + voice as u8,
+            velocity: 0.8,
+        });
+    }
+
+    synth
+}
+
+fn synth(c: &mut Criterion) {
+    let mut group = c.benchmark_group("AmSynth::process");
+    for num_voices in VOICE_COUNTS {
+        for buffer_size in BUFFER_SIZES {
+            group.throughput(Throughput::Elements(buffer_size as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("{num_voices} voices"), buffer_size),
+                &buffer_size,
+                |b, &buffer_size| {
+                    let mut synth = playing_synth(num_voices, buffer_size);
+                    let mut left = vec![0.0; buffer_size];
+                    let mut right = vec![0.0; buffer_size];
+                    b.iter(|| {
+                        let mut output = [left.as_mut_slice(), right.as_mut_slice()];
+                        synth.render(&mut output, None);
+                        black_box(&output);
+                    });
+                },
+            );
+        }
+    }
+    group.finish();
+
+    // The scalar baseline renders every voice one sample at a time with `f32::sin` and reads the
+    // parameters for every voice and sample. Rendering the synth one sample at a time shows how
+    // much is saved by reading the parameters once per block, and rendering in blocks adds the sine
+    // lanes, which use `std::simd` with the `simd` feature.
+    let lanes = if cfg!(feature = "simd") {
+        "std::simd lanes"
+    } else {
+        "lanes"
+    };
+    let mut group = c.benchmark_group("AmSynth::render blocks");
+    group.throughput(Throughput::Elements(SAMPLES as u64));
+    for num_voices in BLOCK_VOICE_COUNTS {
+        group.bench_with_input(
+            BenchmarkId::new("scalar f32::sin", num_voices),
+            &num_voices,
+            |b, &num_voices| {
+                let params = AmSynthParams::default();
+                let bridge = AmBridge::new();
+                let mut voices = playing_scalar_voices(num_voices);
+                let mut output = vec![0.0; SAMPLES];
+                b.iter(|| {
+                    render_scalar(&params, &bridge, &mut voices, &mut output);
+                    black_box(&output);
+                });
+            },
+        );
+
+        for (name, block_size) in [("single samples", 1), (lanes, BLOCK_SIZE)] {
+            group.bench_with_input(
+                BenchmarkId::new(name, num_voices),
+                &block_size,
+                |b, &block_size| {
+                    let mut synth = playing_synth(num_voices, block_size);
+                    let mut output = vec![0.0; SAMPLES];
+                    b.iter(|| {
+                        for block in output.chunks_mut(block_size) {
+                            synth.render(&mut [block], None);
+                        }
+                        black_box(&output);
+                    });
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, sine, filter, am_bridge, envelope, synth);
+criterion_main!(benches);
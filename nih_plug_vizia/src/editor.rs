@@ -10,7 +10,7 @@ use vizia::context::backend::TextConfig;
 use vizia::prelude::*;
 
 use crate::widgets::RawParamEvent;
-use crate::{assets, widgets, ViziaState, ViziaTheming};
+use crate::{assets, theme, widgets, ViziaState, ViziaTheming};
 
 /// An [`Editor`] implementation that calls an vizia draw loop.
 pub(crate) struct ViziaEditor {
@@ -57,6 +57,7 @@ impl Editor for ViziaEditor {
                 // There doesn't seem to be any way to bundle styles with a widget, so we'll always
                 // include the style sheet for our custom widgets at context creation
                 widgets::register_theme(cx);
+                theme::register_builtin_themes(cx);
             }
 
             // Any widget can change the parameters by emitting `ParamEvent` events. This model will
@@ -66,6 +67,19 @@ impl Editor for ViziaEditor {
             }
             .build(cx);
 
+            // Right clicking a parameter widget opens a context menu for that parameter. The menu
+            // itself is a `ParamContextMenu` view added by the plugin, but the menu's state needs
+            // to be reachable from every widget.
+            widgets::ParamMenuModel::default().build(cx);
+            // The plugin's own modulation is drawn on every parameter widget, and modulation sources
+            // can be dragged onto them
+            widgets::ModulationModel::default().build(cx);
+
+            // The selected theme is applied as a class on the root, and `ThemeEvent`s switch
+            // between themes
+            let theme_model = theme::ThemeModel::new(cx, vizia_state.clone());
+            theme_model.build(cx);
+
             // And we'll link `WindowEvent::ResizeWindow` and `WindowEvent::SetScale` events to our
             // `ViziaState`. We'll notify the host when any of these change.
             let current_inner_window_size = cx.window_size();
@@ -9,14 +9,17 @@ use nih_plug::prelude::{Editor, GuiContext};
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use vizia::prelude::*;
 
+use theme::Theme;
+
 // Re-export for convenience
 pub use vizia;
 
 pub mod assets;
 mod editor;
+pub mod theme;
 pub mod vizia_assets;
 pub mod widgets;
 
@@ -78,13 +81,15 @@ pub enum ViziaTheming {
     Builtin,
     /// Apply `nih_plug_vizia`'s custom theming. This is the default. You **need** to call
     /// [`nih_plug_vizia::assets::register_noto_sans_light()`][assets::register_noto_sans_light()]
-    /// at the start of your app function for the font to work correctly.
+    /// at the start of your app function for the font to work correctly. This also registers the
+    /// built-in [themes][theme::Theme] that can be switched between at runtime.
     #[default]
     Custom,
 }
 
 /// State for an `nih_plug_vizia` editor. The scale factor can be manipulated at runtime using
-/// `cx.set_user_scale_factor()`.
+/// `cx.set_user_scale_factor()` or the [`ZoomSelector`][widgets::ZoomSelector], and the theme can
+/// be changed using [`ThemeEvent`][theme::ThemeEvent]s.
 #[derive(Serialize, Deserialize)]
 pub struct ViziaState {
     /// A function that returns the window's current size in logical pixels, before any sort of
@@ -95,6 +100,9 @@ pub struct ViziaState {
     /// This can be used to allow GUIs to be scaled uniformly.
     #[serde(with = "nih_plug::params::persist::serialize_atomic_cell")]
     scale_factor: AtomicCell<f64>,
+    /// The selected theme. This defaults to the light theme for states saved before themes existed.
+    #[serde(default)]
+    theme: RwLock<Theme>,
     /// Whether the editor's window is currently open.
     #[serde(skip)]
     open: AtomicBool,
@@ -112,6 +120,7 @@ impl Debug for ViziaState {
         f.debug_struct("ViziaState")
             .field("size_fn", &format!("<fn> ({}, {})", width, height))
             .field("scale_factor", &self.scale_factor)
+            .field("theme", &self.theme)
             .field("open", &self.open)
             .finish()
     }
@@ -120,6 +129,7 @@ impl Debug for ViziaState {
 impl<'a> PersistentField<'a, ViziaState> for Arc<ViziaState> {
     fn set(&self, new_value: ViziaState) {
         self.scale_factor.store(new_value.scale_factor.load());
+        *self.theme.write().unwrap() = new_value.theme.into_inner().unwrap();
     }
 
     fn map<F, R>(&self, f: F) -> R
@@ -139,6 +149,7 @@ impl ViziaState {
         Arc::new(ViziaState {
             size_fn: Box::new(size_fn),
             scale_factor: AtomicCell::new(1.0),
+            theme: RwLock::new(Theme::default()),
             open: AtomicBool::new(false),
         })
     }
@@ -153,6 +164,7 @@ impl ViziaState {
         Arc::new(ViziaState {
             size_fn: Box::new(size_fn),
             scale_factor: AtomicCell::new(default_scale_factor),
+            theme: RwLock::new(Theme::default()),
             open: AtomicBool::new(false),
         })
     }
@@ -181,6 +193,11 @@ impl ViziaState {
         self.scale_factor.load()
     }
 
+    /// The selected theme. This can be changed using [`ThemeEvent`][theme::ThemeEvent]s.
+    pub fn theme(&self) -> Theme {
+        self.theme.read().unwrap().clone()
+    }
+
     /// Whether the GUI is currently visible.
     // Called `is_open()` instead of `open()` to avoid the ambiguity.
     pub fn is_open(&self) -> bool {
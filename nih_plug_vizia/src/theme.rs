@@ -0,0 +1,170 @@
+//! Themes that can be switched while the editor is open.
+//!
+//! Every theme is a stylesheet whose rules are scoped to a class on the window's root, and
+//! switching themes swaps out that class. The default light theme is the unscoped style from
+//! `nih_plug_vizia`'s own stylesheets, so it's always active underneath the other themes. The
+//! selected theme is stored in the [`ViziaState`].
+
+use nih_plug::debug::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use vizia::prelude::*;
+
+use crate::ViziaState;
+
+/// A theme for the editor. Use [`ThemeEvent::SetTheme`] to switch themes.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Data, Serialize, Deserialize)]
+pub enum Theme {
+    /// The light theme `nih_plug_vizia`'s widgets are styled with by default.
+    #[default]
+    Light,
+    Dark,
+    /// Black and white with a yellow accent color, with thicker borders.
+    HighContrast,
+    /// A theme loaded from a user's CSS file with [`load_user_themes()`]. This contains the file's
+    /// name without the extension.
+    User(String),
+}
+
+/// Switches the editor's theme. This is automatically handled by `nih_plug_vizia`, and the
+/// selected theme is stored in the [`ViziaState`].
+#[derive(Debug, Clone)]
+pub enum ThemeEvent {
+    SetTheme(Theme),
+}
+
+/// Applies the selected theme to the window's root. Registered in
+/// [`ViziaEditor::spawn()`][crate::editor::ViziaEditor::spawn()].
+#[derive(Lens)]
+pub(crate) struct ThemeModel {
+    pub vizia_state: Arc<ViziaState>,
+
+    /// The theme whose class is currently added to the root.
+    pub theme: Theme,
+}
+
+impl Theme {
+    /// The themes that are included with `nih_plug_vizia`.
+    pub const BUILTIN: [Theme; 3] = [Theme::Light, Theme::Dark, Theme::HighContrast];
+
+    /// The name shown to the user.
+    pub fn name(&self) -> &str {
+        match self {
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+            Theme::HighContrast => "High contrast",
+            Theme::User(name) => name,
+        }
+    }
+
+    /// The class that's added to the window's root while the theme is active. A user theme called
+    /// `midnight` uses the `theme-user-midnight` class.
+    pub fn class_name(&self) -> String {
+        match self {
+            Theme::Light => String::from("theme-light"),
+            Theme::Dark => String::from("theme-dark"),
+            Theme::HighContrast => String::from("theme-high-contrast"),
+            Theme::User(name) => format!("theme-user-{name}"),
+        }
+    }
+}
+
+impl ThemeModel {
+    pub fn new(cx: &mut Context, vizia_state: Arc<ViziaState>) -> Self {
+        // The model's event handler adds the class to the root
+        let theme = vizia_state.theme();
+        cx.emit(ThemeEvent::SetTheme(theme.clone()));
+
+        Self { vizia_state, theme }
+    }
+}
+
+impl Model for ThemeModel {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|theme_event, meta| match theme_event {
+            ThemeEvent::SetTheme(theme) => {
+                // This model lives on the root, so these classes are added to the root
+                cx.toggle_class(&self.theme.class_name(), false);
+                cx.toggle_class(&theme.class_name(), true);
+
+                self.theme = theme.clone();
+                *self.vizia_state.theme.write().unwrap() = theme.clone();
+
+                meta.consume();
+            }
+        });
+    }
+}
+
+/// Register the stylesheets for the built-in dark and high contrast themes. This is automatically
+/// called for you when using [`ViziaTheming::Custom`][crate::ViziaTheming::Custom].
+pub fn register_builtin_themes(cx: &mut Context) {
+    for stylesheet in [
+        include_style!("assets/themes/dark.css"),
+        include_style!("assets/themes/high_contrast.css"),
+    ] {
+        if let Err(err) = cx.add_stylesheet(stylesheet) {
+            nih_error!("Failed to load stylesheet: {err:?}")
+        }
+    }
+}
+
+/// Load every `.css` file in a directory as a user theme, and return those themes sorted by name.
+/// This should be called at the start of the app function, and new files are picked up the next
+/// time the editor is opened. A theme is named after its file, and its rules need to be scoped to
+/// the theme's [class][Theme::class_name()] like the built-in themes are. For instance,
+/// `midnight.css` could contain:
+///
+/// ```css
+/// :root.theme-user-midnight {
+///   background-color: #101020;
+///   color: #e0e0ff;
+/// }
+/// .theme-user-midnight param-slider .fill {
+///   background-color: #303060;
+/// }
+/// ```
+///
+/// Files with names that can't be used in a class name are skipped. If the directory does not
+/// exist then there are no user themes.
+pub fn load_user_themes(cx: &mut Context, directory: impl AsRef<Path>) -> Vec<Theme> {
+    let Ok(entries) = std::fs::read_dir(directory.as_ref()) else {
+        return Vec::new();
+    };
+
+    let mut themes: Vec<(String, std::path::PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "css"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?.to_owned();
+            if name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                Some((name, path))
+            } else {
+                nih_warn!("Skipping the user theme at '{}'", path.display());
+                None
+            }
+        })
+        .collect();
+    themes.sort();
+
+    themes
+        .into_iter()
+        .filter_map(
+            |(name, path)| match cx.add_stylesheet(CSS::from_file(&path)) {
+                Ok(()) => Some(Theme::User(name)),
+                Err(err) => {
+                    nih_error!(
+                        "Failed to load the user theme at '{}': {err:?}",
+                        path.display()
+                    );
+                    None
+                }
+            },
+        )
+        .collect()
+}
@@ -13,19 +13,42 @@ use vizia::prelude::*;
 
 use super::ViziaState;
 
+pub(crate) use modulation::ModulationModel;
+pub(crate) use param_context_menu::ParamMenuModel;
+
+mod envelope_editor;
+mod filter_response;
 mod generic_ui;
+mod modulation;
+mod oscilloscope;
 pub mod param_base;
 mod param_button;
+mod param_context_menu;
+mod param_knob;
 mod param_slider;
 mod peak_meter;
+mod piano_keyboard;
 mod resize_handle;
+mod spectrum_analyzer;
+mod theme_selector;
 pub mod util;
+mod zoom_selector;
 
+pub use envelope_editor::EnvelopeEditor;
+pub use filter_response::FilterResponse;
 pub use generic_ui::GenericUi;
+pub use modulation::{ModulationEvent, ModulationProvider, ModulationSources};
+pub use oscilloscope::Oscilloscope;
 pub use param_button::{ParamButton, ParamButtonExt};
+pub use param_context_menu::{MidiLearnHandler, ParamContextMenu, ParamMenuEvent};
+pub use param_knob::{ParamKnob, ParamKnobExt};
 pub use param_slider::{ParamSlider, ParamSliderExt, ParamSliderStyle};
-pub use peak_meter::PeakMeter;
+pub use peak_meter::{MeterOrientation, MeterScale, MeterTick, PeakMeter, PeakMeterExt};
+pub use piano_keyboard::{PianoKeyboard, PianoKeyboardExt};
 pub use resize_handle::ResizeHandle;
+pub use spectrum_analyzer::{Spectrum, SpectrumAnalyzer};
+pub use theme_selector::ThemeSelector;
+pub use zoom_selector::{ZoomSelector, ZOOM_LEVELS};
 
 /// Register the default theme for the widgets exported by this module. This is automatically called
 /// for you when using [`create_vizia_editor()`][super::create_vizia_editor()].
@@ -0,0 +1,386 @@
+//! An ADSR envelope editor with draggable breakpoints.
+
+use nih_plug::prelude::Param;
+use vizia::prelude::*;
+use vizia::vg;
+
+use super::param_base::ParamWidgetBase;
+use super::util::ModifiersExt;
+
+/// The radius of a breakpoint's handle in logical pixels.
+const HANDLE_RADIUS: f32 = 4.0;
+/// Clicks this many logical pixels away from a handle still grab it.
+const HANDLE_HIT_RADIUS: f32 = 10.0;
+/// The thickness of the envelope's curve in logical pixels.
+const CURVE_WIDTH: f32 = 1.5;
+
+/// The width of the attack, decay, and release segments at their parameters' maximum values, and
+/// the width of the sustain segment, as a fraction of the editor's width.
+const SEGMENT_WIDTH: f32 = 0.25;
+
+/// Draws an ADSR envelope from four parameters, and lets the user drag the envelope's breakpoints
+/// to change them. Dragging the attack's peak changes the attack time, dragging the end of the decay
+/// changes both the decay time and the sustain level, dragging the end of the sustain segment only
+/// changes the sustain level, and dragging the end of the release changes the release time. Every
+/// drag is a single automation gesture. Double clicking or Ctrl+Clicking a breakpoint resets its
+/// parameters to their default values, and right clicking a breakpoint opens the
+/// [`ParamContextMenu`][super::ParamContextMenu] for its time parameter, or for the sustain level
+/// on the sustain breakpoint.
+///
+/// The envelope is drawn using the parameters' normalized values. The `.envelope-curve` is stroked
+/// using its `color` and filled using its `background-color`, and the breakpoints are drawn as
+/// `.envelope-handles` using their `color`. The handle that's being dragged uses their
+/// `outline-color` instead.
+#[derive(Lens)]
+pub struct EnvelopeEditor {
+    attack: ParamWidgetBase,
+    decay: ParamWidgetBase,
+    sustain: ParamWidgetBase,
+    release: ParamWidgetBase,
+
+    /// The breakpoint that's currently being dragged.
+    dragged: Option<Breakpoint>,
+}
+
+/// One of the envelope's draggable points.
+// TODO: Vizia's lens derive macro requires this to be marked as pub
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// The end of the attack segment, controls the attack time.
+    Attack,
+    /// The end of the decay segment, controls the decay time and the sustain level.
+    Decay,
+    /// The end of the sustain segment, controls the sustain level.
+    Sustain,
+    /// The end of the release segment, controls the release time.
+    Release,
+}
+
+/// The envelope's normalized attack, decay, sustain, and release values.
+type EnvelopeValues = [f32; 4];
+
+/// The envelope's curve and its filled area, manually drawn using femtovg.
+struct EnvelopeCurve<L: Lens<Target = EnvelopeValues>> {
+    values: L,
+}
+
+/// The envelope's breakpoint handles, manually drawn using femtovg.
+struct EnvelopeHandles<L, D>
+where
+    L: Lens<Target = EnvelopeValues>,
+    D: Lens<Target = Option<Breakpoint>>,
+{
+    values: L,
+    dragged: D,
+}
+
+impl Breakpoint {
+    const ALL: [Breakpoint; 4] = [
+        Breakpoint::Attack,
+        Breakpoint::Decay,
+        Breakpoint::Sustain,
+        Breakpoint::Release,
+    ];
+
+    /// The breakpoint's position within the envelope's area for the given values. `(0, 0)` is the
+    /// bottom left corner and `(1, 1)` is the top right corner.
+    fn position(self, [attack, decay, sustain, release]: EnvelopeValues) -> (f32, f32) {
+        match self {
+            Breakpoint::Attack => (attack * SEGMENT_WIDTH, 1.0),
+            Breakpoint::Decay => ((attack + decay) * SEGMENT_WIDTH, sustain),
+            Breakpoint::Sustain => ((attack + decay + 1.0) * SEGMENT_WIDTH, sustain),
+            Breakpoint::Release => ((attack + decay + 1.0 + release) * SEGMENT_WIDTH, 0.0),
+        }
+    }
+}
+
+/// The area the envelope is drawn in. This leaves room for the handles around the edges. `dpi_scale`
+/// is the ratio between physical and logical pixels.
+fn envelope_area(bounds: BoundingBox, dpi_scale: f32) -> BoundingBox {
+    bounds.shrink(HANDLE_RADIUS * dpi_scale)
+}
+
+/// Convert a position returned by [`Breakpoint::position()`] to physical coordinates.
+fn to_coordinates(area: BoundingBox, (x, y): (f32, f32)) -> (f32, f32) {
+    (area.x + (x * area.w), area.y + ((1.0 - y) * area.h))
+}
+
+impl EnvelopeEditor {
+    /// Creates a new [`EnvelopeEditor`] for an envelope's parameters. `params_to_envelope` should
+    /// return the attack, decay, sustain, and release parameters in that order. See
+    /// [`ParamSlider`][super::ParamSlider] for more information on this function's other arguments.
+    pub fn new<L, Params, P, FMap>(
+        cx: &mut Context,
+        params: L,
+        params_to_envelope: FMap,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        P: Param + 'static,
+        FMap: Fn(&Params) -> [&P; 4] + Copy + 'static,
+    {
+        let values = params.clone().map(move |params| {
+            params_to_envelope(params).map(|param| param.unmodulated_normalized_value())
+        });
+
+        Self {
+            attack: ParamWidgetBase::new(cx, params.clone(), move |params| {
+                params_to_envelope(params)[0]
+            }),
+            decay: ParamWidgetBase::new(cx, params.clone(), move |params| {
+                params_to_envelope(params)[1]
+            }),
+            sustain: ParamWidgetBase::new(cx, params.clone(), move |params| {
+                params_to_envelope(params)[2]
+            }),
+            release: ParamWidgetBase::new(cx, params, move |params| params_to_envelope(params)[3]),
+
+            dragged: None,
+        }
+        .build(cx, move |cx| {
+            EnvelopeCurve {
+                values: values.clone(),
+            }
+            .build(cx, |_| {})
+            .class("envelope-curve")
+            .position_type(PositionType::SelfDirected)
+            .hoverable(false);
+
+            EnvelopeHandles {
+                values,
+                dragged: EnvelopeEditor::dragged,
+            }
+            .build(cx, |_| {})
+            .class("envelope-handles")
+            .position_type(PositionType::SelfDirected)
+            .hoverable(false);
+        })
+    }
+
+    /// The parameters' current normalized values.
+    fn values(&self) -> EnvelopeValues {
+        [
+            self.attack.unmodulated_normalized_value(),
+            self.decay.unmodulated_normalized_value(),
+            self.sustain.unmodulated_normalized_value(),
+            self.release.unmodulated_normalized_value(),
+        ]
+    }
+
+    /// The parameters controlled by a breakpoint.
+    fn breakpoint_params(&self, breakpoint: Breakpoint) -> impl Iterator<Item = &ParamWidgetBase> {
+        match breakpoint {
+            Breakpoint::Attack => [Some(&self.attack), None],
+            Breakpoint::Decay => [Some(&self.decay), Some(&self.sustain)],
+            Breakpoint::Sustain => [Some(&self.sustain), None],
+            Breakpoint::Release => [Some(&self.release), None],
+        }
+        .into_iter()
+        .flatten()
+    }
+
+    /// The breakpoint closest to the mouse cursor, if it's close enough to grab it.
+    fn breakpoint_under_cursor(&self, cx: &EventContext) -> Option<Breakpoint> {
+        let area = envelope_area(cx.bounds(), cx.scale_factor());
+        let values = self.values();
+        let (cursor_x, cursor_y) = (cx.mouse().cursorx, cx.mouse().cursory);
+
+        Breakpoint::ALL
+            .into_iter()
+            .map(|breakpoint| {
+                let (x, y) = to_coordinates(area, breakpoint.position(values));
+                let distance = (x - cursor_x).hypot(y - cursor_y);
+                (breakpoint, distance)
+            })
+            .filter(|(_, distance)| *distance <= HANDLE_HIT_RADIUS * cx.scale_factor())
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(breakpoint, _)| breakpoint)
+    }
+
+    /// Reset a breakpoint's parameters to their default values in a single automation gesture.
+    fn reset_to_default(&self, cx: &mut EventContext, breakpoint: Breakpoint) {
+        for param in self.breakpoint_params(breakpoint) {
+            param.begin_set_parameter(cx);
+            param.set_normalized_value(cx, param.default_normalized_value());
+            param.end_set_parameter(cx);
+        }
+    }
+
+    /// Move the dragged breakpoint to the mouse cursor's position.
+    fn drag_to(&self, cx: &mut EventContext, breakpoint: Breakpoint, cursor: (f32, f32)) {
+        let area = envelope_area(cx.bounds(), cx.scale_factor());
+        if area.w <= 0.0 || area.h <= 0.0 {
+            return;
+        }
+
+        // The x-coordinate is measured in segment widths, the y-coordinate is the envelope's level
+        let x = (cursor.0 - area.x) / area.w / SEGMENT_WIDTH;
+        let y = 1.0 - ((cursor.1 - area.y) / area.h);
+        let [attack, decay, _, _] = self.values();
+
+        let mut set = |param: &ParamWidgetBase, normalized_value: f32| {
+            param.set_normalized_value(cx, normalized_value.clamp(0.0, 1.0))
+        };
+        match breakpoint {
+            Breakpoint::Attack => set(&self.attack, x),
+            Breakpoint::Decay => {
+                set(&self.decay, x - attack);
+                set(&self.sustain, y);
+            }
+            Breakpoint::Sustain => set(&self.sustain, y),
+            Breakpoint::Release => set(&self.release, x - (attack + decay + 1.0)),
+        }
+    }
+}
+
+impl View for EnvelopeEditor {
+    fn element(&self) -> Option<&'static str> {
+        Some("envelope-editor")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            // See `ParamSlider` for why triple clicks are treated as regular clicks
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                if let Some(breakpoint) = self.breakpoint_under_cursor(cx) {
+                    if cx.modifiers().command() {
+                        self.reset_to_default(cx, breakpoint);
+                    } else {
+                        cx.capture();
+                        cx.set_active(true);
+
+                        for param in self.breakpoint_params(breakpoint) {
+                            param.begin_set_parameter(cx);
+                        }
+                        self.dragged = Some(breakpoint);
+                        cx.needs_redraw();
+                    }
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                if let Some(breakpoint) = self.breakpoint_under_cursor(cx) {
+                    self.reset_to_default(cx, breakpoint);
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                // The decay breakpoint also controls the sustain level, but its menu is for the
+                // decay time
+                if let Some(param) = self
+                    .breakpoint_under_cursor(cx)
+                    .and_then(|breakpoint| self.breakpoint_params(breakpoint).next())
+                {
+                    param.open_context_menu(cx);
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if let Some(breakpoint) = self.dragged.take() {
+                    cx.release();
+                    cx.set_active(false);
+
+                    for param in self.breakpoint_params(breakpoint) {
+                        param.end_set_parameter(cx);
+                    }
+                    cx.needs_redraw();
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(x, y) => {
+                if let Some(breakpoint) = self.dragged {
+                    self.drag_to(cx, breakpoint, (*x, *y));
+                    cx.needs_redraw();
+                }
+            }
+            _ => {}
+        });
+    }
+}
+
+impl<L: Lens<Target = EnvelopeValues>> View for EnvelopeCurve<L> {
+    fn element(&self) -> Option<&'static str> {
+        Some("envelope-curve")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let values = self.values.get(cx);
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let opacity = cx.opacity();
+        let mut line_color: vg::Color = cx.font_color().into();
+        line_color.set_alphaf(line_color.a * opacity);
+        let mut fill_color: vg::Color = cx.background_color().into();
+        fill_color.set_alphaf(fill_color.a * opacity);
+        let dpi_scale = cx.logical_to_physical(1.0);
+
+        let area = envelope_area(bounds, dpi_scale);
+        let (start_x, start_y) = to_coordinates(area, (0.0, 0.0));
+        let mut path = vg::Path::new();
+        path.move_to(start_x, start_y);
+        for breakpoint in Breakpoint::ALL {
+            let (x, y) = to_coordinates(area, breakpoint.position(values));
+            path.line_to(x, y);
+        }
+
+        let mut fill_path = path.clone();
+        fill_path.close();
+        canvas.fill_path(&fill_path, &vg::Paint::color(fill_color));
+
+        let mut paint = vg::Paint::color(line_color);
+        paint.set_line_width(CURVE_WIDTH * dpi_scale);
+        paint.set_line_join(vg::LineJoin::Round);
+        canvas.stroke_path(&path, &paint);
+    }
+}
+
+impl<L, D> View for EnvelopeHandles<L, D>
+where
+    L: Lens<Target = EnvelopeValues>,
+    D: Lens<Target = Option<Breakpoint>>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("envelope-handles")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let values = self.values.get(cx);
+        let dragged = self.dragged.get(cx);
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let opacity = cx.opacity();
+        let mut color: vg::Color = cx.font_color().into();
+        color.set_alphaf(color.a * opacity);
+        let mut dragged_color: vg::Color = cx.outline_color().into();
+        dragged_color.set_alphaf(dragged_color.a * opacity);
+        let dpi_scale = cx.logical_to_physical(1.0);
+
+        let area = envelope_area(bounds, dpi_scale);
+        for breakpoint in Breakpoint::ALL {
+            let (x, y) = to_coordinates(area, breakpoint.position(values));
+            let mut path = vg::Path::new();
+            path.circle(x, y, HANDLE_RADIUS * dpi_scale);
+
+            let color = if dragged == Some(breakpoint) {
+                dragged_color
+            } else {
+                color
+            };
+            canvas.fill_path(&path, &vg::Paint::color(color));
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! A filter response curve with a draggable cutoff and resonance node.
+
+use nih_plug::prelude::Param;
+use vizia::prelude::*;
+use vizia::vg;
+
+use super::param_base::ParamWidgetBase;
+use super::util::ModifiersExt;
+
+/// The frequency at the left edge of the graph.
+const MIN_FREQUENCY: f32 = 20.0;
+/// The frequency at the right edge of the graph.
+const MAX_FREQUENCY: f32 = 20_000.0;
+/// The gain at the bottom of the graph.
+const MIN_DB: f32 = -48.0;
+/// The gain at the top of the graph.
+const MAX_DB: f32 = 24.0;
+/// The number of points the response is evaluated at.
+const NUM_POINTS: usize = 200;
+
+/// The radius of the node in logical pixels.
+const NODE_RADIUS: f32 = 4.0;
+/// Clicks this many logical pixels away from the node still grab it.
+const NODE_HIT_RADIUS: f32 = 10.0;
+/// The thickness of the response curve in logical pixels.
+const CURVE_WIDTH: f32 = 1.5;
+
+/// Plots a filter's magnitude response with a logarithmic frequency axis, and lets the user drag a
+/// node to change the filter's cutoff frequency (horizontally) and resonance (vertically). The
+/// response is computed by a function that's called with the parameters and a frequency in Hz, and
+/// that returns the gain at that frequency in decibels. Dragging the node is a single automation
+/// gesture. Double clicking or Ctrl+Clicking the node resets both parameters to their default
+/// values, and right clicking it opens the [`ParamContextMenu`][super::ParamContextMenu] for the
+/// cutoff parameter.
+///
+/// The cutoff parameter's plain values need to be in Hz. The response is drawn as a
+/// `.filter-response__curve` using its `color`, and the area beneath the curve is filled using its
+/// `background-color`. The node is a `.filter-response__node` drawn using its `color`, or its
+/// `outline-color` while it's being dragged.
+#[derive(Lens)]
+pub struct FilterResponse {
+    cutoff: ParamWidgetBase,
+    resonance: ParamWidgetBase,
+
+    /// Whether the node is currently being dragged.
+    dragging: bool,
+}
+
+/// The manually drawn response curve.
+struct ResponseCurve<L: Lens<Target = Vec<f32>>> {
+    /// The gain in decibels at every point along the frequency axis.
+    response_db: L,
+}
+
+/// The manually drawn node. Its position is the cutoff frequency in Hz and the resonance's
+/// normalized value.
+struct ResponseNode<L, D>
+where
+    L: Lens<Target = (f32, f32)>,
+    D: Lens<Target = bool>,
+{
+    position: L,
+    dragging: D,
+}
+
+/// Map a frequency to a `[0, 1]` fraction of the graph's width.
+fn frequency_to_t(frequency: f32) -> f32 {
+    (frequency / MIN_FREQUENCY).ln() / (MAX_FREQUENCY / MIN_FREQUENCY).ln()
+}
+
+/// The inverse of [`frequency_to_t()`].
+fn t_to_frequency(t: f32) -> f32 {
+    MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(t)
+}
+
+/// The area the curve and node are drawn in. This leaves room for the node around the edges.
+/// `dpi_scale` is the ratio between physical and logical pixels.
+fn graph_area(bounds: BoundingBox, dpi_scale: f32) -> BoundingBox {
+    bounds.shrink(NODE_RADIUS * dpi_scale)
+}
+
+/// The node's physical coordinates for a cutoff frequency and a normalized resonance.
+fn node_coordinates(area: BoundingBox, (cutoff, resonance): (f32, f32)) -> (f32, f32) {
+    (
+        area.x + frequency_to_t(cutoff).clamp(0.0, 1.0) * area.w,
+        area.y + (1.0 - resonance) * area.h,
+    )
+}
+
+impl FilterResponse {
+    /// Creates a new [`FilterResponse`]. `params_to_filter` should return the cutoff and resonance
+    /// parameters in that order, and `response_db` computes the filter's gain in decibels at a
+    /// frequency in Hz. See [`ParamSlider`][super::ParamSlider] for more information on this
+    /// function's other arguments.
+    pub fn new<L, Params, P, FMap, FResponse>(
+        cx: &mut Context,
+        params: L,
+        params_to_filter: FMap,
+        response_db: FResponse,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        P: Param<Plain = f32> + 'static,
+        FMap: Fn(&Params) -> [&P; 2] + Copy + 'static,
+        FResponse: Fn(&Params, f32) -> f32 + 'static,
+    {
+        // This is recomputed whenever the curve is drawn, so it follows any parameter changes
+        let response_lens = params.clone().map(move |params| {
+            (0..NUM_POINTS)
+                .map(|idx| {
+                    let frequency = t_to_frequency(idx as f32 / (NUM_POINTS - 1) as f32);
+                    response_db(params, frequency)
+                })
+                .collect::<Vec<f32>>()
+        });
+        let position_lens = params.clone().map(move |params| {
+            let [cutoff, resonance] = params_to_filter(params);
+            (
+                cutoff.unmodulated_plain_value(),
+                resonance.unmodulated_normalized_value(),
+            )
+        });
+
+        Self {
+            cutoff: ParamWidgetBase::new(cx, params.clone(), move |params| {
+                params_to_filter(params)[0]
+            }),
+            resonance: ParamWidgetBase::new(cx, params, move |params| params_to_filter(params)[1]),
+
+            dragging: false,
+        }
+        .build(cx, move |cx| {
+            ResponseCurve {
+                response_db: response_lens,
+            }
+            .build(cx, |_| {})
+            .class("filter-response__curve")
+            .position_type(PositionType::SelfDirected)
+            .hoverable(false);
+
+            ResponseNode {
+                position: position_lens,
+                dragging: FilterResponse::dragging,
+            }
+            .build(cx, |_| {})
+            .class("filter-response__node")
+            .position_type(PositionType::SelfDirected)
+            .hoverable(false);
+        })
+    }
+
+    /// Whether the mouse cursor is close enough to the node to grab it.
+    fn node_under_cursor(&self, cx: &EventContext) -> bool {
+        let area = graph_area(cx.bounds(), cx.scale_factor());
+        let position = (
+            self.cutoff.unmodulated_plain_value(),
+            self.resonance.unmodulated_normalized_value(),
+        );
+        let (x, y) = node_coordinates(area, position);
+
+        (x - cx.mouse().cursorx).hypot(y - cx.mouse().cursory)
+            <= NODE_HIT_RADIUS * cx.scale_factor()
+    }
+
+    /// Reset both parameters to their default values in a single automation gesture.
+    fn reset_to_default(&self, cx: &mut EventContext) {
+        for param in [&self.cutoff, &self.resonance] {
+            param.begin_set_parameter(cx);
+            param.set_normalized_value(cx, param.default_normalized_value());
+            param.end_set_parameter(cx);
+        }
+    }
+
+    /// Move the node to the mouse cursor's position.
+    fn drag_to(&self, cx: &mut EventContext, (x, y): (f32, f32)) {
+        let area = graph_area(cx.bounds(), cx.scale_factor());
+        if area.w <= 0.0 || area.h <= 0.0 {
+            return;
+        }
+
+        let frequency = t_to_frequency(((x - area.x) / area.w).clamp(0.0, 1.0));
+        let resonance = 1.0 - ((y - area.y) / area.h);
+        self.cutoff
+            .set_normalized_value(cx, self.cutoff.preview_normalized(frequency));
+        self.resonance
+            .set_normalized_value(cx, resonance.clamp(0.0, 1.0));
+    }
+}
+
+impl View for FilterResponse {
+    fn element(&self) -> Option<&'static str> {
+        Some("filter-response")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            // See `ParamSlider` for why triple clicks are treated as regular clicks
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                if self.node_under_cursor(cx) {
+                    if cx.modifiers().command() {
+                        self.reset_to_default(cx);
+                    } else {
+                        cx.capture();
+                        cx.set_active(true);
+
+                        self.cutoff.begin_set_parameter(cx);
+                        self.resonance.begin_set_parameter(cx);
+                        self.dragging = true;
+                        cx.needs_redraw();
+                    }
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                if self.node_under_cursor(cx) {
+                    self.reset_to_default(cx);
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                if self.node_under_cursor(cx) {
+                    self.cutoff.open_context_menu(cx);
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.dragging {
+                    self.dragging = false;
+                    cx.release();
+                    cx.set_active(false);
+
+                    self.cutoff.end_set_parameter(cx);
+                    self.resonance.end_set_parameter(cx);
+                    cx.needs_redraw();
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(x, y) => {
+                if self.dragging {
+                    self.drag_to(cx, (*x, *y));
+                    cx.needs_redraw();
+                }
+            }
+            _ => {}
+        });
+    }
+}
+
+impl<L: Lens<Target = Vec<f32>>> View for ResponseCurve<L> {
+    fn element(&self) -> Option<&'static str> {
+        Some("response-curve")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let response_db = self.response_db.get(cx);
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 || response_db.len() < 2 {
+            return;
+        }
+
+        let opacity = cx.opacity();
+        let mut line_color: vg::Color = cx.font_color().into();
+        line_color.set_alphaf(line_color.a * opacity);
+        let mut fill_color: vg::Color = cx.background_color().into();
+        fill_color.set_alphaf(fill_color.a * opacity);
+        let dpi_scale = cx.logical_to_physical(1.0);
+
+        let area = graph_area(bounds, dpi_scale);
+        let mut path = vg::Path::new();
+        for (idx, gain_db) in response_db.iter().enumerate() {
+            let x = area.x + (idx as f32 / (response_db.len() - 1) as f32 * area.w);
+            let y =
+                area.y + (1.0 - ((gain_db - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0)) * area.h;
+            if idx == 0 {
+                path.move_to(x, y);
+            } else {
+                path.line_to(x, y);
+            }
+        }
+
+        let mut fill_path = path.clone();
+        fill_path.line_to(area.right(), area.bottom());
+        fill_path.line_to(area.left(), area.bottom());
+        fill_path.close();
+        canvas.fill_path(&fill_path, &vg::Paint::color(fill_color));
+
+        let mut paint = vg::Paint::color(line_color);
+        paint.set_line_width(CURVE_WIDTH * dpi_scale);
+        paint.set_line_join(vg::LineJoin::Round);
+        canvas.stroke_path(&path, &paint);
+    }
+}
+
+impl<L, D> View for ResponseNode<L, D>
+where
+    L: Lens<Target = (f32, f32)>,
+    D: Lens<Target = bool>,
+{
+    fn element(&self) -> Option<&'static str> {
+        Some("response-node")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let position = self.position.get(cx);
+        let dragging = self.dragging.get(cx);
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let opacity = cx.opacity();
+        let mut color: vg::Color = if dragging {
+            cx.outline_color().into()
+        } else {
+            cx.font_color().into()
+        };
+        color.set_alphaf(color.a * opacity);
+        let dpi_scale = cx.logical_to_physical(1.0);
+
+        let (x, y) = node_coordinates(graph_area(bounds, dpi_scale), position);
+        let mut path = vg::Path::new();
+        path.circle(x, y, NODE_RADIUS * dpi_scale);
+        canvas.fill_path(&path, &vg::Paint::color(color));
+    }
+}
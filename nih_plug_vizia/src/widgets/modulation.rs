@@ -0,0 +1,166 @@
+//! Showing a plugin's own modulation on parameter widgets, and assigning modulation sources by
+//! dragging them onto parameter widgets.
+
+use nih_plug::prelude::ParamPtr;
+use std::sync::Arc;
+use vizia::prelude::*;
+
+/// Describes the modulation a plugin applies internally, for instance through LFOs, envelopes, or
+/// a mod matrix. The parameter widgets in this module draw the range from
+/// [`modulation_range()`][Self::modulation_range()] on top of the parameter's value, and
+/// [`ModulationSources`] lets the user drag the plugin's sources onto those widgets. Set the editor's
+/// provider with [`ModulationEvent::SetProvider`].
+///
+/// The methods are called from the GUI thread, and `modulation_range()` is called for every
+/// parameter widget on every frame, so it should not do more than a couple of atomic loads and a
+/// lookup.
+pub trait ModulationProvider: Send + Sync {
+    /// The normalized range `(start, end)` this parameter currently covers across all voices,
+    /// including the host's modulation, or `None` if the parameter is not modulated internally.
+    fn modulation_range(&self, param: ParamPtr) -> Option<(f32, f32)>;
+    /// The names of the modulation sources that can be dragged onto parameters, in the order they
+    /// are shown in.
+    fn sources(&self) -> Vec<String>;
+    /// Route the source at index `source` in [`sources()`][Self::sources()] to this parameter.
+    /// Returns `false` if the parameter cannot be modulated or if there is no room for another
+    /// route.
+    fn assign(&self, source: usize, param: ParamPtr) -> bool;
+}
+
+impl<T: ModulationProvider + ?Sized> ModulationProvider for Arc<T> {
+    fn modulation_range(&self, param: ParamPtr) -> Option<(f32, f32)> {
+        (**self).modulation_range(param)
+    }
+
+    fn sources(&self) -> Vec<String> {
+        (**self).sources()
+    }
+
+    fn assign(&self, source: usize, param: ParamPtr) -> bool {
+        (**self).assign(source, param)
+    }
+}
+
+/// Events for the editor's modulation state. These events are automatically handled by
+/// `nih_plug_vizia`.
+#[derive(Clone)]
+pub enum ModulationEvent {
+    /// Set the [`ModulationProvider`] the parameter widgets get their modulation from. This should
+    /// be emitted once at the start of the app function.
+    SetProvider(Arc<dyn ModulationProvider>),
+    /// Start dragging the source with this index. [`ModulationSources`] emits this when a source
+    /// is pressed.
+    BeginDrag(usize),
+    /// Route a dragged source to a parameter. The parameter widgets emit this through
+    /// [`ParamWidgetBase::drop_modulation_source()`][super::param_base::ParamWidgetBase::drop_modulation_source()]
+    /// when a source is released on top of them.
+    Assign(usize, ParamPtr),
+}
+
+/// The editor's [`ModulationProvider`] and the source that's currently being dragged. Registered
+/// in [`ViziaEditor::spawn()`][super::super::ViziaEditor::spawn()] so every parameter widget can
+/// reach it.
+#[derive(Lens, Default)]
+pub(crate) struct ModulationModel {
+    pub provider: Option<Arc<dyn ModulationProvider>>,
+    /// The index of the source that's being dragged, if any.
+    pub dragged_source: Option<usize>,
+}
+
+impl Model for ModulationModel {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|modulation_event, meta| match modulation_event {
+            ModulationEvent::SetProvider(provider) => {
+                self.provider = Some(provider.clone());
+
+                meta.consume();
+            }
+            ModulationEvent::BeginDrag(source) => {
+                // This model lives on the root, so this class is added to the root. The parameter
+                // widgets are highlighted as drop targets while it's set.
+                self.dragged_source = Some(*source);
+                cx.toggle_class("modulation-drag", true);
+
+                meta.consume();
+            }
+            ModulationEvent::Assign(source, param) => {
+                if let Some(provider) = &self.provider {
+                    provider.assign(*source, *param);
+                }
+
+                // The parameter widget consumed the mouse up event, so the drag ends here
+                self.dragged_source = None;
+                cx.toggle_class("modulation-drag", false);
+
+                meta.consume();
+            }
+        });
+
+        // Parameter widgets consume the mouse up event when a source is dropped on them, so if the
+        // event makes it here the source was released somewhere else
+        event.map(|window_event, _| {
+            if let WindowEvent::MouseUp(MouseButton::Left) = window_event {
+                if self.dragged_source.take().is_some() {
+                    cx.toggle_class("modulation-drag", false);
+                }
+            }
+        });
+    }
+}
+
+/// A row with the editor's [`ModulationProvider`]'s sources. Pressing a source and releasing the
+/// mouse on top of a parameter widget routes that source to the parameter. The sources are
+/// `.modulation-sources__source` labels, and the source that's being dragged is `:checked`.
+pub struct ModulationSources;
+
+/// One of the sources in a [`ModulationSources`] row.
+struct ModulationSource {
+    source: usize,
+}
+
+impl ModulationSources {
+    /// Create a new row of modulation sources. This is empty until a provider has been set with
+    /// [`ModulationEvent::SetProvider`].
+    pub fn new(cx: &mut Context) -> Handle<Self> {
+        Self.build(cx, |cx| {
+            let sources = ModulationModel::provider.map(|provider| {
+                provider
+                    .as_ref()
+                    .map(|provider| provider.sources())
+                    .unwrap_or_default()
+            });
+
+            // The provider's sources don't change, so this only rebuilds once the provider is set
+            Binding::new(cx, sources, |cx, sources| {
+                for (source, name) in sources.get(cx).into_iter().enumerate() {
+                    ModulationSource { source }
+                        .build(cx, |cx| {
+                            Label::new(cx, &name).hoverable(false);
+                        })
+                        .class("modulation-sources__source")
+                        .checked(
+                            ModulationModel::dragged_source
+                                .map(move |dragged_source| *dragged_source == Some(source)),
+                        );
+                }
+            });
+        })
+    }
+}
+
+impl View for ModulationSources {
+    fn element(&self) -> Option<&'static str> {
+        Some("modulation-sources")
+    }
+}
+
+impl View for ModulationSource {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| {
+            if let WindowEvent::MouseDown(MouseButton::Left) = window_event {
+                cx.emit(ModulationEvent::BeginDrag(self.source));
+                meta.consume();
+            }
+        });
+    }
+}
@@ -0,0 +1,102 @@
+//! An oscilloscope that triggers on rising zero crossings.
+
+use vizia::prelude::*;
+use vizia::vg;
+
+/// The thickness of the waveform in logical pixels.
+const LINE_WIDTH: f32 = 1.5;
+
+/// Draws a waveform. Half of the samples are shown, starting at the first rising zero crossing in
+/// the first half of the samples so periodic signals stand still. If there is no zero crossing then
+/// the most recent samples are shown instead. The samples should be ordered from the oldest to the
+/// newest sample.
+///
+/// The waveform is drawn using the `color` property, and the center line uses the `outline-color`.
+pub struct Oscilloscope<L: Lens<Target = Vec<f32>>> {
+    samples: L,
+}
+
+impl<L: Lens<Target = Vec<f32>>> Oscilloscope<L> {
+    /// Creates a new [`Oscilloscope`] showing a window of samples between -1 and 1.
+    pub fn new(cx: &mut Context, samples: L) -> Handle<Self> {
+        Self { samples }.build(cx, |_| {})
+    }
+}
+
+/// The index of the first sample to show.
+fn trigger_position(samples: &[f32]) -> usize {
+    let search_length = samples.len() / 2;
+    (1..=search_length)
+        .find(|&idx| samples[idx - 1] <= 0.0 && samples[idx] > 0.0)
+        .unwrap_or(search_length)
+}
+
+impl<L: Lens<Target = Vec<f32>>> View for Oscilloscope<L> {
+    fn element(&self) -> Option<&'static str> {
+        Some("oscilloscope")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let samples = self.samples.get(cx);
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        // These basics are taken directly from the default implementation of this function
+        let opacity = cx.opacity();
+        let mut background_color: vg::Color = cx.background_color().into();
+        background_color.set_alphaf(background_color.a * opacity);
+        let mut border_color: vg::Color = cx.border_color().into();
+        border_color.set_alphaf(border_color.a * opacity);
+        let mut line_color: vg::Color = cx.font_color().into();
+        line_color.set_alphaf(line_color.a * opacity);
+        let mut center_color: vg::Color = cx.outline_color().into();
+        center_color.set_alphaf(center_color.a * opacity);
+        let border_width = cx.border_width();
+        let dpi_scale = cx.logical_to_physical(1.0);
+
+        let mut outline = vg::Path::new();
+        outline.rect(
+            bounds.x + border_width / 2.0,
+            bounds.y + border_width / 2.0,
+            bounds.w - border_width,
+            bounds.h - border_width,
+        );
+        canvas.fill_path(&outline, &vg::Paint::color(background_color));
+
+        let area = bounds.shrink(border_width);
+        let center_y = area.y + (area.h / 2.0);
+        let mut path = vg::Path::new();
+        path.move_to(area.left(), center_y);
+        path.line_to(area.right(), center_y);
+        let mut paint = vg::Paint::color(center_color);
+        paint.set_line_width(dpi_scale);
+        canvas.stroke_path(&path, &paint);
+
+        let num_shown = samples.len() / 2;
+        if num_shown >= 2 {
+            let start = trigger_position(&samples);
+            let mut path = vg::Path::new();
+            for (idx, sample) in samples[start..start + num_shown].iter().enumerate() {
+                let x = area.x + (idx as f32 / (num_shown - 1) as f32 * area.w);
+                let y = center_y - (sample.clamp(-1.0, 1.0) * area.h / 2.0);
+                if idx == 0 {
+                    path.move_to(x, y);
+                } else {
+                    path.line_to(x, y);
+                }
+            }
+
+            let mut paint = vg::Paint::color(line_color);
+            paint.set_line_width(LINE_WIDTH * dpi_scale);
+            paint.set_line_join(vg::LineJoin::Round);
+            canvas.stroke_path(&path, &paint);
+        }
+
+        let mut paint = vg::Paint::color(border_color);
+        paint.set_line_width(border_width);
+        canvas.stroke_path(&outline, &paint);
+    }
+}
@@ -3,7 +3,8 @@
 use nih_plug::prelude::*;
 use vizia::prelude::*;
 
-use super::RawParamEvent;
+use super::modulation::{ModulationEvent, ModulationModel};
+use super::{ParamMenuEvent, RawParamEvent};
 
 /// A helper for creating parameter widgets. The general idea is that a parameter widget struct can
 /// adds a `ParamWidgetBase` field on its struct, and then calls [`ParamWidgetBase::view()`] in its
@@ -82,6 +83,20 @@ where
             f(param)
         })
     }
+
+    /// Create a lens for the normalized range the plugin's own modulation currently covers for
+    /// this parameter, as reported by the editor's
+    /// [`ModulationProvider`][super::ModulationProvider]. This is `None` if the parameter is not
+    /// modulated internally or if no provider has been set.
+    pub fn make_modulation_range_lens(&self) -> impl Lens<Target = Option<(f32, f32)>> {
+        let param_ptr = self.param.as_ptr();
+
+        ModulationModel::provider.map(move |provider| {
+            provider
+                .as_ref()
+                .and_then(|provider| provider.modulation_range(param_ptr))
+        })
+    }
 }
 
 /// Generate a [`ParamWidgetData`] function that forwards the function call to the underlying
@@ -227,6 +242,26 @@ impl ParamWidgetBase {
         cx.emit(RawParamEvent::EndSetParameter(self.param_ptr));
     }
 
+    /// Open the [`ParamContextMenu`][super::ParamContextMenu] for this parameter at the mouse
+    /// cursor's position. Usually this is done on right click.
+    pub fn open_context_menu(&self, cx: &mut EventContext) {
+        cx.emit(ParamMenuEvent::Open(self.param_ptr));
+    }
+
+    /// If a source from a [`ModulationSources`][super::ModulationSources] row is being dragged,
+    /// then route it to this parameter and return `true`. This should be called on mouse up when
+    /// the widget is not being dragged itself, and the event should be consumed if this returns
+    /// `true`.
+    pub fn drop_modulation_source(&self, cx: &mut EventContext) -> bool {
+        match ModulationModel::dragged_source.get(cx) {
+            Some(source) => {
+                cx.emit(ModulationEvent::Assign(source, self.param_ptr));
+                true
+            }
+            None => false,
+        }
+    }
+
     param_ptr_forward!(pub fn name(&self) -> &str);
     param_ptr_forward!(pub fn unit(&self) -> &'static str);
     param_ptr_forward!(pub fn poly_modulation_id(&self) -> Option<u32>);
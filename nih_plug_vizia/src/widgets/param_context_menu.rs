@@ -0,0 +1,346 @@
+//! A context menu for parameter widgets.
+
+use nih_plug::debug::*;
+use nih_plug::prelude::ParamPtr;
+use std::rc::Rc;
+use std::sync::Arc;
+use vizia::prelude::*;
+
+use super::RawParamEvent;
+
+/// The width of the menu in logical pixels.
+const MENU_WIDTH: f32 = 170.0;
+
+/// Binds MIDI CCs to parameters for the MIDI learn entries in a [`ParamContextMenu`]. How the CCs
+/// are received and where the bindings are stored is up to the plugin.
+pub trait MidiLearnHandler {
+    /// Bind the next incoming CC to this parameter.
+    fn learn(&self, param: ParamPtr);
+    /// Remove this parameter's CC binding, and stop waiting for a CC if it's being learned.
+    fn forget(&self, param: ParamPtr);
+    /// The CC currently bound to this parameter, if any.
+    fn mapped_cc(&self, param: ParamPtr) -> Option<u8>;
+}
+
+impl<T: MidiLearnHandler + ?Sized> MidiLearnHandler for Arc<T> {
+    fn learn(&self, param: ParamPtr) {
+        (**self).learn(param)
+    }
+
+    fn forget(&self, param: ParamPtr) {
+        (**self).forget(param)
+    }
+
+    fn mapped_cc(&self, param: ParamPtr) -> Option<u8> {
+        (**self).mapped_cc(param)
+    }
+}
+
+/// Opens and closes the menu shown by a [`ParamContextMenu`]. The parameter widgets in this module
+/// emit this on right click through
+/// [`ParamWidgetBase::open_context_menu()`][super::param_base::ParamWidgetBase::open_context_menu()],
+/// and these events are automatically handled by `nih_plug_vizia`.
+#[derive(Debug, Clone, Copy)]
+pub enum ParamMenuEvent {
+    /// Open the menu for a parameter at the mouse cursor's position.
+    Open(ParamPtr),
+    /// Close the menu without doing anything.
+    Close,
+}
+
+/// The parameter the context menu is shown for. Registered in
+/// [`ViziaEditor::spawn()`][super::super::ViziaEditor::spawn()] so every parameter widget can
+/// reach it.
+#[derive(Lens, Default)]
+pub(crate) struct ParamMenuModel {
+    /// The parameter the menu is currently open for, if it's open.
+    pub param: Option<ParamPtr>,
+    /// The position of the menu's top left corner in logical pixels.
+    pub position: (f32, f32),
+}
+
+/// The actions behind the menu's entries.
+#[derive(Debug, Clone)]
+enum ParamMenuAction {
+    ResetToDefault,
+    /// Replace the menu entries with a text box.
+    EnterValue,
+    /// A new value has been sent by the text box after pressing Enter.
+    TextInput(String),
+    CopyValue,
+    PasteValue,
+    MidiLearn,
+    MidiForget,
+}
+
+/// A context menu for the parameter widgets in this module. Right clicking a [`ParamSlider`],
+/// [`ParamButton`], [`ParamKnob`], or one of the other parameter widgets opens this menu for that
+/// parameter with entries to reset the parameter to its default value, to type in a new value, and
+/// to copy and paste the parameter's value. Pasted values are parsed by the pasted parameter, so
+/// values can be pasted between parameters with the same unit. Use
+/// [`with_midi_learn()`][Self::with_midi_learn()] to also add entries for binding a MIDI CC to the
+/// parameter.
+///
+/// This view covers the entire window while the menu is open, and clicking anywhere outside of the
+/// menu closes it. Because of that it should be the last view added to the editor's root. The menu
+/// itself is a `.param-context-menu__menu` with a `.param-context-menu__title` label and
+/// `.param-context-menu__item` buttons.
+///
+/// [`ParamSlider`]: super::ParamSlider
+/// [`ParamButton`]: super::ParamButton
+/// [`ParamKnob`]: super::ParamKnob
+#[derive(Lens)]
+pub struct ParamContextMenu {
+    midi_learn: Option<Rc<dyn MidiLearnHandler>>,
+
+    /// Will be set to `true` when the 'Enter value' entry is clicked, which replaces the entries
+    /// with a text box.
+    text_input_active: bool,
+}
+
+impl Model for ParamMenuModel {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|param_menu_event, meta| match param_menu_event {
+            ParamMenuEvent::Open(param) => {
+                // The menu should not extend past the window's right edge. The mouse coordinates
+                // are in physical pixels.
+                let scale_factor = cx.scale_factor();
+                let max_x = (cx.bounds().w - (MENU_WIDTH * scale_factor)).max(0.0);
+                self.position = (
+                    cx.mouse().cursorx.min(max_x) / scale_factor,
+                    cx.mouse().cursory / scale_factor,
+                );
+                self.param = Some(*param);
+
+                meta.consume();
+            }
+            ParamMenuEvent::Close => {
+                self.param = None;
+
+                meta.consume();
+            }
+        });
+    }
+}
+
+impl ParamContextMenu {
+    /// Creates a new [`ParamContextMenu`] without MIDI learn entries.
+    pub fn new(cx: &mut Context) -> Handle<Self> {
+        Self::build_menu(cx, None)
+    }
+
+    /// Creates a new [`ParamContextMenu`] with entries for binding a MIDI CC to the parameter and
+    /// for removing that binding again.
+    pub fn with_midi_learn(
+        cx: &mut Context,
+        midi_learn: impl MidiLearnHandler + 'static,
+    ) -> Handle<Self> {
+        Self::build_menu(cx, Some(Rc::new(midi_learn)))
+    }
+
+    fn build_menu(cx: &mut Context, midi_learn: Option<Rc<dyn MidiLearnHandler>>) -> Handle<Self> {
+        Self {
+            midi_learn: midi_learn.clone(),
+
+            text_input_active: false,
+        }
+        .build(cx, move |cx| {
+            // The menu's contents are rebuilt every time it's opened, so the MIDI learn entries
+            // reflect the current bindings
+            let is_open = ParamMenuModel::param.map(|param| param.is_some());
+            Binding::new(cx, is_open, move |cx, is_open| {
+                if !is_open.get(cx) {
+                    return;
+                }
+                let Some(param) = ParamMenuModel::param.get(cx) else {
+                    return;
+                };
+
+                let midi_learn = midi_learn.clone();
+                VStack::new(cx, move |cx| {
+                    Label::new(cx, unsafe { param.name() }.to_owned())
+                        .class("param-context-menu__title")
+                        .hoverable(false);
+
+                    Binding::new(
+                        cx,
+                        ParamContextMenu::text_input_active,
+                        move |cx, text_input_active| {
+                            if text_input_active.get(cx) {
+                                Self::text_input_view(cx);
+                            } else {
+                                Self::entries_view(cx, param, midi_learn.as_deref());
+                            }
+                        },
+                    );
+                })
+                .class("param-context-menu__menu")
+                .position_type(PositionType::SelfDirected)
+                .left(ParamMenuModel::position.map(|(x, _)| Pixels(*x)))
+                .top(ParamMenuModel::position.map(|(_, y)| Pixels(*y)))
+                .width(Pixels(MENU_WIDTH))
+                .height(Auto);
+            });
+        })
+        .position_type(PositionType::SelfDirected)
+        .width(Stretch(1.0))
+        .height(Stretch(1.0))
+        .display(ParamMenuModel::param.map(|param| param.is_some()))
+    }
+
+    /// Create the menu's entries.
+    fn entries_view(cx: &mut Context, param: ParamPtr, midi_learn: Option<&dyn MidiLearnHandler>) {
+        Self::entry(cx, "Reset to default", ParamMenuAction::ResetToDefault);
+        Self::entry(cx, "Enter value...", ParamMenuAction::EnterValue);
+        Self::entry(cx, "Copy value", ParamMenuAction::CopyValue);
+        Self::entry(cx, "Paste value", ParamMenuAction::PasteValue);
+
+        if let Some(midi_learn) = midi_learn {
+            Self::entry(cx, "MIDI learn", ParamMenuAction::MidiLearn);
+            if let Some(cc) = midi_learn.mapped_cc(param) {
+                Self::entry(
+                    cx,
+                    format!("Forget MIDI CC {cc}"),
+                    ParamMenuAction::MidiForget,
+                );
+            }
+        }
+    }
+
+    fn entry(cx: &mut Context, label: impl Into<String>, action: ParamMenuAction) {
+        let label = label.into();
+        Button::new(
+            cx,
+            move |cx| cx.emit(action.clone()),
+            move |cx| Label::new(cx, &label),
+        )
+        .class("param-context-menu__item")
+        .width(Stretch(1.0));
+    }
+
+    /// Create a text input that's shown in place of the entries.
+    fn text_input_view(cx: &mut Context) {
+        let display_value_lens = ParamMenuModel::param.map(|param| {
+            param
+                .map(|param| unsafe {
+                    param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
+                })
+                .unwrap_or_default()
+        });
+
+        Textbox::new(cx, display_value_lens)
+            .class("value-entry")
+            .on_submit(|cx, string, success| {
+                if success {
+                    cx.emit(ParamMenuAction::TextInput(string))
+                } else {
+                    cx.emit(ParamMenuEvent::Close);
+                }
+            })
+            .on_cancel(|cx| {
+                cx.emit(ParamMenuEvent::Close);
+            })
+            .on_build(|cx| {
+                cx.emit(TextEvent::StartEdit);
+                cx.emit(TextEvent::SelectAll);
+            })
+            .width(Stretch(1.0));
+    }
+
+    /// Set the parameter to a new normalized value in a single automation gesture.
+    fn set_normalized_value(cx: &mut EventContext, param: ParamPtr, normalized_value: f32) {
+        cx.emit(RawParamEvent::BeginSetParameter(param));
+        cx.emit(RawParamEvent::SetParameterNormalized(
+            param,
+            normalized_value,
+        ));
+        cx.emit(RawParamEvent::EndSetParameter(param));
+    }
+
+    fn close(&mut self, cx: &mut EventContext) {
+        self.text_input_active = false;
+        cx.emit(ParamMenuEvent::Close);
+    }
+}
+
+impl View for ParamContextMenu {
+    fn element(&self) -> Option<&'static str> {
+        Some("param-context-menu")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|action, meta| {
+            let Some(param) = ParamMenuModel::param.get(cx) else {
+                return;
+            };
+
+            match action {
+                ParamMenuAction::ResetToDefault => {
+                    Self::set_normalized_value(cx, param, unsafe {
+                        param.default_normalized_value()
+                    });
+                }
+                ParamMenuAction::EnterValue => {
+                    self.text_input_active = true;
+                    meta.consume();
+                    // The menu stays open until the value has been entered
+                    return;
+                }
+                ParamMenuAction::TextInput(string) => {
+                    if let Some(normalized_value) =
+                        unsafe { param.string_to_normalized_value(string) }
+                    {
+                        Self::set_normalized_value(cx, param, normalized_value);
+                    }
+                }
+                ParamMenuAction::CopyValue => {
+                    let value = unsafe {
+                        param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
+                    };
+                    if let Err(err) = cx.set_clipboard(value) {
+                        nih_error!("Failed to copy the parameter's value: {err}");
+                    }
+                }
+                ParamMenuAction::PasteValue => match cx.get_clipboard() {
+                    Ok(string) => {
+                        if let Some(normalized_value) =
+                            unsafe { param.string_to_normalized_value(string.trim()) }
+                        {
+                            Self::set_normalized_value(cx, param, normalized_value);
+                        }
+                    }
+                    Err(err) => nih_error!("Failed to read the clipboard: {err}"),
+                },
+                ParamMenuAction::MidiLearn => {
+                    if let Some(midi_learn) = &self.midi_learn {
+                        midi_learn.learn(param);
+                    }
+                }
+                ParamMenuAction::MidiForget => {
+                    if let Some(midi_learn) = &self.midi_learn {
+                        midi_learn.forget(param);
+                    }
+                }
+            }
+
+            self.close(cx);
+            meta.consume();
+        });
+
+        // The text box closes the menu directly when text entry is cancelled
+        event.map(|param_menu_event, _| {
+            if let ParamMenuEvent::Close = param_menu_event {
+                self.text_input_active = false;
+            }
+        });
+
+        event.map(|window_event, meta| match window_event {
+            // Clicking anywhere outside of the menu closes it
+            WindowEvent::MouseDown(_) if meta.target == cx.current() => {
+                self.close(cx);
+                meta.consume();
+            }
+            _ => {}
+        });
+    }
+}
@@ -0,0 +1,472 @@
+//! A rotary knob that integrates with NIH-plug's [`Param`] types.
+
+use nih_plug::prelude::Param;
+use std::f32::consts::PI;
+use vizia::prelude::*;
+use vizia::vg;
+
+use super::param_base::ParamWidgetBase;
+use super::util::ModifiersExt;
+
+/// Dragging the knob vertically by this many logical pixels covers the parameter's entire range.
+const DRAG_DISTANCE: f32 = 200.0;
+/// When shift+dragging a parameter, the drag distance is scaled by this amount.
+const GRANULAR_DRAG_MULTIPLIER: f32 = 0.1;
+
+/// The angle in radians of the knob's minimum value. Angles increase clockwise, and zero points to
+/// the right.
+const START_ANGLE: f32 = PI * 0.75;
+/// The angle covered by the knob's range. This leaves a gap at the bottom.
+const SWEEP_ANGLE: f32 = PI * 1.5;
+/// The thickness of the knob's arcs in logical pixels.
+const ARC_WIDTH: f32 = 3.0;
+
+/// A rotary knob that integrates with NIH-plug's [`Param`] types. Dragging the knob up and down
+/// changes the value, and the parameter's value is shown beneath the knob. It otherwise behaves the
+/// same as a [`ParamSlider`][super::ParamSlider]: Shift+drag for granular dragging, double click or
+/// Ctrl+Click to reset the parameter to its default value, Alt+Click to enter a value, right click
+/// to open the [`ParamContextMenu`][super::ParamContextMenu], and scroll to step through the
+/// parameter's values. See [`ParamKnobExt`] for additional options.
+///
+/// The knob consists of three arcs that can be styled through their `color` property: the
+/// `.arc--track` covering the entire range, the `.arc--value` showing the current value, and the
+/// `.arc--modulation` showing the offset between the current value and the value after monophonic
+/// modulation. If the editor has a [`ModulationProvider`][super::ModulationProvider] then a fourth
+/// `.arc--mod-range` arc shows the range the plugin's own modulation covers across all voices.
+/// Dropping a source from a [`ModulationSources`][super::ModulationSources] row on the knob routes
+/// that source to the knob's parameter.
+#[derive(Lens)]
+pub struct ParamKnob {
+    param_base: ParamWidgetBase,
+
+    /// Will be set to `true` when the knob gets Alt+Click'ed which will replace the value label with
+    /// a text box.
+    text_input_active: bool,
+    /// The start of the current drag gesture. This is `None` if the knob is not being dragged.
+    /// Resetting the parameter or entering a text value should not initiate a drag.
+    drag_status: Option<DragStatus>,
+
+    // These fields are set through modifiers:
+    /// Whether or not to listen to scroll events for changing the parameter's value in steps.
+    use_scroll_wheel: bool,
+    /// The number of (fractional) scrolled lines that have not yet been turned into parameter
+    /// change events. This is needed to support trackpads with smooth scrolling.
+    scrolled_lines: f32,
+    /// Whether to draw the value arc from the default value instead of from the minimum value.
+    /// This defaults to `true` for continuous parameters with a default value at around half of
+    /// their range.
+    bipolar: bool,
+    /// A specific label to use instead of displaying the parameter's value.
+    label_override: Option<String>,
+}
+
+enum ParamKnobEvent {
+    /// Text input has been cancelled without submitting a new value.
+    CancelTextInput,
+    /// A new value has been sent by the text input dialog after pressing Enter.
+    TextInput(String),
+}
+
+/// The mouse position and the parameter's value at the start of a drag. Dragging is relative to
+/// this point. These are reset when the Shift key is pressed or released during a drag so the
+/// value doesn't jump when switching to and from granular dragging.
+// TODO: Vizia's lens derive macro requires this to be marked as pub
+#[derive(Debug, Clone, Copy)]
+pub struct DragStatus {
+    /// The mouse's Y-coordinate when the drag was started.
+    pub starting_y_coordinate: f32,
+    /// The normalized value when the drag was started.
+    pub starting_value: f32,
+    /// Whether Shift was held down when the drag was started.
+    pub granular: bool,
+}
+
+/// A single arc of the knob, covering the normalized range `(start_t, end_t)`.
+struct KnobArc<L: Lens<Target = (f32, f32)>> {
+    range: L,
+    /// Whether to draw a pointer from the knob's center to the end of the arc.
+    pointer: bool,
+}
+
+impl ParamKnob {
+    /// Creates a new [`ParamKnob`] for the given parameter. See
+    /// [`ParamSlider`][super::ParamSlider] for more information on this function's arguments.
+    pub fn new<L, Params, P, FMap>(
+        cx: &mut Context,
+        params: L,
+        params_to_param: FMap,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = Params> + Clone,
+        Params: 'static,
+        P: Param + 'static,
+        FMap: Fn(&Params) -> &P + Copy + 'static,
+    {
+        let param_base = ParamWidgetBase::new(cx, params.clone(), params_to_param);
+        let bipolar = param_base.step_count().is_none()
+            && (0.45..=0.55).contains(&param_base.default_normalized_value());
+
+        Self {
+            param_base,
+
+            text_input_active: false,
+            drag_status: None,
+
+            use_scroll_wheel: true,
+            scrolled_lines: 0.0,
+            bipolar,
+            label_override: None,
+        }
+        .build(
+            cx,
+            ParamWidgetBase::build_view(params, params_to_param, move |cx, param_data| {
+                Binding::new(cx, ParamKnob::bipolar, move |cx, bipolar| {
+                    let bipolar = bipolar.get(cx);
+                    let default_value = param_data.param().default_normalized_value();
+
+                    // Can't use `.to_string()` here as that would include the modulation.
+                    let display_value_lens = param_data.make_lens(|param| {
+                        param.normalized_value_to_string(param.unmodulated_normalized_value(), true)
+                    });
+                    let track_lens = param_data.make_lens(|_| (0.0, 1.0));
+                    let value_lens = param_data.make_lens(move |param| {
+                        let start_t = if bipolar { default_value } else { 0.0 };
+                        (start_t, param.unmodulated_normalized_value())
+                    });
+                    // If the parameter is being modulated by the host (this only works for CLAP
+                    // plugins with hosts that support this), then this arc spans the difference
+                    // between the 'true' value and the current value after modulation has been
+                    // applied
+                    let modulation_lens = param_data.make_lens(|param| {
+                        (
+                            param.unmodulated_normalized_value(),
+                            param.modulated_normalized_value(),
+                        )
+                    });
+                    // An empty range isn't drawn
+                    let mod_range_lens = param_data
+                        .make_modulation_range_lens()
+                        .map(|range| range.unwrap_or((0.0, 0.0)));
+
+                    ZStack::new(cx, |cx| {
+                        Self::arc_view(cx, track_lens, "arc--track", false);
+                        Self::arc_view(cx, mod_range_lens, "arc--mod-range", false);
+                        Self::arc_view(cx, value_lens, "arc--value", true);
+                        Self::arc_view(cx, modulation_lens, "arc--modulation", false);
+                    })
+                    .class("knob")
+                    .hoverable(false);
+
+                    // Only draw the text input widget when it gets focussed. Creating the textbox
+                    // based on `ParamKnob::text_input_active` lets us focus the textbox when it
+                    // gets created.
+                    Binding::new(
+                        cx,
+                        ParamKnob::text_input_active,
+                        move |cx, text_input_active| {
+                            if text_input_active.get(cx) {
+                                Self::text_input_view(cx, display_value_lens);
+                            } else {
+                                Binding::new(
+                                    cx,
+                                    ParamKnob::label_override,
+                                    move |cx, label_override| {
+                                        match label_override.get(cx) {
+                                            Some(label_override) => Label::new(cx, &label_override),
+                                            None => Label::new(cx, display_value_lens),
+                                        }
+                                        .class("value")
+                                        .hoverable(false);
+                                    },
+                                );
+                            }
+                        },
+                    );
+                });
+            }),
+        )
+    }
+
+    /// Create one of the knob's arcs. These are drawn on top of each other.
+    fn arc_view(
+        cx: &mut Context,
+        range: impl Lens<Target = (f32, f32)>,
+        class: &str,
+        pointer: bool,
+    ) {
+        KnobArc { range, pointer }
+            .build(cx, |_| {})
+            .class("arc")
+            .class(class)
+            .hoverable(false);
+    }
+
+    /// Create a text input that's shown in place of the value label.
+    fn text_input_view(cx: &mut Context, display_value_lens: impl Lens<Target = String>) {
+        Textbox::new(cx, display_value_lens)
+            .class("value-entry")
+            .on_submit(|cx, string, success| {
+                if success {
+                    cx.emit(ParamKnobEvent::TextInput(string))
+                } else {
+                    cx.emit(ParamKnobEvent::CancelTextInput);
+                }
+            })
+            .on_cancel(|cx| {
+                cx.emit(ParamKnobEvent::CancelTextInput);
+            })
+            .on_build(|cx| {
+                cx.emit(TextEvent::StartEdit);
+                cx.emit(TextEvent::SelectAll);
+            })
+            .class("align_center");
+    }
+
+    /// Reset the parameter to its default value in a single automation gesture.
+    fn reset_to_default(&self, cx: &mut EventContext) {
+        self.param_base.begin_set_parameter(cx);
+        self.param_base
+            .set_normalized_value(cx, self.param_base.default_normalized_value());
+        self.param_base.end_set_parameter(cx);
+    }
+
+    /// Start a new drag from the current mouse position and value.
+    fn start_drag(&mut self, cx: &EventContext, y_coordinate: f32) {
+        self.drag_status = Some(DragStatus {
+            starting_y_coordinate: y_coordinate,
+            starting_value: self.param_base.unmodulated_normalized_value(),
+            granular: cx.modifiers().shift(),
+        });
+    }
+}
+
+impl View for ParamKnob {
+    fn element(&self) -> Option<&'static str> {
+        Some("param-knob")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|param_knob_event, meta| match param_knob_event {
+            ParamKnobEvent::CancelTextInput => {
+                self.text_input_active = false;
+                cx.set_active(false);
+
+                meta.consume();
+            }
+            ParamKnobEvent::TextInput(string) => {
+                if let Some(normalized_value) = self.param_base.string_to_normalized_value(string) {
+                    self.param_base.begin_set_parameter(cx);
+                    self.param_base.set_normalized_value(cx, normalized_value);
+                    self.param_base.end_set_parameter(cx);
+                }
+
+                self.text_input_active = false;
+
+                meta.consume();
+            }
+        });
+
+        event.map(|window_event, meta| match window_event {
+            // See `ParamSlider` for why triple clicks are treated as regular clicks
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                if cx.modifiers().alt() {
+                    // Alt+Click brings up a text entry dialog
+                    self.text_input_active = true;
+                    cx.set_active(true);
+                } else if cx.modifiers().command() {
+                    self.reset_to_default(cx);
+                } else if !self.text_input_active {
+                    cx.capture();
+                    // NOTE: Otherwise we don't get key up events
+                    cx.focus();
+                    cx.set_active(true);
+
+                    // Unlike the slider, the knob never jumps to the clicked position
+                    self.param_base.begin_set_parameter(cx);
+                    self.start_drag(cx, cx.mouse().cursory);
+                }
+
+                meta.consume();
+            }
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                self.param_base.open_context_menu(cx);
+                meta.consume();
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                // Ctrl+Click and double click should reset the parameter instead of initiating a
+                // drag operation
+                self.reset_to_default(cx);
+
+                meta.consume();
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.drag_status.is_some() {
+                    self.drag_status = None;
+                    cx.release();
+                    cx.set_active(false);
+
+                    self.param_base.end_set_parameter(cx);
+
+                    meta.consume();
+                } else if self.param_base.drop_modulation_source(cx) {
+                    meta.consume();
+                }
+            }
+            WindowEvent::MouseMove(_x, y) => {
+                if let Some(drag_status) = self.drag_status {
+                    // Pressing or releasing Shift continues the drag from the current position
+                    if drag_status.granular != cx.modifiers().shift() {
+                        self.start_drag(cx, *y);
+                        return;
+                    }
+
+                    // The cursor position is in physical pixels, so this needs to be compensated
+                    // for the DPI scale to remain consistent
+                    let multiplier = if drag_status.granular {
+                        GRANULAR_DRAG_MULTIPLIER
+                    } else {
+                        1.0
+                    };
+                    let delta_y = (drag_status.starting_y_coordinate - *y) / cx.scale_factor();
+                    let normalized_value =
+                        drag_status.starting_value + (delta_y * multiplier / DRAG_DISTANCE);
+
+                    self.param_base
+                        .set_normalized_value(cx, normalized_value.clamp(0.0, 1.0));
+                }
+            }
+            WindowEvent::MouseScroll(_scroll_x, scroll_y) if self.use_scroll_wheel => {
+                // With a regular scroll wheel `scroll_y` will only ever be -1 or 1, but with smooth
+                // scrolling trackpads being a thing `scroll_y` could be anything.
+                self.scrolled_lines += scroll_y;
+
+                if self.scrolled_lines.abs() >= 1.0 {
+                    let use_finer_steps = cx.modifiers().shift();
+
+                    // Scrolling while dragging needs to be taken into account here
+                    let drag_active = self.drag_status.is_some();
+                    if !drag_active {
+                        self.param_base.begin_set_parameter(cx);
+                    }
+
+                    let mut current_value = self.param_base.unmodulated_normalized_value();
+
+                    while self.scrolled_lines >= 1.0 {
+                        current_value = self
+                            .param_base
+                            .next_normalized_step(current_value, use_finer_steps);
+                        self.param_base.set_normalized_value(cx, current_value);
+                        self.scrolled_lines -= 1.0;
+                    }
+
+                    while self.scrolled_lines <= -1.0 {
+                        current_value = self
+                            .param_base
+                            .previous_normalized_step(current_value, use_finer_steps);
+                        self.param_base.set_normalized_value(cx, current_value);
+                        self.scrolled_lines += 1.0;
+                    }
+
+                    if drag_active {
+                        // Otherwise the next mouse move would undo the scrolling
+                        self.start_drag(cx, cx.mouse().cursory);
+                    } else {
+                        self.param_base.end_set_parameter(cx);
+                    }
+                }
+
+                meta.consume();
+            }
+            _ => {}
+        });
+    }
+}
+
+impl<L: Lens<Target = (f32, f32)>> View for KnobArc<L> {
+    fn element(&self) -> Option<&'static str> {
+        Some("knob-arc")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let (start_t, end_t) = self.range.get(cx);
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let opacity = cx.opacity();
+        let mut color: vg::Color = cx.font_color().into();
+        color.set_alphaf(color.a * opacity);
+        let line_width = ARC_WIDTH * cx.logical_to_physical(1.0);
+
+        let center_x = bounds.x + (bounds.w / 2.0);
+        let center_y = bounds.y + (bounds.h / 2.0);
+        let radius = (bounds.w.min(bounds.h) - line_width) / 2.0;
+        let t_to_angle = |t: f32| START_ANGLE + (t.clamp(0.0, 1.0) * SWEEP_ANGLE);
+        let (start_angle, end_angle) = (t_to_angle(start_t), t_to_angle(end_t));
+
+        let mut paint = vg::Paint::color(color);
+        paint.set_line_width(line_width);
+        paint.set_line_cap(vg::LineCap::Round);
+
+        // Don't draw slivers caused by rounding errors
+        if (end_angle - start_angle).abs() >= 1e-3 {
+            // Angles increase clockwise because the y-axis points down, which femtovg calls `Hole`
+            let mut path = vg::Path::new();
+            path.arc(
+                center_x,
+                center_y,
+                radius,
+                start_angle.min(end_angle),
+                start_angle.max(end_angle),
+                vg::Solidity::Hole,
+            );
+            canvas.stroke_path(&path, &paint);
+        }
+
+        if self.pointer {
+            let mut path = vg::Path::new();
+            path.move_to(
+                center_x + end_angle.cos() * radius * 0.3,
+                center_y + end_angle.sin() * radius * 0.3,
+            );
+            path.line_to(
+                center_x + end_angle.cos() * radius,
+                center_y + end_angle.sin() * radius,
+            );
+            canvas.stroke_path(&path, &paint);
+        }
+    }
+}
+
+/// Extension methods for [`ParamKnob`] handles.
+pub trait ParamKnobExt {
+    /// Don't respond to scroll wheel events. Useful when this knob is used as part of a scrolling
+    /// view.
+    fn disable_scroll_wheel(self) -> Self;
+
+    /// Draw the value arc starting from the parameter's default value (`true`), or starting from
+    /// the parameter's minimum value (`false`). By default this is only enabled for continuous
+    /// parameters with a centered default value.
+    fn set_bipolar(self, bipolar: bool) -> Self;
+
+    /// Manually set a fixed label for the knob instead of displaying the current value. This is
+    /// currently not reactive.
+    fn with_label(self, value: impl Into<String>) -> Self;
+}
+
+impl ParamKnobExt for Handle<'_, ParamKnob> {
+    fn disable_scroll_wheel(self) -> Self {
+        self.modify(|param_knob: &mut ParamKnob| param_knob.use_scroll_wheel = false)
+    }
+
+    fn set_bipolar(self, bipolar: bool) -> Self {
+        self.modify(|param_knob: &mut ParamKnob| param_knob.bipolar = bipolar)
+    }
+
+    fn with_label(self, value: impl Into<String>) -> Self {
+        self.modify(|param_knob: &mut ParamKnob| param_knob.label_override = Some(value.into()))
+    }
+}
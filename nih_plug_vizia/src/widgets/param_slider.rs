@@ -143,6 +143,9 @@ impl ParamSlider {
                     let modulation_start_delta_lens = param_data.make_lens(move |param| {
                         Self::compute_modulation_fill_start_delta(style, param)
                     });
+                    // The range covered by the plugin's own modulation, if it has a
+                    // `ModulationProvider`
+                    let mod_range_lens = param_data.make_modulation_range_lens();
 
                     // This is used to draw labels for `CurrentStepLabeled`
                     let make_preview_value_lens = move |normalized_value| {
@@ -167,6 +170,7 @@ impl ParamSlider {
                                         cx,
                                         fill_start_delta_lens,
                                         modulation_start_delta_lens,
+                                        mod_range_lens,
                                     );
                                     Self::slider_label_view(
                                         cx,
@@ -217,7 +221,24 @@ impl ParamSlider {
         cx: &mut Context,
         fill_start_delta_lens: impl Lens<Target = (f32, f32)>,
         modulation_start_delta_lens: impl Lens<Target = (f32, f32)>,
+        mod_range_lens: impl Lens<Target = Option<(f32, f32)>>,
     ) {
+        // The range covered by the plugin's own modulation is drawn underneath the other bars
+        Element::new(cx)
+            .class("fill")
+            .class("fill--mod-range")
+            .height(Stretch(1.0))
+            .visibility(mod_range_lens.map(|range| range.is_some()))
+            .left(mod_range_lens.map(|range| {
+                let (start, end) = range.unwrap_or_default();
+                Percentage(start.min(end) * 100.0)
+            }))
+            .width(mod_range_lens.map(|range| {
+                let (start, end) = range.unwrap_or_default();
+                Percentage((end - start).abs() * 100.0)
+            }))
+            .hoverable(false);
+
         // The filled bar portion. This can be visualized in a couple different ways depending on
         // the current style property. See [`ParamSliderStyle`].
         Element::new(cx)
@@ -443,8 +464,8 @@ impl View for ParamSlider {
                     self.text_input_active = true;
                     cx.set_active(true);
                 } else if cx.modifiers().command() {
-                    // Ctrl+Click, double click, and right clicks should reset the parameter instead
-                    // of initiating a drag operation
+                    // Ctrl+Click and double click should reset the parameter instead of initiating a
+                    // drag operation
                     self.param_base.begin_set_parameter(cx);
                     self.param_base
                         .set_normalized_value(cx, self.param_base.default_normalized_value());
@@ -478,12 +499,13 @@ impl View for ParamSlider {
 
                 meta.consume();
             }
-            WindowEvent::MouseDoubleClick(MouseButton::Left)
-            | WindowEvent::MouseDown(MouseButton::Right)
-            | WindowEvent::MouseDoubleClick(MouseButton::Right)
-            | WindowEvent::MouseTripleClick(MouseButton::Right) => {
-                // Ctrl+Click, double click, and right clicks should reset the parameter instead of
-                // initiating a drag operation
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                self.param_base.open_context_menu(cx);
+                meta.consume();
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                // Ctrl+Click and double click should reset the parameter instead of initiating a
+                // drag operation
                 self.param_base.begin_set_parameter(cx);
                 self.param_base
                     .set_normalized_value(cx, self.param_base.default_normalized_value());
@@ -499,6 +521,8 @@ impl View for ParamSlider {
 
                     self.param_base.end_set_parameter(cx);
 
+                    meta.consume();
+                } else if self.param_base.drop_modulation_source(cx) {
                     meta.consume();
                 }
             }
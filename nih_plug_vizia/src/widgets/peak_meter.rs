@@ -1,4 +1,4 @@
-//! A super simple peak meter widget.
+//! A peak meter widget with optional RMS levels and stereo channels.
 
 use nih_plug::prelude::util;
 use std::cell::Cell;
@@ -12,28 +12,120 @@ const TICK_WIDTH: f32 = 1.0;
 /// The gap between individual ticks.
 const TICK_GAP: f32 = 1.0;
 
-/// The decibel value corresponding to the very left of the bar.
+/// The default decibel value corresponding to the very left or bottom of the bar.
 const MIN_TICK: f32 = -90.0;
-/// The decibel value corresponding to the very right of the bar.
+/// The default decibel value corresponding to the very right or top of the bar.
 const MAX_TICK: f32 = 20.0;
-/// The ticks that will be shown beneath the peak meter's bar. The first value is shown as
+/// The default ticks that will be shown next to the peak meter's bar. The first value is shown as
 /// -infinity, and at the last position we'll draw the `dBFS` string.
 const TEXT_TICKS: [i32; 6] = [-80, -60, -40, -20, 0, 12];
 
-/// A simple horizontal peak meter.
+/// A peak meter for one or two channels, optionally showing the RMS level on top of the peak level.
+/// Use the methods on [`PeakMeterExt`] to change the meter's orientation, range, and ticks.
 ///
-/// TODO: There are currently no styling options at all
-/// TODO: Vertical peak meter, this is just a proof of concept to fit the gain GUI example.
-pub struct PeakMeter;
+/// The meter can be styled through CSS. Every channel is drawn as a `.bar` element. Within a bar,
+/// the `.bar__peak` ticks are drawn with a gradient from their `color` at the bottom of the range
+/// to their `outline-color` at the top of the range, or with a solid color if the outline color is
+/// transparent. The same goes for the `.bar__rms` ticks, and the held peak value is drawn as a
+/// `.bar__hold` line using its `color`. Vertical meters have the `vertical` class.
+#[derive(Lens)]
+pub struct PeakMeter {
+    orientation: MeterOrientation,
+    scale: MeterScale,
+}
+
+/// The direction a [`PeakMeter`]'s bars are drawn in. Set this using
+/// [`PeakMeterExt::orientation()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Data)]
+pub enum MeterOrientation {
+    /// The bar grows from left to right, with the ticks beneath the bar.
+    Horizontal,
+    /// The bar grows from bottom to top, with the ticks to the right of the bar.
+    Vertical,
+}
+
+/// The decibel range and ticks for a [`PeakMeter`]. Set this using [`PeakMeterExt::range()`] and
+/// [`PeakMeterExt::ticks()`].
+#[derive(Debug, Clone, PartialEq, Data)]
+pub struct MeterScale {
+    /// The decibel value at the start of the bar.
+    pub min_db: f32,
+    /// The decibel value at the end of the bar.
+    pub max_db: f32,
+    /// The ticks shown next to the bar.
+    pub ticks: Vec<MeterTick>,
+}
+
+/// A labeled tick next to a [`PeakMeter`]'s bar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeterTick {
+    pub db: f32,
+    pub label: String,
+}
 
-/// The bar bit for the peak meter, manually drawn using vertical lines.
-struct PeakMeterBar<L, P>
+/// How a single layer of a bar displays its level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LayerKind {
+    /// Fill the bar up to the level using ticks.
+    Ticks,
+    /// Draw a single line at the level.
+    Line,
+}
+
+/// One layer of a peak meter's bar, manually drawn using lines. The peak level, RMS level, and
+/// held peak value are all drawn as separate layers so they can be styled separately.
+struct PeakMeterLayer<L>
 where
     L: Lens<Target = f32>,
-    P: Lens<Target = f32>,
 {
     level_dbfs: L,
-    peak_dbfs: P,
+    kind: LayerKind,
+    orientation: MeterOrientation,
+    min_db: f32,
+    max_db: f32,
+}
+
+impl Default for MeterScale {
+    fn default() -> Self {
+        let ticks = TEXT_TICKS
+            .iter()
+            .enumerate()
+            .map(|(idx, tick_db)| match idx {
+                0 => MeterTick::new(*tick_db as f32, "-inf"),
+                // This is only inclued in the array to make positioning this easier
+                _ if idx == TEXT_TICKS.len() - 1 => MeterTick::new(*tick_db as f32, "dBFS"),
+                _ => MeterTick::db(*tick_db as f32),
+            })
+            .collect();
+
+        Self {
+            min_db: MIN_TICK,
+            max_db: MAX_TICK,
+            ticks,
+        }
+    }
+}
+
+impl MeterScale {
+    /// Map a decibel value to a `[0, 1]` fraction of the bar. This is not clamped.
+    fn fraction(&self, db: f32) -> f32 {
+        (db - self.min_db) / (self.max_db - self.min_db)
+    }
+}
+
+impl MeterTick {
+    /// A tick at `db` with a custom label.
+    pub fn new(db: f32, label: impl Into<String>) -> Self {
+        Self {
+            db,
+            label: label.into(),
+        }
+    }
+
+    /// A tick at `db` labeled with its value.
+    pub fn db(db: f32) -> Self {
+        Self::new(db, db.to_string())
+    }
 }
 
 impl PeakMeter {
@@ -41,95 +133,239 @@ impl PeakMeter {
     /// value for a certain amount of time.
     pub fn new<L>(cx: &mut Context, level_dbfs: L, hold_time: Option<Duration>) -> Handle<Self>
     where
-        L: Lens<Target = f32>,
+        L: Lens<Target = f32> + Clone,
+    {
+        let levels_dbfs = level_dbfs.map(|level| [*level]);
+        // The mapped lens' type can't be named, so this is the easiest way to get a typed `None`
+        let no_rms_dbfs = Some(levels_dbfs.clone()).filter(|_| false);
+        Self::build_meter(cx, levels_dbfs, no_rms_dbfs, hold_time)
+    }
+
+    /// The same as [`new()`][Self::new()], but with the RMS level shown on top of the peak level.
+    pub fn new_with_rms<L, R>(
+        cx: &mut Context,
+        level_dbfs: L,
+        rms_dbfs: R,
+        hold_time: Option<Duration>,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = f32> + Clone,
+        R: Lens<Target = f32> + Clone,
+    {
+        let levels_dbfs = level_dbfs.map(|level| [*level]);
+        let rms_dbfs = rms_dbfs.map(|level| [*level]);
+        Self::build_meter(cx, levels_dbfs, Some(rms_dbfs), hold_time)
+    }
+
+    /// Creates a new [`PeakMeter`] with two bars for the left and right channels' values in
+    /// decibel.
+    pub fn new_stereo<L>(
+        cx: &mut Context,
+        levels_dbfs: L,
+        hold_time: Option<Duration>,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = [f32; 2]> + Clone,
+    {
+        Self::build_meter(cx, levels_dbfs, None::<L>, hold_time)
+    }
+
+    /// The same as [`new_stereo()`][Self::new_stereo()], but with the RMS levels shown on top of
+    /// the peak levels.
+    pub fn new_stereo_with_rms<L, R>(
+        cx: &mut Context,
+        levels_dbfs: L,
+        rms_dbfs: R,
+        hold_time: Option<Duration>,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = [f32; 2]> + Clone,
+        R: Lens<Target = [f32; 2]> + Clone,
     {
-        Self.build(cx, |cx| {
-            // Now for something that may be illegal under some jurisdictions. If a hold time is
-            // given, then we'll build a new lens that always gives the held peak level for the
-            // current moment in time by mutating some values captured into the mapping closure.
-            let held_peak_value_db = Cell::new(f32::MIN);
-            let last_held_peak_value: Cell<Option<Instant>> = Cell::new(None);
-            let peak_dbfs = level_dbfs.map(move |level| -> f32 {
-                match hold_time {
-                    Some(hold_time) => {
-                        let mut peak_level = held_peak_value_db.get();
-                        let peak_time = last_held_peak_value.get();
-
-                        let now = Instant::now();
-                        if *level >= peak_level
-                            || peak_time.is_none()
-                            || now > peak_time.unwrap() + hold_time
-                        {
-                            peak_level = *level;
-                            held_peak_value_db.set(peak_level);
-                            last_held_peak_value.set(Some(now));
+        Self::build_meter(cx, levels_dbfs, Some(rms_dbfs), hold_time)
+    }
+
+    /// Build a meter with a bar for every channel.
+    fn build_meter<const N: usize, L, R>(
+        cx: &mut Context,
+        levels_dbfs: L,
+        rms_dbfs: Option<R>,
+        hold_time: Option<Duration>,
+    ) -> Handle<Self>
+    where
+        L: Lens<Target = [f32; N]> + Clone,
+        R: Lens<Target = [f32; N]> + Clone,
+    {
+        Self {
+            orientation: MeterOrientation::Horizontal,
+            scale: MeterScale::default(),
+        }
+        .build(cx, move |cx| {
+            Binding::new(cx, PeakMeter::orientation, move |cx, orientation| {
+                let orientation = orientation.get(cx);
+                let levels_dbfs = levels_dbfs.clone();
+                let rms_dbfs = rms_dbfs.clone();
+
+                Binding::new(cx, PeakMeter::scale, move |cx, scale| {
+                    let scale = scale.get(cx);
+
+                    // The channels are stacked in the opposite direction of the bars
+                    HStack::new(cx, |cx| {
+                        for channel in 0..N {
+                            let level_dbfs = levels_dbfs.clone().map(move |levels| levels[channel]);
+                            let rms_dbfs = rms_dbfs
+                                .clone()
+                                .map(|rms_dbfs| rms_dbfs.map(move |levels| levels[channel]));
+
+                            Self::bar_view(
+                                cx,
+                                orientation,
+                                &scale,
+                                level_dbfs,
+                                rms_dbfs,
+                                hold_time,
+                            );
                         }
+                    })
+                    .class("bars")
+                    .layout_type(match orientation {
+                        MeterOrientation::Horizontal => LayoutType::Column,
+                        MeterOrientation::Vertical => LayoutType::Row,
+                    });
 
-                        peak_level
+                    Self::ticks_view(cx, orientation, &scale);
+                });
+            });
+        })
+        .overflow(Overflow::Visible)
+    }
+
+    /// Create the bar for a single channel.
+    fn bar_view<L, R>(
+        cx: &mut Context,
+        orientation: MeterOrientation,
+        scale: &MeterScale,
+        level_dbfs: L,
+        rms_dbfs: Option<R>,
+        hold_time: Option<Duration>,
+    ) where
+        L: Lens<Target = f32> + Clone,
+        R: Lens<Target = f32>,
+    {
+        // Now for something that may be illegal under some jurisdictions. If a hold time is
+        // given, then we'll build a new lens that always gives the held peak level for the
+        // current moment in time by mutating some values captured into the mapping closure.
+        let held_peak_value_db = Cell::new(f32::MIN);
+        let last_held_peak_value: Cell<Option<Instant>> = Cell::new(None);
+        let peak_dbfs = level_dbfs.clone().map(move |level| -> f32 {
+            match hold_time {
+                Some(hold_time) => {
+                    let mut peak_level = held_peak_value_db.get();
+                    let peak_time = last_held_peak_value.get();
+
+                    let now = Instant::now();
+                    if *level >= peak_level
+                        || peak_time.is_none()
+                        || now > peak_time.unwrap() + hold_time
+                    {
+                        peak_level = *level;
+                        held_peak_value_db.set(peak_level);
+                        last_held_peak_value.set(Some(now));
                     }
-                    None => util::MINUS_INFINITY_DB,
+
+                    peak_level
                 }
-            });
+                None => util::MINUS_INFINITY_DB,
+            }
+        });
 
-            PeakMeterBar {
+        let (min_db, max_db) = (scale.min_db, scale.max_db);
+        ZStack::new(cx, |cx| {
+            PeakMeterLayer {
                 level_dbfs,
-                peak_dbfs,
+                kind: LayerKind::Ticks,
+                orientation,
+                min_db,
+                max_db,
             }
             .build(cx, |_| {})
-            .class("bar");
-
-            ZStack::new(cx, |cx| {
-                const WIDTH_PCT: f32 = 50.0;
-                for tick_db in TEXT_TICKS {
-                    let tick_fraction = (tick_db as f32 - MIN_TICK) / (MAX_TICK - MIN_TICK);
-                    let tick_pct = tick_fraction * 100.0;
-                    // We'll shift negative numbers slightly to the left so they look more centered
-                    let needs_minus_offset = tick_db < 0;
-
-                    ZStack::new(cx, |cx| {
-                        let first_tick = tick_db == TEXT_TICKS[0];
-                        let last_tick = tick_db == TEXT_TICKS[TEXT_TICKS.len() - 1];
-
-                        if !last_tick {
-                            // FIXME: This is not aligned to the pixel grid and some ticks will look
-                            //        blurry, is there a way to fix this?
-                            Element::new(cx).class("ticks__tick");
-                        }
+            .class("bar__peak");
 
-                        let font_size = {
-                            let event_cx = EventContext::new(cx);
-                            event_cx.font_size() * event_cx.scale_factor()
-                        };
-                        let label = if first_tick {
-                            Label::new(cx, "-inf")
-                                .class("ticks__label")
-                                .class("ticks__label--inf")
-                        } else if last_tick {
-                            // This is only inclued in the array to make positioning this easier
-                            Label::new(cx, "dBFS")
-                                .class("ticks__label")
-                                .class("ticks__label--dbfs")
-                        } else {
-                            Label::new(cx, &tick_db.to_string()).class("ticks__label")
-                        }
+            if let Some(rms_dbfs) = rms_dbfs {
+                PeakMeterLayer {
+                    level_dbfs: rms_dbfs,
+                    kind: LayerKind::Ticks,
+                    orientation,
+                    min_db,
+                    max_db,
+                }
+                .build(cx, |_| {})
+                .class("bar__rms");
+            }
+
+            PeakMeterLayer {
+                level_dbfs: peak_dbfs,
+                kind: LayerKind::Line,
+                orientation,
+                min_db,
+                max_db,
+            }
+            .build(cx, |_| {})
+            .class("bar__hold");
+        })
+        .class("bar");
+    }
+
+    /// Create the labeled ticks next to the bars.
+    fn ticks_view(cx: &mut Context, orientation: MeterOrientation, scale: &MeterScale) {
+        ZStack::new(cx, |cx| {
+            const SIZE_PCT: f32 = 50.0;
+            for (idx, tick) in scale.ticks.iter().enumerate() {
+                let tick_pct = scale.fraction(tick.db) * 100.0;
+                // We'll shift negative numbers slightly to the left so they look more centered
+                let needs_minus_offset =
+                    orientation == MeterOrientation::Horizontal && tick.label.starts_with('-');
+                let last_tick = idx == scale.ticks.len() - 1;
+
+                let tick_stack = ZStack::new(cx, |cx| {
+                    if !last_tick {
+                        // FIXME: This is not aligned to the pixel grid and some ticks will look
+                        //        blurry, is there a way to fix this?
+                        Element::new(cx).class("ticks__tick");
+                    }
+
+                    let font_size = {
+                        let event_cx = EventContext::new(cx);
+                        event_cx.font_size() * event_cx.scale_factor()
+                    };
+                    let label = Label::new(cx, &tick.label)
+                        .class("ticks__label")
                         .overflow(Overflow::Visible);
 
-                        if needs_minus_offset {
-                            label.child_right(Pixels(font_size * 0.15));
-                        }
-                    })
-                    .height(Stretch(1.0))
-                    .left(Percentage(tick_pct - (WIDTH_PCT / 2.0)))
-                    .width(Percentage(WIDTH_PCT))
-                    .child_left(Stretch(1.0))
-                    .child_right(Stretch(1.0))
-                    .overflow(Overflow::Visible);
-                }
-            })
-            .class("ticks")
-            .overflow(Overflow::Visible);
+                    if needs_minus_offset {
+                        label.child_right(Pixels(font_size * 0.15));
+                    }
+                })
+                .overflow(Overflow::Visible);
+
+                match orientation {
+                    MeterOrientation::Horizontal => tick_stack
+                        .height(Stretch(1.0))
+                        .left(Percentage(tick_pct - (SIZE_PCT / 2.0)))
+                        .width(Percentage(SIZE_PCT))
+                        .child_left(Stretch(1.0))
+                        .child_right(Stretch(1.0)),
+                    MeterOrientation::Vertical => tick_stack
+                        .width(Stretch(1.0))
+                        .top(Percentage((100.0 - tick_pct) - (SIZE_PCT / 2.0)))
+                        .height(Percentage(SIZE_PCT))
+                        .child_top(Stretch(1.0))
+                        .child_bottom(Stretch(1.0)),
+                };
+            }
         })
-        .overflow(Overflow::Visible)
+        .class("ticks")
+        .overflow(Overflow::Visible);
     }
 }
 
@@ -139,108 +375,144 @@ impl View for PeakMeter {
     }
 }
 
-impl<L, P> View for PeakMeterBar<L, P>
+impl<L> View for PeakMeterLayer<L>
 where
     L: Lens<Target = f32>,
-    P: Lens<Target = f32>,
 {
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
         let level_dbfs = self.level_dbfs.get(cx);
-        let peak_dbfs = self.peak_dbfs.get(cx);
 
-        // These basics are taken directly from the default implementation of this function
         let bounds = cx.bounds();
         if bounds.w == 0.0 || bounds.h == 0.0 {
             return;
         }
 
-        // TODO: It would be cool to allow the text color property to control the gradient here. For
-        //       now we'll only support basic background colors and borders.
-        let background_color = cx.background_color();
-        let border_color = cx.border_color();
         let opacity = cx.opacity();
-        let mut background_color: vg::Color = background_color.into();
-        background_color.set_alphaf(background_color.a * opacity);
-        let mut border_color: vg::Color = border_color.into();
-        border_color.set_alphaf(border_color.a * opacity);
-        let border_width = cx.border_width();
-
-        let mut path = vg::Path::new();
-        {
-            let x = bounds.x + border_width / 2.0;
-            let y = bounds.y + border_width / 2.0;
-            let w = bounds.w - border_width;
-            let h = bounds.h - border_width;
-            path.move_to(x, y);
-            path.line_to(x, y + h);
-            path.line_to(x + w, y + h);
-            path.line_to(x + w, y);
-            path.line_to(x, y);
-            path.close();
+        let mut start_color: vg::Color = cx.font_color().into();
+        start_color.set_alphaf(start_color.a * opacity);
+        // The gradient is optional, a transparent outline color results in a solid color
+        let mut end_color: vg::Color = cx.outline_color().into();
+        if end_color.a == 0.0 {
+            end_color = start_color;
+        } else {
+            end_color.set_alphaf(end_color.a * opacity);
         }
 
-        // Fill with background color
-        let paint = vg::Paint::color(background_color);
-        canvas.fill_path(&path, &paint);
-
-        // And now for the fun stuff. We'll try to not overlap the border, but we'll draw that last
-        // just in case.
+        // The layers are drawn on top of the bar's background and border, so we'll try not to
+        // overlap the border
+        let border_width = cx.border_width();
         let bar_bounds = bounds.shrink(border_width / 2.0);
-        let bar_ticks_start_x = bar_bounds.left().floor() as i32;
-        let bar_ticks_end_x = bar_bounds.right().ceil() as i32;
+        let (bar_start, bar_end) = match self.orientation {
+            MeterOrientation::Horizontal => (
+                bar_bounds.left().floor() as i32,
+                bar_bounds.right().ceil() as i32,
+            ),
+            // Vertical bars grow upwards
+            MeterOrientation::Vertical => (
+                bar_bounds.bottom().ceil() as i32,
+                bar_bounds.top().floor() as i32,
+            ),
+        };
+        let bar_length = (bar_end - bar_start).abs() as f32;
 
         // NOTE: We'll scale this with the nearest integer DPI ratio. That way it will still look
         //       good at 2x scaling, and it won't look blurry at 1.x times scaling.
         let dpi_scale = cx.logical_to_physical(1.0).floor().max(1.0);
-        let bar_tick_coordinates = (bar_ticks_start_x..bar_ticks_end_x)
-            .step_by(((TICK_WIDTH + TICK_GAP) * dpi_scale).round() as usize);
-        for tick_x in bar_tick_coordinates {
-            let tick_fraction =
-                (tick_x - bar_ticks_start_x) as f32 / (bar_ticks_end_x - bar_ticks_start_x) as f32;
-            let tick_db = (tick_fraction * (MAX_TICK - MIN_TICK)) + MIN_TICK;
-            if tick_db > level_dbfs {
-                break;
-            }
 
-            // femtovg draws paths centered on these coordinates, so in order to be pixel perfect we
-            // need to account for that. Otherwise the ticks will be 2px wide instead of 1px.
+        // femtovg draws paths centered on these coordinates, so in order to be pixel perfect we
+        // need to account for that. Otherwise the ticks will be 2px wide instead of 1px.
+        let line_path = |position: f32| {
             let mut path = vg::Path::new();
-            path.move_to(tick_x as f32 + (dpi_scale / 2.0), bar_bounds.top());
-            path.line_to(tick_x as f32 + (dpi_scale / 2.0), bar_bounds.bottom());
-
-            let grayscale_color = 0.3 + ((1.0 - tick_fraction) * 0.5);
-            let mut paint = vg::Paint::color(vg::Color::rgbaf(
-                grayscale_color,
-                grayscale_color,
-                grayscale_color,
-                opacity,
-            ));
-            paint.set_line_width(TICK_WIDTH * dpi_scale);
-            canvas.stroke_path(&path, &paint);
-        }
-
-        // Draw the hold peak value if the hold time option has been set
-        let db_to_x_coord = |db: f32| {
-            let tick_fraction = (db - MIN_TICK) / (MAX_TICK - MIN_TICK);
-            bar_ticks_start_x as f32
-                + ((bar_ticks_end_x - bar_ticks_start_x) as f32 * tick_fraction).round()
+            match self.orientation {
+                MeterOrientation::Horizontal => {
+                    path.move_to(position + (dpi_scale / 2.0), bar_bounds.top());
+                    path.line_to(position + (dpi_scale / 2.0), bar_bounds.bottom());
+                }
+                MeterOrientation::Vertical => {
+                    path.move_to(bar_bounds.left(), position - (dpi_scale / 2.0));
+                    path.line_to(bar_bounds.right(), position - (dpi_scale / 2.0));
+                }
+            }
+            path
         };
-        if (MIN_TICK..MAX_TICK).contains(&peak_dbfs) {
-            // femtovg draws paths centered on these coordinates, so in order to be pixel perfect we
-            // need to account for that. Otherwise the ticks will be 2px wide instead of 1px.
-            let peak_x = db_to_x_coord(peak_dbfs);
-            let mut path = vg::Path::new();
-            path.move_to(peak_x + (dpi_scale / 2.0), bar_bounds.top());
-            path.line_to(peak_x + (dpi_scale / 2.0), bar_bounds.bottom());
+        let db_range = self.max_db - self.min_db;
 
-            let mut paint = vg::Paint::color(vg::Color::rgbaf(0.3, 0.3, 0.3, opacity));
-            paint.set_line_width(TICK_WIDTH * dpi_scale);
-            canvas.stroke_path(&path, &paint);
+        match self.kind {
+            LayerKind::Ticks => {
+                let tick_step = ((TICK_WIDTH + TICK_GAP) * dpi_scale).round() as usize;
+                for tick_offset in (0..bar_length as usize).step_by(tick_step.max(1)) {
+                    let tick_fraction = tick_offset as f32 / bar_length;
+                    let tick_db = (tick_fraction * db_range) + self.min_db;
+                    if tick_db > level_dbfs {
+                        break;
+                    }
+
+                    let position = match self.orientation {
+                        MeterOrientation::Horizontal => (bar_start + tick_offset as i32) as f32,
+                        MeterOrientation::Vertical => (bar_start - tick_offset as i32) as f32,
+                    };
+                    let color = vg::Color::rgbaf(
+                        start_color.r + ((end_color.r - start_color.r) * tick_fraction),
+                        start_color.g + ((end_color.g - start_color.g) * tick_fraction),
+                        start_color.b + ((end_color.b - start_color.b) * tick_fraction),
+                        start_color.a + ((end_color.a - start_color.a) * tick_fraction),
+                    );
+                    let mut paint = vg::Paint::color(color);
+                    paint.set_line_width(TICK_WIDTH * dpi_scale);
+                    canvas.stroke_path(&line_path(position), &paint);
+                }
+            }
+            LayerKind::Line => {
+                // This is used to draw the hold peak value if the hold time option has been set
+                if (self.min_db..self.max_db).contains(&level_dbfs) {
+                    let tick_fraction = (level_dbfs - self.min_db) / db_range;
+                    let offset = (bar_length * tick_fraction).round();
+                    let position = match self.orientation {
+                        MeterOrientation::Horizontal => bar_start as f32 + offset,
+                        MeterOrientation::Vertical => bar_start as f32 - offset,
+                    };
+
+                    let mut paint = vg::Paint::color(start_color);
+                    paint.set_line_width(TICK_WIDTH * dpi_scale);
+                    canvas.stroke_path(&line_path(position), &paint);
+                }
+            }
         }
+    }
+}
+
+/// Extension methods for [`PeakMeter`] handles.
+pub trait PeakMeterExt {
+    /// Change the direction the meter's bars are drawn in. Vertical meters get the `vertical`
+    /// class so they can be sized differently in CSS.
+    fn orientation(self, orientation: MeterOrientation) -> Self;
+
+    /// Change the decibel values at the start and the end of the bar.
+    fn range(self, min_db: f32, max_db: f32) -> Self;
+
+    /// Replace the ticks shown next to the bar.
+    fn ticks(self, ticks: impl IntoIterator<Item = MeterTick>) -> Self;
+}
+
+impl PeakMeterExt for Handle<'_, PeakMeter> {
+    fn orientation(self, orientation: MeterOrientation) -> Self {
+        self.modify(|peak_meter: &mut PeakMeter| peak_meter.orientation = orientation)
+            .toggle_class("vertical", orientation == MeterOrientation::Vertical)
+            .layout_type(match orientation {
+                MeterOrientation::Horizontal => LayoutType::Column,
+                MeterOrientation::Vertical => LayoutType::Row,
+            })
+    }
+
+    fn range(self, min_db: f32, max_db: f32) -> Self {
+        self.modify(|peak_meter: &mut PeakMeter| {
+            peak_meter.scale.min_db = min_db;
+            peak_meter.scale.max_db = max_db;
+        })
+    }
 
-        // Draw border last
-        let mut paint = vg::Paint::color(border_color);
-        paint.set_line_width(border_width);
-        canvas.stroke_path(&path, &paint);
+    fn ticks(self, ticks: impl IntoIterator<Item = MeterTick>) -> Self {
+        let ticks = ticks.into_iter().collect();
+        self.modify(|peak_meter: &mut PeakMeter| peak_meter.scale.ticks = ticks)
     }
 }
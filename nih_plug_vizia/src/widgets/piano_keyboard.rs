@@ -0,0 +1,336 @@
+//! An on-screen piano keyboard that can be played with the mouse and the computer keyboard.
+
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+use vizia::prelude::*;
+use vizia::vg;
+
+/// The height of the black keys as a fraction of the keyboard's height.
+const BLACK_KEY_HEIGHT: f32 = 0.6;
+/// The width of the black keys as a fraction of the white keys' width.
+const BLACK_KEY_WIDTH: f32 = 0.6;
+/// The lowest velocity that can be played by clicking the top of a key.
+const MIN_VELOCITY: f32 = 0.1;
+
+/// The computer keyboard's keys that play notes, and their offsets from the current octave's C.
+/// This mimics the layout of a piano on the home row.
+const NOTE_KEYS: [(Code, u8); 15] = [
+    (Code::KeyA, 0),
+    (Code::KeyW, 1),
+    (Code::KeyS, 2),
+    (Code::KeyE, 3),
+    (Code::KeyD, 4),
+    (Code::KeyF, 5),
+    (Code::KeyT, 6),
+    (Code::KeyG, 7),
+    (Code::KeyY, 8),
+    (Code::KeyH, 9),
+    (Code::KeyU, 10),
+    (Code::KeyJ, 11),
+    (Code::KeyK, 12),
+    (Code::KeyO, 13),
+    (Code::KeyL, 14),
+];
+/// The velocity used for notes played with the computer keyboard.
+const KEY_VELOCITY: f32 = 0.8;
+
+/// A piano keyboard. Clicking a key plays a note with a velocity that depends on how far down the
+/// key was clicked, and dragging across the keys plays the notes under the mouse cursor. After the
+/// keyboard has been clicked, the computer keyboard's home row also plays notes starting with C on
+/// the A key. Z and X move those notes down and up an octave. Use the methods on
+/// [`PianoKeyboardExt`] to handle the played notes.
+///
+/// The white keys are filled with the `background-color`, the black keys are filled with the
+/// `color`, and the lines between the white keys use the `border-color`. Notes that are held, either
+/// according to the `held_notes` lens or because they're currently being played from this keyboard,
+/// are filled with the `outline-color`.
+pub struct PianoKeyboard<L: Lens<Target = u128>> {
+    /// A bit mask containing the notes that should be highlighted.
+    held_notes: L,
+    notes: RangeInclusive<u8>,
+
+    /// Whether the left mouse button is held down on the keyboard.
+    mouse_down: bool,
+    /// The note that's being played with the mouse.
+    mouse_note: Option<u8>,
+    /// The computer keyboard's keys that are currently held down, and the notes they're playing.
+    /// This is needed to ignore key repeats and to release the right notes after changing octaves.
+    held_keys: HashSet<(Code, u8)>,
+    /// The MIDI note number of the C played by the A key.
+    key_octave_start: u8,
+
+    on_note_on: Option<Box<dyn Fn(&mut EventContext, u8, f32)>>,
+    on_note_off: Option<Box<dyn Fn(&mut EventContext, u8)>>,
+}
+
+/// The position of a key within the keyboard, in physical pixels.
+struct KeyRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+fn is_black_key(note: u8) -> bool {
+    matches!(note % 12, 1 | 3 | 6 | 8 | 10)
+}
+
+impl KeyRect {
+    fn contains(&self, x: f32, y: f32) -> bool {
+        (self.x..self.x + self.w).contains(&x) && (self.y..self.y + self.h).contains(&y)
+    }
+}
+
+impl<L: Lens<Target = u128>> PianoKeyboard<L> {
+    /// Creates a new [`PianoKeyboard`] for a range of MIDI notes. The range should start and end on
+    /// white keys. `held_notes` contains a bit for every MIDI note that should be highlighted.
+    pub fn new(cx: &mut Context, notes: RangeInclusive<u8>, held_notes: L) -> Handle<Self> {
+        Self {
+            held_notes,
+            notes,
+
+            mouse_down: false,
+            mouse_note: None,
+            held_keys: HashSet::new(),
+            key_octave_start: 60,
+
+            on_note_on: None,
+            on_note_off: None,
+        }
+        .build(cx, |_| {})
+        .focusable(true)
+    }
+
+    /// The number of white keys.
+    fn num_white_keys(&self) -> usize {
+        self.notes
+            .clone()
+            .filter(|note| !is_black_key(*note))
+            .count()
+    }
+
+    /// The key's position within `bounds`.
+    fn key_rect(&self, bounds: BoundingBox, note: u8) -> KeyRect {
+        let white_key_width = bounds.w / self.num_white_keys().max(1) as f32;
+        // The number of white keys to the left of this key
+        let white_keys_before = (*self.notes.start()..note)
+            .filter(|note| !is_black_key(*note))
+            .count() as f32;
+
+        if is_black_key(note) {
+            let width = white_key_width * BLACK_KEY_WIDTH;
+            KeyRect {
+                x: bounds.x + (white_keys_before * white_key_width) - (width / 2.0),
+                y: bounds.y,
+                w: width,
+                h: bounds.h * BLACK_KEY_HEIGHT,
+            }
+        } else {
+            KeyRect {
+                x: bounds.x + (white_keys_before * white_key_width),
+                y: bounds.y,
+                w: white_key_width,
+                h: bounds.h,
+            }
+        }
+    }
+
+    /// The note under the mouse cursor and its velocity, if there is one. The black keys are on top
+    /// of the white keys.
+    fn note_under_cursor(&self, cx: &EventContext) -> Option<(u8, f32)> {
+        let bounds = cx.bounds();
+        let (x, y) = (cx.mouse().cursorx, cx.mouse().cursory);
+
+        let black_keys = self.notes.clone().filter(|note| is_black_key(*note));
+        let white_keys = self.notes.clone().filter(|note| !is_black_key(*note));
+        black_keys.chain(white_keys).find_map(|note| {
+            let rect = self.key_rect(bounds, note);
+            rect.contains(x, y).then(|| {
+                let velocity = ((y - rect.y) / rect.h).clamp(MIN_VELOCITY, 1.0);
+                (note, velocity)
+            })
+        })
+    }
+
+    fn note_on(&self, cx: &mut EventContext, note: u8, velocity: f32) {
+        if let Some(callback) = &self.on_note_on {
+            (callback)(cx, note, velocity);
+        }
+        cx.needs_redraw();
+    }
+
+    fn note_off(&self, cx: &mut EventContext, note: u8) {
+        if let Some(callback) = &self.on_note_off {
+            (callback)(cx, note);
+        }
+        cx.needs_redraw();
+    }
+
+    /// Whether a note is currently being played from this keyboard.
+    fn is_playing(&self, note: u8) -> bool {
+        self.mouse_note == Some(note)
+            || self
+                .held_keys
+                .iter()
+                .any(|(_, held_note)| *held_note == note)
+    }
+}
+
+impl<L: Lens<Target = u128>> View for PianoKeyboard<L> {
+    fn element(&self) -> Option<&'static str> {
+        Some("piano-keyboard")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseDoubleClick(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                cx.capture();
+                // NOTE: Otherwise we don't get key events
+                cx.focus();
+                cx.set_active(true);
+                self.mouse_down = true;
+
+                if let Some((note, velocity)) = self.note_under_cursor(cx) {
+                    self.mouse_note = Some(note);
+                    self.note_on(cx, note, velocity);
+                }
+
+                meta.consume();
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                cx.release();
+                cx.set_active(false);
+                self.mouse_down = false;
+
+                if let Some(note) = self.mouse_note.take() {
+                    self.note_off(cx, note);
+                }
+
+                meta.consume();
+            }
+            WindowEvent::MouseMove(_, _) => {
+                // Dragging across the keys plays the notes under the cursor
+                if self.mouse_down {
+                    let new_note = self.note_under_cursor(cx);
+                    if new_note.map(|(note, _)| note) != self.mouse_note {
+                        if let Some(note) = self.mouse_note.take() {
+                            self.note_off(cx, note);
+                        }
+                        if let Some((note, velocity)) = new_note {
+                            self.mouse_note = Some(note);
+                            self.note_on(cx, note, velocity);
+                        }
+                    }
+                }
+            }
+            WindowEvent::KeyDown(code, _) => {
+                if let Some((_, offset)) = NOTE_KEYS.iter().find(|(key, _)| key == code) {
+                    let note = (self.key_octave_start + offset).min(127);
+                    // Key repeats should not retrigger the note
+                    if !self.held_keys.iter().any(|(key, _)| key == code) {
+                        self.held_keys.insert((*code, note));
+                        self.note_on(cx, note, KEY_VELOCITY);
+                    }
+
+                    meta.consume();
+                } else if *code == Code::KeyZ {
+                    self.key_octave_start = self.key_octave_start.saturating_sub(12);
+                    meta.consume();
+                } else if *code == Code::KeyX {
+                    self.key_octave_start = (self.key_octave_start + 12).min(108);
+                    meta.consume();
+                }
+            }
+            WindowEvent::KeyUp(code, _) => {
+                if let Some(&(key, note)) = self.held_keys.iter().find(|(key, _)| key == code) {
+                    self.held_keys.remove(&(key, note));
+                    self.note_off(cx, note);
+
+                    meta.consume();
+                }
+            }
+            WindowEvent::FocusOut => {
+                // The key up events would otherwise get lost
+                for (_, note) in std::mem::take(&mut self.held_keys) {
+                    self.note_off(cx, note);
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let held_notes = self.held_notes.get(cx);
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let opacity = cx.opacity();
+        let mut white_color: vg::Color = cx.background_color().into();
+        white_color.set_alphaf(white_color.a * opacity);
+        let mut black_color: vg::Color = cx.font_color().into();
+        black_color.set_alphaf(black_color.a * opacity);
+        let mut held_color: vg::Color = cx.outline_color().into();
+        held_color.set_alphaf(held_color.a * opacity);
+        let mut border_color: vg::Color = cx.border_color().into();
+        border_color.set_alphaf(border_color.a * opacity);
+        let border_width = cx.border_width().max(cx.logical_to_physical(1.0));
+
+        let is_held = |note: u8| held_notes & (1 << note) != 0 || self.is_playing(note);
+        let white_keys = self.notes.clone().filter(|note| !is_black_key(*note));
+        let black_keys = self.notes.clone().filter(|note| is_black_key(*note));
+        for note in white_keys.chain(black_keys) {
+            let rect = self.key_rect(bounds, note);
+            let mut path = vg::Path::new();
+            path.rect(rect.x, rect.y, rect.w, rect.h);
+
+            let fill_color = match (is_held(note), is_black_key(note)) {
+                (true, _) => held_color,
+                (false, true) => black_color,
+                (false, false) => white_color,
+            };
+            canvas.fill_path(&path, &vg::Paint::color(fill_color));
+
+            let mut paint = vg::Paint::color(border_color);
+            paint.set_line_width(border_width);
+            canvas.stroke_path(&path, &paint);
+        }
+    }
+}
+
+/// Extension methods for [`PianoKeyboard`] handles.
+pub trait PianoKeyboardExt {
+    /// Called with the note number and a velocity between 0 and 1 when a note is played.
+    fn on_note_on<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, u8, f32);
+
+    /// Called with the note number when a note is released.
+    fn on_note_off<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, u8);
+}
+
+impl<L: Lens<Target = u128>> PianoKeyboardExt for Handle<'_, PianoKeyboard<L>> {
+    fn on_note_on<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, u8, f32),
+    {
+        self.modify(|keyboard: &mut PianoKeyboard<L>| {
+            keyboard.on_note_on = Some(Box::new(callback))
+        })
+    }
+
+    fn on_note_off<F>(self, callback: F) -> Self
+    where
+        F: 'static + Fn(&mut EventContext, u8),
+    {
+        self.modify(|keyboard: &mut PianoKeyboard<L>| {
+            keyboard.on_note_off = Some(Box::new(callback))
+        })
+    }
+}
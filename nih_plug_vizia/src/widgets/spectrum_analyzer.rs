@@ -0,0 +1,164 @@
+//! A spectrum analyzer with a logarithmic frequency axis.
+
+use vizia::prelude::*;
+use vizia::vg;
+
+/// The frequency at the left edge of the analyzer.
+const MIN_FREQUENCY: f32 = 20.0;
+/// The frequency at the right edge of the analyzer.
+const MAX_FREQUENCY: f32 = 20_000.0;
+/// The decibel value at the bottom of the analyzer.
+const MIN_DB: f32 = -90.0;
+/// The decibel value at the top of the analyzer.
+const MAX_DB: f32 = 6.0;
+/// The frequencies that get a grid line and a label.
+const GRID_FREQUENCIES: [(f32, &str); 3] = [(100.0, "100"), (1_000.0, "1k"), (10_000.0, "10k")];
+
+/// The thickness of the spectrum's lines in logical pixels.
+const LINE_WIDTH: f32 = 1.5;
+
+/// The data shown by a [`SpectrumAnalyzer`]. Both vectors contain one value in decibels for every
+/// bin of a real FFT, so bin `i` is centered on `i * sample_rate / ((len - 1) * 2)` Hz.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spectrum {
+    pub sample_rate: f32,
+    /// The (averaged) magnitude of every bin.
+    pub magnitudes_db: Vec<f32>,
+    /// The held peak magnitude of every bin.
+    pub peaks_db: Vec<f32>,
+}
+
+/// Draws a [`Spectrum`] between 20 Hz and 20 kHz with a logarithmic frequency axis. The analysis,
+/// averaging, and peak hold are up to the spectrum's producer.
+///
+/// The magnitudes are drawn as a `.spectrum` filled with its `background-color` and stroked with
+/// its `color`, the peaks are drawn as a line using the spectrum's `outline-color`, and the grid
+/// lines use its `border-color`. The frequency labels are `.spectrum-analyzer__label` elements.
+pub struct SpectrumAnalyzer;
+
+/// The manually drawn graph inside of a [`SpectrumAnalyzer`].
+struct SpectrumGraph<L: Lens<Target = Spectrum>> {
+    spectrum: L,
+}
+
+/// Map a frequency to a `[0, 1]` fraction of the analyzer's width.
+fn frequency_to_t(frequency: f32) -> f32 {
+    (frequency / MIN_FREQUENCY).ln() / (MAX_FREQUENCY / MIN_FREQUENCY).ln()
+}
+
+/// Map a decibel value to a `[0, 1]` fraction of the analyzer's height, measured from the bottom.
+fn db_to_t(db: f32) -> f32 {
+    ((db - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0)
+}
+
+impl SpectrumAnalyzer {
+    /// Creates a new [`SpectrumAnalyzer`] for the given spectrum.
+    pub fn new<L>(cx: &mut Context, spectrum: L) -> Handle<Self>
+    where
+        L: Lens<Target = Spectrum>,
+    {
+        Self.build(cx, |cx| {
+            SpectrumGraph { spectrum }
+                .build(cx, |_| {})
+                .class("spectrum")
+                .hoverable(false);
+
+            for (frequency, label) in GRID_FREQUENCIES {
+                Label::new(cx, label)
+                    .class("spectrum-analyzer__label")
+                    .position_type(PositionType::SelfDirected)
+                    .left(Percentage(frequency_to_t(frequency) * 100.0))
+                    .hoverable(false);
+            }
+        })
+    }
+}
+
+impl View for SpectrumAnalyzer {
+    fn element(&self) -> Option<&'static str> {
+        Some("spectrum-analyzer")
+    }
+}
+
+impl<L: Lens<Target = Spectrum>> View for SpectrumGraph<L> {
+    fn element(&self) -> Option<&'static str> {
+        Some("spectrum")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let spectrum = self.spectrum.get(cx);
+
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let opacity = cx.opacity();
+        let mut fill_color: vg::Color = cx.background_color().into();
+        fill_color.set_alphaf(fill_color.a * opacity);
+        let mut line_color: vg::Color = cx.font_color().into();
+        line_color.set_alphaf(line_color.a * opacity);
+        let mut peak_color: vg::Color = cx.outline_color().into();
+        peak_color.set_alphaf(peak_color.a * opacity);
+        let mut grid_color: vg::Color = cx.border_color().into();
+        grid_color.set_alphaf(grid_color.a * opacity);
+        let dpi_scale = cx.logical_to_physical(1.0);
+
+        let mut grid = vg::Path::new();
+        for (frequency, _) in GRID_FREQUENCIES {
+            let x = (bounds.x + frequency_to_t(frequency) * bounds.w).round() + 0.5;
+            grid.move_to(x, bounds.top());
+            grid.line_to(x, bounds.bottom());
+        }
+        let mut paint = vg::Paint::color(grid_color);
+        paint.set_line_width(dpi_scale);
+        canvas.stroke_path(&grid, &paint);
+
+        // The first bin is DC, which can't be shown on a logarithmic axis
+        let num_bins = spectrum.magnitudes_db.len();
+        if num_bins < 3 || spectrum.sample_rate <= 0.0 {
+            return;
+        }
+        let bin_frequency = spectrum.sample_rate / ((num_bins - 1) * 2) as f32;
+        // Bins above the maximum frequency are not drawn, except for the first one above it so the line
+        // reaches the right edge
+        let last_bin = ((MAX_FREQUENCY / bin_frequency).ceil() as usize).min(num_bins - 1);
+        let to_coordinates = |bin: usize, db: f32| {
+            let frequency_t = frequency_to_t(bin as f32 * bin_frequency).clamp(0.0, 1.0);
+            (
+                bounds.x + frequency_t * bounds.w,
+                bounds.bottom() - db_to_t(db) * bounds.h,
+            )
+        };
+        let line_path = |values_db: &[f32]| {
+            let mut path = vg::Path::new();
+            for (bin, db) in values_db.iter().enumerate().take(last_bin + 1).skip(1) {
+                let (x, y) = to_coordinates(bin, *db);
+                if bin == 1 {
+                    path.move_to(x, y);
+                } else {
+                    path.line_to(x, y);
+                }
+            }
+            path
+        };
+
+        let path = line_path(&spectrum.magnitudes_db);
+        let mut fill_path = path.clone();
+        fill_path.line_to(to_coordinates(last_bin, MIN_DB).0, bounds.bottom());
+        fill_path.line_to(to_coordinates(1, MIN_DB).0, bounds.bottom());
+        fill_path.close();
+        canvas.fill_path(&fill_path, &vg::Paint::color(fill_color));
+
+        let mut paint = vg::Paint::color(line_color);
+        paint.set_line_width(LINE_WIDTH * dpi_scale);
+        paint.set_line_join(vg::LineJoin::Round);
+        canvas.stroke_path(&path, &paint);
+
+        if spectrum.peaks_db.len() == num_bins {
+            let mut paint = vg::Paint::color(peak_color);
+            paint.set_line_width(dpi_scale);
+            canvas.stroke_path(&line_path(&spectrum.peaks_db), &paint);
+        }
+    }
+}
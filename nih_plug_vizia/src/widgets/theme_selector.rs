@@ -0,0 +1,85 @@
+//! A button for switching between the editor's themes.
+
+use vizia::prelude::*;
+
+use crate::theme::{Theme, ThemeEvent, ThemeModel};
+
+/// A small button that shows the name of the active [`Theme`]. Left clicking or scrolling up
+/// switches to the next theme, and right clicking or scrolling down switches to the previous one.
+/// The built-in themes come first, followed by the user themes passed to
+/// [`new()`][Self::new()].
+pub struct ThemeSelector {
+    /// The themes the selector cycles through.
+    themes: Vec<Theme>,
+
+    /// The number of (fractional) scrolled lines that have not yet been turned into theme
+    /// changes. This is needed to support trackpads with smooth scrolling.
+    scrolled_lines: f32,
+}
+
+impl ThemeSelector {
+    /// Create a new theme selector. `user_themes` should contain the themes returned by
+    /// [`load_user_themes()`][crate::theme::load_user_themes()], or be empty.
+    pub fn new(cx: &mut Context, user_themes: Vec<Theme>) -> Handle<Self> {
+        Self {
+            themes: Theme::BUILTIN.into_iter().chain(user_themes).collect(),
+
+            scrolled_lines: 0.0,
+        }
+        .build(cx, |cx| {
+            Label::new(cx, ThemeModel::theme.map(|theme| theme.name().to_owned())).hoverable(false);
+        })
+    }
+
+    /// Switch to the theme `steps` themes after or before the active one, wrapping around at the
+    /// ends. If the active theme is not in the list, for instance because its user theme has been
+    /// removed, then this starts from the first theme.
+    fn step_theme(&self, cx: &mut EventContext, steps: i32) {
+        let current_theme = ThemeModel::theme.get(cx);
+        let current_idx = self
+            .themes
+            .iter()
+            .position(|theme| *theme == current_theme)
+            .unwrap_or(0);
+        let new_idx = (current_idx as i32 + steps).rem_euclid(self.themes.len() as i32) as usize;
+
+        cx.emit(ThemeEvent::SetTheme(self.themes[new_idx].clone()));
+    }
+}
+
+impl View for ThemeSelector {
+    fn element(&self) -> Option<&'static str> {
+        Some("theme-selector")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseDoubleClick(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                self.step_theme(cx, 1);
+                meta.consume();
+            }
+            WindowEvent::MouseDown(MouseButton::Right)
+            | WindowEvent::MouseDoubleClick(MouseButton::Right)
+            | WindowEvent::MouseTripleClick(MouseButton::Right) => {
+                self.step_theme(cx, -1);
+                meta.consume();
+            }
+            WindowEvent::MouseScroll(_scroll_x, scroll_y) => {
+                // With a regular scroll wheel `scroll_y` will only ever be -1 or 1, but with smooth
+                // scrolling trackpads being a thing `scroll_y` could be anything.
+                self.scrolled_lines += scroll_y;
+
+                let steps = self.scrolled_lines.trunc();
+                if steps != 0.0 {
+                    self.step_theme(cx, steps as i32);
+                    self.scrolled_lines -= steps;
+                }
+
+                meta.consume();
+            }
+            _ => {}
+        });
+    }
+}
@@ -0,0 +1,101 @@
+//! A button for switching between preset UI scale factors.
+
+use vizia::prelude::*;
+
+use super::WindowModel;
+
+/// The scale factors the [`ZoomSelector`] steps through.
+pub const ZOOM_LEVELS: [f64; 6] = [0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+
+/// A small button that shows the GUI's current scale factor as a percentage and steps through the
+/// [`ZOOM_LEVELS`]. Left clicking or scrolling up selects the next larger zoom level, and right
+/// clicking or scrolling down selects the next smaller one. This works together with the
+/// [`ResizeHandle`][super::ResizeHandle], and the scale factor is stored in the
+/// [`ViziaState`][crate::ViziaState] either way.
+pub struct ZoomSelector {
+    /// The number of (fractional) scrolled lines that have not yet been turned into zoom level
+    /// changes. This is needed to support trackpads with smooth scrolling.
+    scrolled_lines: f32,
+}
+
+impl ZoomSelector {
+    /// Create a new zoom selector. This is usually placed next to the
+    /// [`ResizeHandle`][super::ResizeHandle].
+    pub fn new(cx: &mut Context) -> Handle<Self> {
+        Self {
+            scrolled_lines: 0.0,
+        }
+        .build(cx, |cx| {
+            Label::new(
+                cx,
+                WindowModel::vizia_state
+                    .map(|vizia_state| format!("{:.0}%", vizia_state.user_scale_factor() * 100.0)),
+            )
+            .hoverable(false);
+        })
+    }
+
+    /// Step `steps` zoom levels up or down from the current scale factor. If the window was resized
+    /// to a scale factor in between two zoom levels, then the nearest level in that direction is
+    /// selected.
+    fn step_zoom_level(&self, cx: &mut EventContext, steps: i32) {
+        let current_scale_factor = cx.user_scale_factor();
+        let mut new_scale_factor = current_scale_factor;
+        for _ in 0..steps.abs() {
+            new_scale_factor = if steps > 0 {
+                ZOOM_LEVELS
+                    .into_iter()
+                    .find(|level| *level > new_scale_factor + 1e-3)
+                    .unwrap_or(new_scale_factor)
+            } else {
+                ZOOM_LEVELS
+                    .into_iter()
+                    .rev()
+                    .find(|level| *level < new_scale_factor - 1e-3)
+                    .unwrap_or(new_scale_factor)
+            };
+        }
+
+        // The `WindowModel` takes care of resizing the window and storing the new scale factor
+        if new_scale_factor != current_scale_factor {
+            cx.set_user_scale_factor(new_scale_factor);
+        }
+    }
+}
+
+impl View for ZoomSelector {
+    fn element(&self) -> Option<&'static str> {
+        Some("zoom-selector")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, meta| match window_event {
+            WindowEvent::MouseDown(MouseButton::Left)
+            | WindowEvent::MouseDoubleClick(MouseButton::Left)
+            | WindowEvent::MouseTripleClick(MouseButton::Left) => {
+                self.step_zoom_level(cx, 1);
+                meta.consume();
+            }
+            WindowEvent::MouseDown(MouseButton::Right)
+            | WindowEvent::MouseDoubleClick(MouseButton::Right)
+            | WindowEvent::MouseTripleClick(MouseButton::Right) => {
+                self.step_zoom_level(cx, -1);
+                meta.consume();
+            }
+            WindowEvent::MouseScroll(_scroll_x, scroll_y) => {
+                // With a regular scroll wheel `scroll_y` will only ever be -1 or 1, but with smooth
+                // scrolling trackpads being a thing `scroll_y` could be anything.
+                self.scrolled_lines += scroll_y;
+
+                let steps = self.scrolled_lines.trunc();
+                if steps != 0.0 {
+                    self.step_zoom_level(cx, steps as i32);
+                    self.scrolled_lines -= steps;
+                }
+
+                meta.consume();
+            }
+            _ => {}
+        });
+    }
+}
@@ -0,0 +1,190 @@
+use nih_plug::prelude::Enum;
+
+/// The maximum number of operators per voice.
+pub const MAX_OPERATORS: usize = 6;
+
+/// Defines which operators modulate which other operators through a bridge, and which operators
+/// are mixed into the voice's output. Operators only ever get modulated by operators with a higher
+/// index, so rendering the operators from the last to the first one always renders the modulators
+/// before their carriers.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Every operator modulates the previous operator, and only the first operator is heard. With
+    /// two operators this is the classic carrier and modulator pair.
+    Stack,
+    /// Two stacks, each using half of the operators.
+    #[name = "Two Stacks"]
+    TwoStacks,
+    /// Every odd operator modulates the even operator before it.
+    #[name = "Parallel Pairs"]
+    ParallelPairs,
+    /// The last operator modulates all other operators.
+    #[name = "One to Many"]
+    OneToMany,
+    /// All other operators modulate the first operator.
+    #[name = "Many to One"]
+    ManyToOne,
+    /// No modulation, all operators are heard.
+    Additive,
+}
+
+/// The connections an [`Algorithm`] makes between a number of operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Routing {
+    /// For every operator, a bit mask containing the operators that modulate it.
+    modulators: [u8; MAX_OPERATORS],
+    /// A bit mask containing the operators that are mixed into the output.
+    outputs: u8,
+}
+
+impl Algorithm {
+    /// Compute the routing for `num_operators` operators. This does not allocate and can be called
+    /// from the audio thread.
+    pub fn routing(self, num_operators: usize) -> Routing {
+        let num_operators = num_operators.clamp(1, MAX_OPERATORS);
+        let mut routing = Routing {
+            modulators: [0; MAX_OPERATORS],
+            outputs: 0,
+        };
+
+        match self {
+            Algorithm::Stack => {
+                routing.stack(0..num_operators);
+            }
+            Algorithm::TwoStacks => {
+                let split = (num_operators / 2).max(1);
+                routing.stack(0..split);
+                routing.stack(split..num_operators);
+            }
+            Algorithm::ParallelPairs => {
+                for carrier in (0..num_operators).step_by(2) {
+                    routing.stack(carrier..(carrier + 2).min(num_operators));
+                }
+            }
+            Algorithm::OneToMany => {
+                let modulator = num_operators - 1;
+                for carrier in 0..modulator.max(1) {
+                    routing.outputs |= 1 << carrier;
+                    if carrier != modulator {
+                        routing.modulators[carrier] |= 1 << modulator;
+                    }
+                }
+            }
+            Algorithm::ManyToOne => {
+                routing.outputs = 1;
+                for modulator in 1..num_operators {
+                    routing.modulators[0] |= 1 << modulator;
+                }
+            }
+            Algorithm::Additive => {
+                routing.outputs = (1 << num_operators) - 1;
+            }
+        }
+
+        routing
+    }
+}
+
+impl Routing {
+    /// Chain the operators in `range` so every operator modulates the one before it. The first
+    /// operator in the range is heard.
+    fn stack(&mut self, range: std::ops::Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+
+        self.outputs |= 1 << range.start;
+        for carrier in range.start..range.end - 1 {
+            self.modulators[carrier] |= 1 << (carrier + 1);
+        }
+    }
+
+    /// The operators that modulate `operator`.
+    pub fn modulators(&self, operator: usize) -> impl Iterator<Item = usize> {
+        let mask = self.modulators[operator];
+        (0..MAX_OPERATORS).filter(move |modulator| mask & (1 << modulator) != 0)
+    }
+
+    pub fn is_output(&self, operator: usize) -> bool {
+        self.outputs & (1 << operator) != 0
+    }
+
+    /// The number of operators mixed into the output.
+    pub fn num_outputs(&self) -> usize {
+        self.outputs.count_ones() as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stack_of_two_is_carrier_and_modulator() {
+        let routing = Algorithm::Stack.routing(2);
+        assert!(routing.is_output(0));
+        assert!(!routing.is_output(1));
+        assert_eq!(routing.modulators(0).collect::<Vec<_>>(), [1]);
+        assert_eq!(routing.modulators(1).count(), 0);
+    }
+
+    #[test]
+    fn algorithm_shapes() {
+        let routing = Algorithm::TwoStacks.routing(6);
+        assert_eq!(routing.num_outputs(), 2);
+        assert!(routing.is_output(0) && routing.is_output(3));
+        assert_eq!(routing.modulators(3).collect::<Vec<_>>(), [4]);
+        assert_eq!(routing.modulators(2).count(), 0);
+
+        let routing = Algorithm::ParallelPairs.routing(5);
+        assert_eq!(routing.num_outputs(), 3);
+        assert_eq!(routing.modulators(2).collect::<Vec<_>>(), [3]);
+        assert!(routing.is_output(4));
+
+        let routing = Algorithm::OneToMany.routing(4);
+        assert_eq!(routing.num_outputs(), 3);
+        for carrier in 0..3 {
+            assert_eq!(routing.modulators(carrier).collect::<Vec<_>>(), [3]);
+        }
+
+        let routing = Algorithm::ManyToOne.routing(4);
+        assert_eq!(routing.num_outputs(), 1);
+        assert_eq!(routing.modulators(0).collect::<Vec<_>>(), [1, 2, 3]);
+
+        let routing = Algorithm::Additive.routing(6);
+        assert_eq!(routing.num_outputs(), 6);
+        assert!((0..6).all(|operator| routing.modulators(operator).count() == 0));
+    }
+
+    /// Every algorithm must be renderable from the last to the first operator, must not reference
+    /// unused operators, and must be heard.
+    #[test]
+    fn routings_are_valid() {
+        for algorithm in [
+            Algorithm::Stack,
+            Algorithm::TwoStacks,
+            Algorithm::ParallelPairs,
+            Algorithm::OneToMany,
+            Algorithm::ManyToOne,
+            Algorithm::Additive,
+        ] {
+            for num_operators in 1..=MAX_OPERATORS {
+                let routing = algorithm.routing(num_operators);
+                assert!(
+                    routing.num_outputs() > 0,
+                    "{algorithm:?} with {num_operators} operators"
+                );
+
+                for operator in 0..MAX_OPERATORS {
+                    for modulator in routing.modulators(operator) {
+                        assert!(modulator > operator && modulator < num_operators);
+                        assert!(operator < num_operators);
+                    }
+                    if operator >= num_operators {
+                        assert!(!routing.is_output(operator));
+                    }
+                }
+            }
+        }
+    }
+}
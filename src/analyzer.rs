@@ -0,0 +1,220 @@
+use nih_plug::prelude::util;
+use nih_plug_vizia::widgets::Spectrum;
+use realfft::num_complex::Complex32;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::Instant;
+use triple_buffer::TripleBuffer;
+
+/// The number of samples shown by the oscilloscope and analyzed by the spectrum analyzer.
+pub const WINDOW_SIZE: usize = 2048;
+/// The audio thread publishes a new window after this many samples.
+const PUBLISH_INTERVAL: usize = WINDOW_SIZE / 4;
+
+/// How much of the previous average is kept when a new spectrum comes in.
+const AVERAGING: f32 = 0.7;
+/// How quickly the held peaks fall back down.
+const PEAK_DECAY_DB_PER_SECOND: f32 = 12.0;
+
+/// Collects the output on the audio thread and publishes the most recent [`WINDOW_SIZE`] samples
+/// for the editor through a lock-free triple buffer. This never allocates.
+pub struct AnalyzerInput {
+    /// The most recent samples. `position` is the index of the oldest sample.
+    ring: Vec<f32>,
+    position: usize,
+    /// The number of samples written since the last window was published.
+    unpublished: usize,
+    input: triple_buffer::Input<Vec<f32>>,
+}
+
+/// Reads the windows published by an [`AnalyzerInput`] on the GUI thread, and turns them into the
+/// data shown by the oscilloscope and the spectrum analyzer.
+pub struct AnalyzerOutput {
+    output: triple_buffer::Output<Vec<f32>>,
+    sample_rate: f32,
+
+    fft: Arc<dyn RealToComplex<f32>>,
+    /// A Hann window, normalized so a full scale sine results in a 0 dBFS peak.
+    window: Vec<f32>,
+    fft_input: Vec<f32>,
+    fft_output: Vec<Complex32>,
+
+    /// The averaged magnitudes as linear gain.
+    averages: Vec<f32>,
+    /// The held peaks in decibels.
+    peaks_db: Vec<f32>,
+    last_update: Option<Instant>,
+}
+
+/// Create a connected analyzer input and output for a sample rate.
+pub fn analyzer(sample_rate: f32) -> (AnalyzerInput, AnalyzerOutput) {
+    let (input, output) = TripleBuffer::new(&vec![0.0; WINDOW_SIZE]).split();
+    let fft = RealFftPlanner::new().plan_fft_forward(WINDOW_SIZE);
+    let window: Vec<f32> = (0..WINDOW_SIZE)
+        .map(|n| 0.5 - 0.5 * (2.0 * PI * n as f32 / WINDOW_SIZE as f32).cos())
+        .collect();
+    let window_gain: f32 = window.iter().sum::<f32>() / 2.0;
+    let num_bins = WINDOW_SIZE / 2 + 1;
+
+    let input = AnalyzerInput {
+        ring: vec![0.0; WINDOW_SIZE],
+        position: 0,
+        unpublished: 0,
+        input,
+    };
+    let output = AnalyzerOutput {
+        output,
+        sample_rate,
+
+        fft_input: fft.make_input_vec(),
+        fft_output: fft.make_output_vec(),
+        fft,
+        window: window.into_iter().map(|gain| gain / window_gain).collect(),
+
+        averages: vec![0.0; num_bins],
+        peaks_db: vec![util::MINUS_INFINITY_DB; num_bins],
+        last_update: None,
+    };
+
+    (input, output)
+}
+
+impl AnalyzerInput {
+    /// Add a block of output to the ring buffer, and publish the window if enough samples have
+    /// been written since the last time.
+    pub fn process(&mut self, samples: &[f32]) {
+        for sample in samples {
+            self.ring[self.position] = *sample;
+            self.position = (self.position + 1) % WINDOW_SIZE;
+            self.unpublished += 1;
+
+            if self.unpublished >= PUBLISH_INTERVAL {
+                self.publish();
+            }
+        }
+    }
+
+    /// Copy the ring buffer into the triple buffer, from the oldest to the newest sample.
+    fn publish(&mut self) {
+        let (newest, oldest) = self.ring.split_at(self.position);
+        let window = self.input.input_buffer();
+        window[..oldest.len()].copy_from_slice(oldest);
+        window[oldest.len()..].copy_from_slice(newest);
+
+        self.input.publish();
+        self.unpublished = 0;
+    }
+}
+
+impl AnalyzerOutput {
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// The most recent [`WINDOW_SIZE`] samples, from the oldest to the newest sample.
+    pub fn waveform(&mut self) -> Vec<f32> {
+        self.output.read().clone()
+    }
+
+    /// The spectrum of the most recent window, averaged with the previous windows. The peaks are
+    /// held and slowly fall back down.
+    pub fn spectrum(&mut self) -> Spectrum {
+        if self.output.update() {
+            self.analyze();
+        }
+
+        Spectrum {
+            sample_rate: self.sample_rate,
+            magnitudes_db: self
+                .averages
+                .iter()
+                .map(|gain| util::gain_to_db(*gain))
+                .collect(),
+            peaks_db: self.peaks_db.clone(),
+        }
+    }
+
+    /// Add a new window to the averages and the held peaks.
+    fn analyze(&mut self) {
+        for ((fft_input, sample), gain) in self
+            .fft_input
+            .iter_mut()
+            .zip(self.output.output_buffer().iter())
+            .zip(&self.window)
+        {
+            *fft_input = sample * gain;
+        }
+        self.fft
+            .process(&mut self.fft_input, &mut self.fft_output)
+            .unwrap();
+
+        let now = Instant::now();
+        let peak_decay_db = match self.last_update {
+            Some(last_update) => (now - last_update).as_secs_f32() * PEAK_DECAY_DB_PER_SECOND,
+            None => 0.0,
+        };
+        self.last_update = Some(now);
+
+        for ((bin, average), peak_db) in self
+            .fft_output
+            .iter()
+            .zip(self.averages.iter_mut())
+            .zip(self.peaks_db.iter_mut())
+        {
+            let magnitude = bin.norm();
+            *average = (*average * AVERAGING) + (magnitude * (1.0 - AVERAGING));
+            *peak_db = (*peak_db - peak_decay_db).max(util::gain_to_db(magnitude));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publishes_latest_window() {
+        let (mut input, mut output) = analyzer(44100.0);
+        let ramp: Vec<f32> = (0..WINDOW_SIZE * 2 + 100).map(|n| n as f32).collect();
+
+        // Nothing gets published until a full interval has been written
+        input.process(&ramp[..PUBLISH_INTERVAL - 1]);
+        assert!(output.waveform().iter().all(|sample| *sample == 0.0));
+
+        input.process(&ramp[PUBLISH_INTERVAL - 1..]);
+        let waveform = output.waveform();
+        let published_until = (ramp.len() / PUBLISH_INTERVAL) * PUBLISH_INTERVAL;
+        assert_eq!(
+            waveform,
+            ramp[published_until - WINDOW_SIZE..published_until]
+        );
+    }
+
+    #[test]
+    fn sine_spectrum_peaks_at_its_frequency() {
+        let sample_rate = 44100.0;
+        let (mut input, mut output) = analyzer(sample_rate);
+
+        // Centered on a bin so the peak isn't spread out
+        let bin = 100;
+        let freq = bin as f32 * sample_rate / WINDOW_SIZE as f32;
+        let sine: Vec<f32> = (0..WINDOW_SIZE)
+            .map(|n| (2.0 * PI * freq * n as f32 / sample_rate).sin())
+            .collect();
+        input.process(&sine);
+
+        let spectrum = output.spectrum();
+        assert_eq!(spectrum.peaks_db.len(), WINDOW_SIZE / 2 + 1);
+        assert!(
+            spectrum.peaks_db[bin].abs() < 0.1,
+            "{}",
+            spectrum.peaks_db[bin]
+        );
+        assert!(spectrum.peaks_db[bin * 2] < -60.0);
+
+        // The averages only move part of the way towards the new spectrum
+        let expected_average_db = util::gain_to_db(1.0 - AVERAGING);
+        assert!((spectrum.magnitudes_db[bin] - expected_average_db).abs() < 0.1);
+    }
+}
@@ -0,0 +1,839 @@
+use nih_plug::prelude::{
+    formatters, BoolParam, Enum, EnumParam, FloatParam, FloatRange, IntParam, IntRange, NoteEvent,
+    Params,
+};
+
+/// The number of steps in the arpeggiator's pattern.
+pub const ARP_STEPS: usize = 16;
+/// The maximum number of notes the arpeggiator can hold at once. Notes played beyond this are
+/// ignored.
+const MAX_ARP_NOTES: usize = 16;
+
+/// The tempo the arpeggiator runs at when the host doesn't report one.
+const DEFAULT_TEMPO: f64 = 120.0;
+/// How far the host's position can be from the arpeggiator's own position before the arpeggiator
+/// assumes the host jumped, in beats. Smaller differences come from rounding and tempo changes.
+const MAX_POSITION_DRIFT: f64 = 1e-3;
+/// How far past a sample a beat can be and still land on that sample, so rounding errors in the
+/// position don't push events to the next sample.
+const SAMPLE_TOLERANCE: f64 = 1e-6;
+
+/// The order the arpeggiator plays the held notes in.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArpOrder {
+    Up,
+    Down,
+    /// Up and then back down, without repeating the highest and lowest notes.
+    #[name = "Up/Down"]
+    UpDown,
+    Random,
+    /// The order the notes were played in.
+    #[name = "As Played"]
+    AsPlayed,
+}
+
+/// The length of an arpeggiator step, as a note value.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArpRate {
+    #[name = "1/2"]
+    Half,
+    #[name = "1/2 T"]
+    HalfTriplet,
+    #[name = "1/2 D"]
+    HalfDotted,
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/4 T"]
+    QuarterTriplet,
+    #[name = "1/4 D"]
+    QuarterDotted,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/8 T"]
+    EighthTriplet,
+    #[name = "1/8 D"]
+    EighthDotted,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/16 T"]
+    SixteenthTriplet,
+    #[name = "1/16 D"]
+    SixteenthDotted,
+    #[name = "1/32"]
+    ThirtySecond,
+    #[name = "1/32 T"]
+    ThirtySecondTriplet,
+    #[name = "1/32 D"]
+    ThirtySecondDotted,
+}
+
+#[derive(Params)]
+pub struct ArpParams {
+    /// Whether played notes go through the arpeggiator instead of straight to the voices.
+    #[id = "arp_enabled"]
+    pub enabled: BoolParam,
+    #[id = "arp_order"]
+    pub order: EnumParam<ArpOrder>,
+    /// The number of octaves the held notes are repeated in, going up from the played notes.
+    #[id = "arp_octaves"]
+    pub octaves: IntParam,
+    #[id = "arp_rate"]
+    pub rate: EnumParam<ArpRate>,
+    /// How long every note is held, as a fraction of the step.
+    #[id = "arp_gate"]
+    pub gate: FloatParam,
+    /// How far every second step is delayed, as a fraction of half a step.
+    #[id = "arp_swing"]
+    pub swing: FloatParam,
+    /// Whether notes keep playing after they're released. Playing a new note after every key has
+    /// been released replaces the latched notes.
+    #[id = "arp_latch"]
+    pub latch: BoolParam,
+    /// The number of pattern steps that are used before the pattern repeats.
+    #[id = "arp_length"]
+    pub length: IntParam,
+    #[nested(array, group = "Arp Step")]
+    pub steps: [ArpStepParams; ARP_STEPS],
+}
+
+/// One step in the arpeggiator's pattern.
+#[derive(Params)]
+pub struct ArpStepParams {
+    /// Steps that are turned off are rests.
+    #[id = "arp_step_on"]
+    pub on: BoolParam,
+    /// The step's velocity, multiplied with the played note's velocity.
+    #[id = "arp_step_velocity"]
+    pub velocity: FloatParam,
+}
+
+/// The host's transport at the start of a buffer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransportPosition {
+    pub tempo: Option<f64>,
+    pub playing: bool,
+    /// The position in quarter notes.
+    pub pos_beats: Option<f64>,
+}
+
+/// A note held by the arpeggiator.
+#[derive(Debug, Clone, Copy)]
+struct HeldNote {
+    channel: u8,
+    note: u8,
+    velocity: f32,
+    /// Whether the key is still down. Latched notes keep playing after they're released.
+    pressed: bool,
+}
+
+/// Turns the held notes into a sequence of notes on a grid synced to the host's transport. The
+/// steps fall on multiples of the step length since the start of the host's timeline, so the
+/// pattern stays aligned with the bars. While the host is stopped the arpeggiator keeps running at
+/// the host's tempo.
+///
+/// The arpeggiator works in sample positions within the current buffer, so its notes can be
+/// applied at their exact samples: [`Arpeggiator::next_event()`] is the position of its next note
+/// on or note off, and [`Arpeggiator::events_at()`] produces the events at a position.
+pub struct Arpeggiator {
+    notes: [HeldNote; MAX_ARP_NOTES],
+    num_notes: usize,
+    enabled: bool,
+
+    /// The position at the start of the current buffer in beats.
+    block_beat: f64,
+    beats_per_sample: f64,
+    step_beats: f64,
+    /// How far odd steps are delayed in beats.
+    swing_beats: f64,
+    gate: f64,
+    /// The index of the next step since the start of the timeline.
+    next_step: i64,
+    /// The number of notes that have been played, which is the position in the order.
+    counter: usize,
+    /// The note that's playing, and the beat it's released at.
+    playing: Option<(u8, u8, f64)>,
+    rng_state: u32,
+}
+
+impl Default for ArpParams {
+    fn default() -> Self {
+        Self {
+            enabled: BoolParam::new("Arp", false),
+            order: EnumParam::new("Arp Order", ArpOrder::Up),
+            octaves: IntParam::new("Arp Octaves", 1, IntRange::Linear { min: 1, max: 4 }),
+            rate: EnumParam::new("Arp Rate", ArpRate::Sixteenth),
+            gate: FloatParam::new(
+                "Arp Gate",
+                0.5,
+                FloatRange::Linear {
+                    min: 0.05,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            swing: FloatParam::new("Arp Swing", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            latch: BoolParam::new("Arp Latch", false),
+            length: IntParam::new(
+                "Arp Length",
+                ARP_STEPS as i32,
+                IntRange::Linear {
+                    min: 1,
+                    max: ARP_STEPS as i32,
+                },
+            ),
+            steps: std::array::from_fn(|step| ArpStepParams::new(step + 1)),
+        }
+    }
+}
+
+impl ArpStepParams {
+    pub fn new(number: usize) -> Self {
+        Self {
+            on: BoolParam::new(format!("Arp Step {number} On"), true),
+            velocity: FloatParam::new(
+                format!("Arp Step {number} Velocity"),
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+impl ArpRate {
+    /// The step's length in quarter notes.
+    pub fn beats(self) -> f64 {
+        match self {
+            ArpRate::Half => 2.0,
+            ArpRate::HalfTriplet => 4.0 / 3.0,
+            ArpRate::HalfDotted => 3.0,
+            ArpRate::Quarter => 1.0,
+            ArpRate::QuarterTriplet => 2.0 / 3.0,
+            ArpRate::QuarterDotted => 1.5,
+            ArpRate::Eighth => 0.5,
+            ArpRate::EighthTriplet => 1.0 / 3.0,
+            ArpRate::EighthDotted => 0.75,
+            ArpRate::Sixteenth => 0.25,
+            ArpRate::SixteenthTriplet => 1.0 / 6.0,
+            ArpRate::SixteenthDotted => 0.375,
+            ArpRate::ThirtySecond => 0.125,
+            ArpRate::ThirtySecondTriplet => 1.0 / 12.0,
+            ArpRate::ThirtySecondDotted => 0.1875,
+        }
+    }
+}
+
+impl Default for Arpeggiator {
+    fn default() -> Self {
+        Self {
+            notes: [HeldNote {
+                channel: 0,
+                note: 0,
+                velocity: 0.0,
+                pressed: false,
+            }; MAX_ARP_NOTES],
+            num_notes: 0,
+            enabled: false,
+            block_beat: 0.0,
+            beats_per_sample: 0.0,
+            step_beats: ArpRate::Sixteenth.beats(),
+            swing_beats: 0.0,
+            gate: 0.5,
+            next_step: 0,
+            counter: 0,
+            playing: None,
+            rng_state: 0x9e37_79b9,
+        }
+    }
+}
+
+impl Arpeggiator {
+    /// Whether played notes should be passed to the arpeggiator. This is updated at the start of
+    /// every buffer.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Read the parameters and follow the host's transport at the start of a buffer. Returns a
+    /// note off for the playing note if the arpeggiator was turned off, which should be applied
+    /// before anything else.
+    pub fn start_block(
+        &mut self,
+        params: &ArpParams,
+        transport: TransportPosition,
+        sample_rate: f32,
+    ) -> Option<NoteEvent<()>> {
+        let was_enabled = self.enabled;
+        self.enabled = params.enabled.value();
+        if !self.enabled {
+            self.num_notes = 0;
+            self.counter = 0;
+            return if was_enabled { self.release(0) } else { None };
+        }
+
+        // Latched notes are dropped as soon as latching is turned off
+        if !params.latch.value() {
+            self.retain(|note| note.pressed);
+        }
+
+        let tempo = transport.tempo.unwrap_or(DEFAULT_TEMPO);
+        self.beats_per_sample = tempo / 60.0 / sample_rate as f64;
+        let step_beats = params.rate.value().beats();
+        self.swing_beats = params.swing.value() as f64 * step_beats * 0.5;
+        self.gate = params.gate.value() as f64;
+
+        let mut resync = !was_enabled || step_beats != self.step_beats;
+        self.step_beats = step_beats;
+        if let (true, Some(pos_beats)) = (transport.playing, transport.pos_beats) {
+            // Jumps and loops restart the grid from the new position
+            if (pos_beats - self.block_beat).abs() > MAX_POSITION_DRIFT {
+                resync = true;
+                if let Some((_, _, off_beat)) = &mut self.playing {
+                    *off_beat = pos_beats;
+                }
+            }
+            self.block_beat = pos_beats;
+        }
+        if resync {
+            self.next_step = self.first_step_from(self.block_beat);
+        }
+
+        None
+    }
+
+    /// Move the position to the start of the next buffer.
+    pub fn end_block(&mut self, num_samples: usize) {
+        if self.enabled {
+            self.block_beat += num_samples as f64 * self.beats_per_sample;
+        }
+    }
+
+    /// A key was pressed.
+    pub fn note_on(&mut self, channel: u8, note: u8, velocity: f32, latch: bool) {
+        if latch && !self.notes().iter().any(|note| note.pressed) {
+            self.num_notes = 0;
+        }
+
+        match self.notes[..self.num_notes]
+            .iter_mut()
+            .find(|held| held.channel == channel && held.note == note)
+        {
+            Some(held) => {
+                held.velocity = velocity;
+                held.pressed = true;
+            }
+            None if self.num_notes < MAX_ARP_NOTES => {
+                self.notes[self.num_notes] = HeldNote {
+                    channel,
+                    note,
+                    velocity,
+                    pressed: true,
+                };
+                self.num_notes += 1;
+            }
+            None => (),
+        }
+    }
+
+    /// A key was released. The note keeps playing if it's latched.
+    pub fn note_off(&mut self, channel: u8, note: u8, latch: bool) {
+        for held in &mut self.notes[..self.num_notes] {
+            if held.channel == channel && held.note == note {
+                held.pressed = false;
+            }
+        }
+        if !latch {
+            self.retain(|note| note.pressed);
+        }
+    }
+
+    /// Stop every note, e.g. for all notes off. The playing note is released at its usual time.
+    pub fn clear(&mut self) {
+        self.num_notes = 0;
+    }
+
+    /// The position in the current buffer of the next note on or note off, if there is one.
+    pub fn next_event(&self) -> Option<usize> {
+        if !self.enabled {
+            return None;
+        }
+
+        let next_step = self.sample_at(self.step_beat(self.next_step));
+        let note_off = self
+            .playing
+            .map(|(_, _, off_beat)| self.sample_at(off_beat));
+
+        Some(note_off.map_or(next_step, |note_off| note_off.min(next_step)))
+    }
+
+    /// The note off and the note on that happen at a position in the current buffer. The note off
+    /// always comes first.
+    pub fn events_at(&mut self, params: &ArpParams, position: usize) -> [Option<NoteEvent<()>>; 2] {
+        let mut events = [None, None];
+        if !self.enabled {
+            return events;
+        }
+
+        if self
+            .playing
+            .is_some_and(|(_, _, off_beat)| self.sample_at(off_beat) <= position)
+        {
+            events[0] = self.release(position);
+        }
+
+        let step = self.next_step;
+        let step_beat = self.step_beat(step);
+        if self.sample_at(step_beat) <= position {
+            // Steps that were skipped, e.g. because the position jumped, are not played
+            let beat = self.block_beat + position as f64 * self.beats_per_sample;
+            self.next_step = self.first_step_from(beat).max(step + 1);
+
+            if let Some((channel, note, velocity)) = self.step_note(params, step) {
+                if events[0].is_none() {
+                    events[0] = self.release(position);
+                }
+
+                // The note is released before the next step starts, even with swing
+                let off_beat =
+                    (step_beat + self.step_beats * self.gate).min(self.step_beat(step + 1));
+                self.playing = Some((channel, note, off_beat));
+                events[1] = Some(NoteEvent::NoteOn {
+                    timing: position as u32,
+                    voice_id: None,
+                    channel,
+                    note,
+                    velocity,
+                });
+            }
+        }
+
+        events
+    }
+
+    fn notes(&self) -> &[HeldNote] {
+        &self.notes[..self.num_notes]
+    }
+
+    /// Remove the notes that don't match `keep`, without changing the order of the other notes.
+    fn retain(&mut self, keep: impl Fn(&HeldNote) -> bool) {
+        let mut kept = 0;
+        for idx in 0..self.num_notes {
+            if keep(&self.notes[idx]) {
+                self.notes[kept] = self.notes[idx];
+                kept += 1;
+            }
+        }
+        self.num_notes = kept;
+    }
+
+    /// A note off for the playing note.
+    fn release(&mut self, position: usize) -> Option<NoteEvent<()>> {
+        let (channel, note, _) = self.playing.take()?;
+
+        Some(NoteEvent::NoteOff {
+            timing: position as u32,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 0.0,
+        })
+    }
+
+    /// The beat a step starts at, including the swing.
+    fn step_beat(&self, step: i64) -> f64 {
+        let swing = if step % 2 != 0 { self.swing_beats } else { 0.0 };
+
+        step as f64 * self.step_beats + swing
+    }
+
+    /// The first step that starts at or after a beat.
+    fn first_step_from(&self, beat: f64) -> i64 {
+        let step = (beat / self.step_beats).floor() as i64;
+        if self.step_beat(step) >= beat {
+            step
+        } else {
+            step + 1
+        }
+    }
+
+    /// The first sample in the current buffer at or after a beat. Beats before the buffer are at
+    /// its first sample.
+    fn sample_at(&self, beat: f64) -> usize {
+        ((beat - self.block_beat) / self.beats_per_sample - SAMPLE_TOLERANCE)
+            .ceil()
+            .max(0.0) as usize
+    }
+
+    /// The channel, note, and velocity to play for a step, or `None` for rests.
+    fn step_note(&mut self, params: &ArpParams, step: i64) -> Option<(u8, u8, f32)> {
+        let length = params.length.value() as i64;
+        let step_params = &params.steps[step.rem_euclid(length) as usize];
+        if self.num_notes == 0 || !step_params.on.value() {
+            return None;
+        }
+
+        // The notes sorted by pitch, which is the order for everything except `AsPlayed`
+        let order = params.order.value();
+        let mut notes = self.notes;
+        if order != ArpOrder::AsPlayed {
+            notes[..self.num_notes].sort_by_key(|note| note.note);
+        }
+
+        let period = self.num_notes * params.octaves.value() as usize;
+        let position = self.counter % period;
+        let idx = match order {
+            ArpOrder::Up | ArpOrder::AsPlayed => position,
+            ArpOrder::Down => period - 1 - position,
+            ArpOrder::UpDown if period > 1 => {
+                let position = self.counter % (2 * period - 2);
+                if position < period {
+                    position
+                } else {
+                    2 * period - 2 - position
+                }
+            }
+            ArpOrder::UpDown => 0,
+            ArpOrder::Random => self.next_random() as usize % period,
+        };
+        self.counter += 1;
+
+        let held = notes[idx % self.num_notes];
+        let octave = (idx / self.num_notes) as u8;
+        let note = held.note.saturating_add(12 * octave).min(127);
+
+        Some((
+            held.channel,
+            note,
+            held.velocity * step_params.velocity.value(),
+        ))
+    }
+
+    /// A xorshift random number, so the random order doesn't need an allocation or a lock.
+    fn next_random(&mut self) -> u32 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 17;
+        self.rng_state ^= self.rng_state << 5;
+
+        self.rng_state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    /// A sixteenth note at 120 BPM.
+    const STEP: usize = 6000;
+
+    fn transport(pos_beats: f64) -> TransportPosition {
+        TransportPosition {
+            tempo: Some(120.0),
+            playing: true,
+            pos_beats: Some(pos_beats),
+        }
+    }
+
+    fn enabled() -> ArpParams {
+        ArpParams {
+            enabled: BoolParam::new("Arp", true),
+            ..ArpParams::default()
+        }
+    }
+
+    /// Run the arpeggiator for `num_samples` samples from the start of the timeline, split into
+    /// buffers of `buffer_size` samples. Returns the note ons and note offs with their absolute
+    /// positions.
+    fn run(
+        arp: &mut Arpeggiator,
+        params: &ArpParams,
+        start: usize,
+        num_samples: usize,
+        buffer_size: usize,
+    ) -> Vec<(usize, NoteEvent<()>)> {
+        let mut events = Vec::new();
+        let mut buffer_start = start;
+        while buffer_start < start + num_samples {
+            let len = buffer_size.min(start + num_samples - buffer_start);
+            let pos_beats = buffer_start as f64 / STEP as f64 / 4.0;
+            if let Some(event) = arp.start_block(params, transport(pos_beats), SAMPLE_RATE) {
+                events.push((buffer_start, event));
+            }
+
+            let mut position = 0;
+            while let Some(next) = arp.next_event().filter(|next| *next < len) {
+                position = position.max(next);
+                for event in arp.events_at(params, position).into_iter().flatten() {
+                    events.push((buffer_start + position, event));
+                }
+            }
+
+            arp.end_block(len);
+            buffer_start += len;
+        }
+
+        events
+    }
+
+    fn note_ons(events: &[(usize, NoteEvent<()>)]) -> Vec<(usize, u8)> {
+        events
+            .iter()
+            .filter_map(|(position, event)| match event {
+                NoteEvent::NoteOn { note, .. } => Some((*position, *note)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn note_offs(events: &[(usize, NoteEvent<()>)]) -> Vec<(usize, u8)> {
+        events
+            .iter()
+            .filter_map(|(position, event)| match event {
+                NoteEvent::NoteOff { note, .. } => Some((*position, *note)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn held(notes: &[u8]) -> Arpeggiator {
+        let mut arp = Arpeggiator::default();
+        for note in notes {
+            arp.note_on(0, *note, 1.0, false);
+        }
+
+        arp
+    }
+
+    #[test]
+    fn plays_on_the_grid() {
+        let params = enabled();
+        let mut arp = held(&[64, 60, 67]);
+        let events = run(&mut arp, &params, 0, 4 * STEP, 512);
+
+        assert_eq!(
+            note_ons(&events),
+            [(0, 60), (STEP, 64), (2 * STEP, 67), (3 * STEP, 60)]
+        );
+        assert_eq!(
+            note_offs(&events),
+            [
+                (STEP / 2, 60),
+                (3 * STEP / 2, 64),
+                (5 * STEP / 2, 67),
+                (7 * STEP / 2, 60)
+            ]
+        );
+    }
+
+    #[test]
+    fn buffer_size_does_not_change_timing() {
+        let params = ArpParams {
+            rate: EnumParam::new("Arp Rate", ArpRate::SixteenthTriplet),
+            swing: FloatParam::new("Arp Swing", 0.3, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            gate: FloatParam::new(
+                "Arp Gate",
+                0.7,
+                FloatRange::Linear {
+                    min: 0.05,
+                    max: 1.0,
+                },
+            ),
+            ..enabled()
+        };
+        let reference = run(&mut held(&[60, 64, 67]), &params, 0, 16 * STEP, 48000);
+        for buffer_size in [1, 64, 100, 512, 4096] {
+            let events = run(&mut held(&[60, 64, 67]), &params, 0, 16 * STEP, buffer_size);
+            assert_eq!(
+                note_ons(&events),
+                note_ons(&reference),
+                "{buffer_size} sample buffers"
+            );
+            assert_eq!(
+                note_offs(&events),
+                note_offs(&reference),
+                "{buffer_size} sample buffers"
+            );
+        }
+    }
+
+    #[test]
+    fn orders_and_octaves() {
+        let notes = |order, octaves| {
+            let params = ArpParams {
+                order: EnumParam::new("Arp Order", order),
+                octaves: IntParam::new("Arp Octaves", octaves, IntRange::Linear { min: 1, max: 4 }),
+                ..enabled()
+            };
+            let events = run(&mut held(&[64, 60, 67]), &params, 0, 8 * STEP, 512);
+            note_ons(&events)
+                .into_iter()
+                .map(|(_, note)| note)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(notes(ArpOrder::Up, 2), [60, 64, 67, 72, 76, 79, 60, 64]);
+        assert_eq!(notes(ArpOrder::Down, 1), [67, 64, 60, 67, 64, 60, 67, 64]);
+        assert_eq!(notes(ArpOrder::UpDown, 1), [60, 64, 67, 64, 60, 64, 67, 64]);
+        assert_eq!(
+            notes(ArpOrder::AsPlayed, 1),
+            [64, 60, 67, 64, 60, 67, 64, 60]
+        );
+
+        let random = notes(ArpOrder::Random, 2);
+        assert_eq!(random.len(), 8);
+        assert!(random
+            .iter()
+            .all(|note| [60, 64, 67, 72, 76, 79].contains(note)));
+    }
+
+    #[test]
+    fn rates() {
+        assert_eq!(ArpRate::Quarter.beats(), 1.0);
+        assert_eq!(ArpRate::EighthDotted.beats(), 0.75);
+        assert!((ArpRate::EighthTriplet.beats() * 3.0 - 1.0).abs() < 1e-12);
+
+        let params = ArpParams {
+            rate: EnumParam::new("Arp Rate", ArpRate::EighthTriplet),
+            ..enabled()
+        };
+        let events = run(&mut held(&[60]), &params, 0, 4 * STEP, 512);
+        let positions: Vec<usize> = note_ons(&events)
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect();
+        assert_eq!(positions, [0, 8000, 16000]);
+    }
+
+    #[test]
+    fn swing_delays_every_second_step() {
+        let params = ArpParams {
+            swing: FloatParam::new("Arp Swing", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            gate: FloatParam::new(
+                "Arp Gate",
+                1.0,
+                FloatRange::Linear {
+                    min: 0.05,
+                    max: 1.0,
+                },
+            ),
+            ..enabled()
+        };
+        let events = run(&mut held(&[60]), &params, 0, 4 * STEP, 512);
+        let positions: Vec<usize> = note_ons(&events)
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect();
+        assert_eq!(
+            positions,
+            [0, STEP + STEP / 2, 2 * STEP, 3 * STEP + STEP / 2]
+        );
+
+        // Full length notes on swung steps are released when the next step starts
+        let offs: Vec<usize> = note_offs(&events)
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect();
+        assert_eq!(offs, [STEP, 2 * STEP, 3 * STEP]);
+    }
+
+    #[test]
+    fn pattern_rests_and_velocities() {
+        let mut params = ArpParams {
+            length: IntParam::new(
+                "Arp Length",
+                3,
+                IntRange::Linear {
+                    min: 1,
+                    max: ARP_STEPS as i32,
+                },
+            ),
+            ..enabled()
+        };
+        params.steps[1] = ArpStepParams {
+            on: BoolParam::new("Arp Step 2 On", false),
+            ..ArpStepParams::new(2)
+        };
+        params.steps[2] = ArpStepParams {
+            velocity: FloatParam::new(
+                "Arp Step 3 Velocity",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+            ..ArpStepParams::new(3)
+        };
+
+        let mut arp = Arpeggiator::default();
+        arp.note_on(0, 60, 0.8, false);
+        arp.note_on(0, 64, 0.8, false);
+        let events = run(&mut arp, &params, 0, 6 * STEP, 512);
+        let note_ons: Vec<_> = events
+            .iter()
+            .filter_map(|(position, event)| match event {
+                NoteEvent::NoteOn { note, velocity, .. } => {
+                    Some((*position / STEP, *note, *velocity))
+                }
+                _ => None,
+            })
+            .collect();
+
+        // Rests don't move through the notes
+        assert_eq!(
+            note_ons,
+            [(0, 60, 0.8), (2, 64, 0.4), (3, 60, 0.8), (5, 64, 0.4)]
+        );
+    }
+
+    #[test]
+    fn latch() {
+        let params = ArpParams {
+            latch: BoolParam::new("Arp Latch", true),
+            ..enabled()
+        };
+        let mut arp = Arpeggiator::default();
+        arp.note_on(0, 60, 1.0, true);
+        arp.note_on(0, 64, 1.0, true);
+        arp.note_off(0, 60, true);
+        arp.note_off(0, 64, true);
+        let events = run(&mut arp, &params, 0, 4 * STEP, 512);
+        assert_eq!(note_ons(&events).len(), 4);
+
+        // A new note after every key was released replaces the latched notes
+        arp.note_on(0, 67, 1.0, true);
+        let events = run(&mut arp, &params, 4 * STEP, 2 * STEP, 512);
+        assert_eq!(note_ons(&events), [(4 * STEP, 67), (5 * STEP, 67)]);
+
+        // Without latching, released notes stop
+        arp.note_off(0, 67, true);
+        let events = run(&mut arp, &enabled(), 6 * STEP, 2 * STEP, 512);
+        assert!(note_ons(&events).is_empty());
+    }
+
+    #[test]
+    fn follows_host_jumps() {
+        let params = enabled();
+        let mut arp = held(&[60]);
+        run(&mut arp, &params, 0, STEP + 100, 512);
+
+        // The host loops back to a position between two steps, so the next note is on the
+        // next step from there, and the playing note is released right away
+        let events = run(&mut arp, &params, STEP / 2, STEP, 512);
+        assert_eq!(note_offs(&events)[0], (STEP / 2, 60));
+        assert_eq!(note_ons(&events), [(STEP, 60)]);
+    }
+
+    #[test]
+    fn turning_off_releases_the_playing_note() {
+        let mut arp = held(&[60]);
+        run(&mut arp, &enabled(), 0, 100, 100);
+        let events = run(&mut arp, &ArpParams::default(), 100, 100, 100);
+        assert_eq!(note_offs(&events), [(100, 60)]);
+        assert!(!arp.is_enabled());
+    }
+}
@@ -0,0 +1,205 @@
+use std::f32::consts::TAU;
+#[cfg(feature = "simd")]
+use std::simd::prelude::*;
+
+#[cfg(not(feature = "simd"))]
+use crate::synth::sine::fast_sin;
+
+/// The maximum number of samples that's rendered at once. The host's blocks are split into blocks
+/// of at most this size, and the smoothed parameter values are read for a whole block at a time.
+pub const BLOCK_SIZE: usize = 64;
+
+/// The number of voices whose sine operators are rendered together, which fills a 256-bit AVX
+/// register. The lanes are plain arrays. With the `simd` feature on a nightly compiler they're
+/// rendered with `std::simd`, otherwise the loop over the lanes is left to the compiler to
+/// vectorize, which it does since [`fast_sin()`][crate::synth::sine::fast_sin] only uses arithmetic and bit operations.
+pub const LANES: usize = 8;
+
+/// One value for every lane.
+pub type Lanes = [f32; LANES];
+
+/// A block of one sine operator for up to [`LANES`] voices. The voices' oscillator and feedback
+/// state and their per-sample inputs are copied in before rendering and copied back out afterwards,
+/// so the lanes of every sample are next to each other. Unused lanes are rendered as well, they're
+/// just never copied back.
+pub struct SineLanes {
+    /// The oscillators' phases in radians.
+    pub phase: Lanes,
+    /// The operators' last two output samples, the newest sample first. This is the same as
+    /// [`FeedbackBridge`][crate::bridge::feedback::FeedbackBridge]'s history.
+    pub history: [Lanes; 2],
+    /// The oscillators' phase increments in radians.
+    pub phase_delta: [Lanes; BLOCK_SIZE],
+    /// The feedback amounts.
+    pub feedback: [Lanes; BLOCK_SIZE],
+    /// The gain applied to the oscillators, which includes the operators' levels and envelopes and
+    /// the amplitude modulation.
+    pub gain: [Lanes; BLOCK_SIZE],
+    pub output: [Lanes; BLOCK_SIZE],
+}
+
+impl SineLanes {
+    pub fn new() -> Self {
+        Self {
+            phase: [0.0; LANES],
+            history: [[0.0; LANES]; 2],
+            phase_delta: [[0.0; LANES]; BLOCK_SIZE],
+            feedback: [[0.0; LANES]; BLOCK_SIZE],
+            gain: [[0.0; LANES]; BLOCK_SIZE],
+            output: [[0.0; LANES]; BLOCK_SIZE],
+        }
+    }
+
+    /// Silence a lane that isn't used for the next block.
+    pub fn clear_lane(&mut self, lane: usize) {
+        self.phase[lane] = 0.0;
+        for history in &mut self.history {
+            history[lane] = 0.0;
+        }
+        for sample in 0..BLOCK_SIZE {
+            self.phase_delta[sample][lane] = 0.0;
+            self.feedback[sample][lane] = 0.0;
+            self.gain[sample][lane] = 0.0;
+        }
+    }
+
+    /// Render the first `len` samples. This does the same thing as
+    /// [`SineOscillator::generate_with_phase_offset()`][crate::synth::sine::SineOscillator::generate_with_phase_offset()]
+    /// with the feedback computed by the feedback bridge, for every lane at once.
+    #[cfg(not(feature = "simd"))]
+    pub fn render(&mut self, len: usize) {
+        for sample in 0..len {
+            let mut output = [0.0; LANES];
+            for (lane, lane_output) in output.iter_mut().enumerate() {
+                let feedback = self.feedback[sample][lane]
+                    * (self.history[0][lane] + self.history[1][lane])
+                    * 0.5;
+                *lane_output =
+                    fast_sin(self.phase[lane] + TAU * feedback) * self.gain[sample][lane];
+
+                let phase = self.phase[lane] + self.phase_delta[sample][lane];
+                self.phase[lane] = if phase >= TAU { phase - TAU } else { phase };
+            }
+
+            self.history = [output, self.history[0]];
+            self.output[sample] = output;
+        }
+    }
+
+    /// The same as the array version, using `std::simd` vectors.
+    #[cfg(feature = "simd")]
+    pub fn render(&mut self, len: usize) {
+        let tau = F32s::splat(TAU);
+        let mut phase = F32s::from_array(self.phase);
+        let mut history = self.history.map(F32s::from_array);
+        for sample in 0..len {
+            let feedback = F32s::from_array(self.feedback[sample])
+                * (history[0] + history[1])
+                * F32s::splat(0.5);
+            let output =
+                simd_fast_sin(phase + tau * feedback) * F32s::from_array(self.gain[sample]);
+
+            let next_phase = phase + F32s::from_array(self.phase_delta[sample]);
+            phase = next_phase.simd_ge(tau).select(next_phase - tau, next_phase);
+
+            history = [output, history[0]];
+            self.output[sample] = output.to_array();
+        }
+
+        self.phase = phase.to_array();
+        self.history = history.map(|history| history.to_array());
+    }
+}
+
+/// One value for every lane as a `std::simd` vector.
+#[cfg(feature = "simd")]
+type F32s = std::simd::Simd<f32, LANES>;
+
+/// [`fast_sin()`][crate::synth::sine::fast_sin] for every lane, with the same operations in the same order so the lanes match
+/// the scalar oscillators exactly.
+#[cfg(feature = "simd")]
+fn simd_fast_sin(x: F32s) -> F32s {
+    use std::f32::consts::{FRAC_1_PI, PI};
+    use std::simd::Simd;
+
+    const ROUND: f32 = 12582912.0;
+    let c = F32s::splat;
+
+    let rounded = x * c(FRAC_1_PI) + c(ROUND);
+    let half_periods = rounded - c(ROUND);
+    let sign = rounded.to_bits() << Simd::splat(31);
+    let x = x - half_periods * c(PI);
+
+    let x2 = x * x;
+    let series = c(1.0)
+        + x2 * (c(-1.0 / 6.0)
+            + x2 * (c(1.0 / 120.0)
+                + x2 * (c(-1.0 / 5040.0) + x2 * (c(1.0 / 362880.0) + x2 * c(-1.0 / 39916800.0)))));
+
+    F32s::from_bits((x * series).to_bits() ^ sign)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::feedback::FeedbackBridge;
+    use crate::synth::sine::SineOscillator;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    #[test]
+    fn lanes_match_scalar_operators() {
+        let frequencies = [55.0, 110.0, 220.0, 440.0, 1000.0, 1760.0, 3520.0, 7040.0];
+        let feedback = [0.0, 0.1, 0.2, 0.35, 0.5, 0.7, 0.85, 1.0];
+        let gain = [1.0, 0.5, 0.25, 0.8, 0.6, 0.9, 0.3, 0.7];
+
+        let mut lanes = SineLanes::new();
+        for sample in 0..BLOCK_SIZE {
+            for lane in 0..LANES {
+                lanes.phase_delta[sample][lane] = TAU * frequencies[lane] / SAMPLE_RATE;
+                lanes.feedback[sample][lane] = feedback[lane];
+                lanes.gain[sample][lane] = gain[lane];
+            }
+        }
+
+        let mut oscillators: [_; LANES] = std::array::from_fn(|lane| {
+            let mut oscillator = SineOscillator::new(SAMPLE_RATE);
+            oscillator.set_frequency(frequencies[lane]);
+            (oscillator, FeedbackBridge::new())
+        });
+
+        // Render a few blocks to make sure the state carries over between blocks
+        for _ in 0..4 {
+            lanes.render(BLOCK_SIZE);
+            for (sample, output) in lanes.output.iter().enumerate() {
+                for (lane, (oscillator, bridge)) in oscillators.iter_mut().enumerate() {
+                    let expected = oscillator
+                        .generate_with_phase_offset(bridge.process(feedback[lane]))
+                        * gain[lane];
+                    bridge.push(expected);
+
+                    let actual = output[lane];
+                    assert!(
+                        (actual - expected).abs() < 1e-5,
+                        "Lane {lane}, sample {sample}: {actual} != {expected}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cleared_lanes_are_silent() {
+        let mut lanes = SineLanes::new();
+        for sample in 0..BLOCK_SIZE {
+            lanes.phase_delta[sample] = [0.1; LANES];
+            lanes.gain[sample] = [1.0; LANES];
+        }
+        lanes.render(BLOCK_SIZE);
+
+        lanes.clear_lane(2);
+        lanes.render(BLOCK_SIZE);
+        assert!(lanes.output.iter().all(|output| output[2] == 0.0));
+        assert!(lanes.output.iter().any(|output| output[0] != 0.0));
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Default)]
 pub struct AmBridge;
 
 impl AmBridge {
@@ -9,4 +10,57 @@ impl AmBridge {
         let modulation = 1.0 + (modulator * depth);
         carrier * modulation
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::test_util::magnitude_at;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn zero_depth_passes_carrier() {
+        let bridge = AmBridge::new();
+        for (carrier, modulator) in [(0.5, 1.0), (-0.25, -1.0), (1.0, 0.3)] {
+            assert_eq!(bridge.process(carrier, modulator, 0.0), carrier);
+        }
+    }
+
+    #[test]
+    fn full_depth_modulation() {
+        let bridge = AmBridge::new();
+        assert_eq!(bridge.process(0.5, 1.0, 1.0), 1.0);
+        assert_eq!(bridge.process(0.5, -1.0, 1.0), 0.0);
+        assert_eq!(bridge.process(0.5, 0.0, 1.0), 0.5);
+    }
+
+    #[test]
+    fn sidebands() {
+        const SAMPLE_RATE: f32 = 8000.0;
+        const CARRIER: f32 = 1000.0;
+        const MODULATOR: f32 = 100.0;
+        const DEPTH: f32 = 0.5;
+
+        let bridge = AmBridge::new();
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|n| {
+                let t = n as f32 / SAMPLE_RATE;
+                bridge.process(
+                    (2.0 * PI * CARRIER * t).sin(),
+                    (2.0 * PI * MODULATOR * t).sin(),
+                    DEPTH,
+                )
+            })
+            .collect();
+
+        // Amplitude modulation keeps the carrier and adds two sidebands at half the depth
+        assert!((magnitude_at(&signal, CARRIER, SAMPLE_RATE) - 1.0).abs() < 1e-2);
+        assert!(
+            (magnitude_at(&signal, CARRIER - MODULATOR, SAMPLE_RATE) - DEPTH / 2.0).abs() < 1e-2
+        );
+        assert!(
+            (magnitude_at(&signal, CARRIER + MODULATOR, SAMPLE_RATE) - DEPTH / 2.0).abs() < 1e-2
+        );
+        assert!(magnitude_at(&signal, MODULATOR, SAMPLE_RATE) < 1e-2);
+    }
+}
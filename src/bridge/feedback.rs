@@ -0,0 +1,103 @@
+/// Feeds an operator's output back into the operator itself. The feedback signal is the average
+/// of the last two output samples. This one-sample averaging filter dampens the oscillation at the
+/// Nyquist frequency that unfiltered feedback falls into, so high feedback amounts settle into
+/// noise instead.
+pub struct FeedbackBridge {
+    previous: [f32; 2],
+}
+
+impl FeedbackBridge {
+    pub fn new() -> Self {
+        Self { previous: [0.0; 2] }
+    }
+
+    /// Forget the previous output, e.g. when a voice gets retriggered.
+    pub fn reset(&mut self) {
+        self.previous = [0.0; 2];
+    }
+
+    /// The averaged feedback signal scaled by `amount`. Oscillators use this as a phase offset in
+    /// cycles.
+    pub fn process(&self, amount: f32) -> f32 {
+        amount * (self.previous[0] + self.previous[1]) * 0.5
+    }
+
+    /// The last two output samples, the newest sample first. [`SineLanes`][crate::block::SineLanes]
+    /// uses this to apply the feedback of several voices at once.
+    pub fn history(&self) -> [f32; 2] {
+        self.previous
+    }
+
+    pub fn set_history(&mut self, history: [f32; 2]) {
+        self.previous = history;
+    }
+
+    /// Store the operator's latest output sample.
+    pub fn push(&mut self, output: f32) {
+        self.previous = [output, self.previous[0]];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::test_util::magnitude_at;
+    use std::f32::consts::PI;
+
+    /// Render a sine operator with phase feedback.
+    fn render(amount: f32, num_samples: usize) -> Vec<f32> {
+        let mut feedback = FeedbackBridge::new();
+        let mut phase = 0.0f32;
+        (0..num_samples)
+            .map(|_| {
+                let output = (2.0 * PI * (phase + feedback.process(amount))).sin();
+                feedback.push(output);
+                phase = (phase + 440.0 / 44100.0).fract();
+                output
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zero_amount_is_silent() {
+        let mut feedback = FeedbackBridge::new();
+        feedback.push(1.0);
+        assert_eq!(feedback.process(0.0), 0.0);
+    }
+
+    #[test]
+    fn averages_two_samples() {
+        let mut feedback = FeedbackBridge::new();
+        feedback.push(1.0);
+        assert_eq!(feedback.process(1.0), 0.5);
+        feedback.push(-1.0);
+        assert_eq!(feedback.process(1.0), 0.0);
+        feedback.push(-1.0);
+        assert_eq!(feedback.process(0.5), -0.5);
+
+        feedback.reset();
+        assert_eq!(feedback.process(1.0), 0.0);
+    }
+
+    #[test]
+    fn high_feedback_stays_bounded() {
+        let output = render(1.0, 44100);
+        assert!(output
+            .iter()
+            .all(|sample| sample.is_finite() && sample.abs() <= 1.0));
+
+        // The averaging filter should prevent the output from flipping sign on every sample
+        let sign_flips = output
+            .windows(2)
+            .filter(|window| window[0] * window[1] < 0.0)
+            .count();
+        assert!(sign_flips < output.len() / 2, "{sign_flips} sign flips");
+    }
+
+    #[test]
+    fn feedback_adds_harmonics() {
+        let second_harmonic = |signal: &[f32]| magnitude_at(signal, 880.0, 44100.0);
+        assert!(second_harmonic(&render(0.0, 4410)) < 0.01);
+        assert!(second_harmonic(&render(0.3, 4410)) > 0.1);
+    }
+}
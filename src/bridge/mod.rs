@@ -1 +1,6 @@
 pub mod am;
+pub mod feedback;
+
+/// Helpers shared by the bridges' tests.
+#[cfg(test)]
+mod test_util;
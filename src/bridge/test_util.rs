@@ -0,0 +1,14 @@
+use std::f32::consts::PI;
+
+/// The amplitude of the `frequency` component in `signal`, computed with a single DFT bin.
+pub fn magnitude_at(signal: &[f32], frequency: f32, sample_rate: f32) -> f32 {
+    let (re, im) = signal
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(re, im), (n, sample)| {
+            let phase = 2.0 * PI * frequency * n as f32 / sample_rate;
+            (re + sample * phase.cos(), im - sample * phase.sin())
+        });
+
+    2.0 * (re * re + im * im).sqrt() / signal.len() as f32
+}
@@ -0,0 +1,1176 @@
+use nih_plug::prelude::{
+    util, AsyncExecutor, BoolParam, Editor, FloatParam, GuiContext, NoteEvent, Param,
+};
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::widgets::*;
+use nih_plug_vizia::{assets, create_vizia_editor, theme, ViziaState, ViziaTheming};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::algorithm::MAX_OPERATORS;
+use crate::analyzer::AnalyzerOutput;
+use crate::arpeggiator::ARP_STEPS;
+use crate::filter::ResonantFilter;
+use crate::keyboard::VirtualKeyboard;
+use crate::meter::OutputMeter;
+use crate::midi_learn::MidiLearn;
+use crate::modulation::{ModMatrix, NUM_LFOS};
+use crate::params::{AmSynthParams, EnvelopeTarget};
+use crate::presets::{Preset, PresetFilter, PresetLibrary};
+use crate::synth::wavetable::{BuiltinWavetable, WavetableSource};
+use crate::{AmSynth, Task};
+
+#[derive(Lens)]
+struct Data {
+    params: Arc<AmSynthParams>,
+    meter: Arc<OutputMeter>,
+    analyzer: Arc<Mutex<AnalyzerOutput>>,
+    keyboard: Arc<VirtualKeyboard>,
+    mod_matrix: Arc<ModMatrix>,
+    async_executor: AsyncExecutor<AmSynth>,
+    /// Used to save and restore the plugin's state for presets.
+    gui_context: Arc<dyn GuiContext>,
+    presets: PresetLibrary,
+
+    /// The operator whose wavetable is shown in the wavetable section.
+    selected_operator: usize,
+    /// The file path typed into the wavetable import text box.
+    wavetable_path: String,
+    /// The file path typed into the sample text box.
+    sample_path: String,
+    /// The error from the last multi-stage envelope shape that could not be parsed, or an empty
+    /// string.
+    breakpoints_status: String,
+    /// The error from the last user velocity curve that could not be parsed, or an empty string.
+    velocity_table_status: String,
+
+    show_preset_browser: bool,
+    /// The index of the last loaded or saved preset in `presets`.
+    current_preset: Option<usize>,
+    current_preset_name: String,
+    preset_filter: PresetFilter,
+    /// The presets that pass `preset_filter`, as shown in the preset browser.
+    preset_rows: Vec<PresetRow>,
+    preset_categories: Vec<String>,
+    preset_tags: Vec<String>,
+    /// The name, author, category, and comma separated tags for saving a preset.
+    save_name: String,
+    save_author: String,
+    save_category: String,
+    save_tags: String,
+    /// The result of the last preset operation that failed, or an empty string.
+    preset_status: String,
+}
+
+/// A preset in the preset browser's list.
+#[derive(Debug, Clone, PartialEq)]
+struct PresetRow {
+    /// The preset's index in the [`PresetLibrary`].
+    idx: usize,
+    name: String,
+    /// The preset's category and author.
+    details: String,
+    favourite: bool,
+}
+
+// The derive macro can't be used here since the model above is also called `Data`
+impl nih_plug_vizia::vizia::prelude::Data for PresetRow {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+enum EditorEvent {
+    SelectOperator(usize),
+    SetWavetablePath(String),
+    /// Load a built-in table or the file from the path text box for the selected operator.
+    LoadWavetable(Option<BuiltinWavetable>),
+    /// Embed the file from the path text box in the plugin's state.
+    EmbedWavetable,
+    SetSamplePath(String),
+    /// Load the file from the sample path text box.
+    LoadSample,
+    /// A note played on the on-screen keyboard, with a velocity between 0 and 1.
+    NoteOn(u8, f32),
+    NoteOff(u8),
+    /// Parse a multi-stage shape typed into an envelope's text box and store it in the parameters.
+    SetBreakpoints(EnvelopeTarget, String),
+    /// Parse the user velocity curve typed into its text box and store it in the parameters.
+    SetVelocityTable(String),
+    /// Remove the route in this mod matrix slot.
+    ClearModSlot(usize),
+    TogglePresetBrowser,
+    /// Load the preset with this index in the preset library.
+    LoadPreset(usize),
+    /// Load the next or previous preset in the browser's list.
+    StepPreset(isize),
+    ToggleFavourite(usize),
+    SetPresetSearch(String),
+    SetPresetCategory(Option<String>),
+    SetPresetTag(Option<String>),
+    ToggleFavouritesOnly,
+    SetSaveName(String),
+    SetSaveAuthor(String),
+    SetSaveCategory(String),
+    SetSaveTags(String),
+    /// Save the current state as a user preset with the name, author, category, and tags from the
+    /// save text boxes.
+    SavePreset,
+}
+
+impl Model for Data {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|editor_event, meta| {
+            match editor_event {
+                EditorEvent::SelectOperator(operator) => {
+                    self.selected_operator = *operator;
+                }
+                EditorEvent::SetWavetablePath(path) => {
+                    self.wavetable_path = path.clone();
+                }
+                EditorEvent::LoadWavetable(Some(builtin)) => {
+                    self.async_executor.execute_background(Task::LoadWavetable(
+                        self.selected_operator,
+                        WavetableSource::Builtin(*builtin),
+                    ));
+                }
+                EditorEvent::LoadWavetable(None) => {
+                    let path = PathBuf::from(self.wavetable_path.trim());
+                    self.async_executor.execute_background(Task::LoadWavetable(
+                        self.selected_operator,
+                        WavetableSource::File(path),
+                    ));
+                }
+                EditorEvent::EmbedWavetable => {
+                    let path = PathBuf::from(self.wavetable_path.trim());
+                    self.async_executor
+                        .execute_background(Task::EmbedWavetable(self.selected_operator, path));
+                }
+                EditorEvent::SetSamplePath(path) => {
+                    self.sample_path = path.clone();
+                }
+                EditorEvent::LoadSample => {
+                    let path = PathBuf::from(self.sample_path.trim());
+                    self.async_executor
+                        .execute_background(Task::LoadSample(path));
+                }
+                EditorEvent::NoteOn(note, velocity) => {
+                    self.keyboard.send(NoteEvent::NoteOn {
+                        timing: 0,
+                        voice_id: None,
+                        channel: 0,
+                        note,
+                        velocity,
+                    });
+                }
+                EditorEvent::NoteOff(note) => {
+                    self.keyboard.send(NoteEvent::NoteOff {
+                        timing: 0,
+                        voice_id: None,
+                        channel: 0,
+                        note,
+                        velocity: 0.0,
+                    });
+                }
+                EditorEvent::SetBreakpoints(target, text) => match text.parse() {
+                    Ok(shape) => {
+                        *self.params.breakpoints(*target).write().unwrap() = shape;
+                        self.breakpoints_status.clear();
+                    }
+                    Err(err) => self.breakpoints_status = err,
+                },
+                EditorEvent::SetVelocityTable(text) => match text.parse() {
+                    Ok(table) => {
+                        *self.params.velocity_table.write().unwrap() = table;
+                        self.velocity_table_status.clear();
+                    }
+                    Err(err) => self.velocity_table_status = err,
+                },
+                EditorEvent::ClearModSlot(slot) => {
+                    self.mod_matrix.clear(*slot);
+                }
+                EditorEvent::TogglePresetBrowser => {
+                    self.show_preset_browser = !self.show_preset_browser;
+                }
+                EditorEvent::LoadPreset(idx) => {
+                    self.load_preset(*idx);
+                }
+                EditorEvent::StepPreset(step) => {
+                    if !self.preset_rows.is_empty() {
+                        let num_rows = self.preset_rows.len() as isize;
+                        let current_row = self
+                            .preset_rows
+                            .iter()
+                            .position(|row| Some(row.idx) == self.current_preset);
+                        let new_row = match current_row {
+                            Some(row) => (row as isize + step).rem_euclid(num_rows),
+                            None if *step > 0 => 0,
+                            None => num_rows - 1,
+                        };
+
+                        self.load_preset(self.preset_rows[new_row as usize].idx);
+                    }
+                }
+                EditorEvent::ToggleFavourite(idx) => {
+                    let result = self.presets.toggle_favourite(*idx);
+                    self.set_preset_status(result);
+                    self.update_preset_rows();
+                }
+                EditorEvent::SetPresetSearch(search) => {
+                    self.preset_filter.search = search.clone();
+                    self.update_preset_rows();
+                }
+                EditorEvent::SetPresetCategory(category) => {
+                    self.preset_filter.category = category.clone();
+                    self.update_preset_rows();
+                }
+                EditorEvent::SetPresetTag(tag) => {
+                    self.preset_filter.tag = tag.clone();
+                    self.update_preset_rows();
+                }
+                EditorEvent::ToggleFavouritesOnly => {
+                    self.preset_filter.favourites_only = !self.preset_filter.favourites_only;
+                    self.update_preset_rows();
+                }
+                EditorEvent::SetSaveName(name) => {
+                    self.save_name = name.clone();
+                }
+                EditorEvent::SetSaveAuthor(author) => {
+                    self.save_author = author.clone();
+                }
+                EditorEvent::SetSaveCategory(category) => {
+                    self.save_category = category.clone();
+                }
+                EditorEvent::SetSaveTags(tags) => {
+                    self.save_tags = tags.clone();
+                }
+                EditorEvent::SavePreset => {
+                    let preset = Preset::from_state(
+                        self.save_name.trim().to_owned(),
+                        self.save_author.trim().to_owned(),
+                        self.save_category.trim().to_owned(),
+                        self.save_tags
+                            .split(',')
+                            .map(|tag| tag.trim().to_owned())
+                            .filter(|tag| !tag.is_empty())
+                            .collect(),
+                        self.gui_context.get_state(),
+                    );
+                    let name = preset.name.clone();
+                    let result = self.presets.save(preset).map(|idx| {
+                        self.current_preset = Some(idx);
+                        self.current_preset_name = name;
+                    });
+
+                    self.set_preset_status(result);
+                    self.update_preset_rows();
+                }
+            }
+
+            meta.consume();
+        });
+    }
+}
+
+impl Data {
+    /// Restore a preset's state through the GUI context, so the host sees it as a single change.
+    fn load_preset(&mut self, idx: usize) {
+        let preset = &self.presets.presets()[idx].preset;
+        let state = preset.restore_state(&self.gui_context.get_state());
+        self.gui_context.set_state(state);
+
+        self.current_preset = Some(idx);
+        self.current_preset_name = preset.name.clone();
+        self.preset_status.clear();
+    }
+
+    /// Recompute the preset browser's list, categories, and tags after the filter or the library
+    /// has changed.
+    fn update_preset_rows(&mut self) {
+        self.preset_rows = self
+            .presets
+            .filter(&self.preset_filter)
+            .into_iter()
+            .map(|idx| {
+                let preset = &self.presets.presets()[idx].preset;
+                let details = match (preset.category.as_str(), preset.author.as_str()) {
+                    ("", "") => String::new(),
+                    (category, "") => category.to_owned(),
+                    ("", author) => author.to_owned(),
+                    (category, author) => format!("{category}, {author}"),
+                };
+
+                PresetRow {
+                    idx,
+                    name: preset.name.clone(),
+                    details,
+                    favourite: self.presets.is_favourite(idx),
+                }
+            })
+            .collect();
+        self.preset_categories = self.presets.categories();
+        self.preset_tags = self.presets.tags();
+    }
+
+    fn set_preset_status<T>(&mut self, result: Result<T, String>) {
+        match result {
+            Ok(_) => self.preset_status.clear(),
+            Err(err) => {
+                nih_plug::nih_error!("{err}");
+                self.preset_status = err;
+            }
+        }
+    }
+}
+
+pub(crate) fn default_state() -> Arc<ViziaState> {
+    ViziaState::new(|| (560, 1200))
+}
+
+pub(crate) fn create(
+    params: Arc<AmSynthParams>,
+    meter: Arc<OutputMeter>,
+    analyzer: Arc<Mutex<AnalyzerOutput>>,
+    keyboard: Arc<VirtualKeyboard>,
+    midi_learn: Arc<MidiLearn>,
+    mod_matrix: Arc<ModMatrix>,
+    editor_state: Arc<ViziaState>,
+    async_executor: AsyncExecutor<AmSynth>,
+) -> Option<Box<dyn Editor>> {
+    create_vizia_editor(
+        editor_state,
+        ViziaTheming::Custom,
+        move |cx, gui_context| {
+            assets::register_noto_sans_light(cx);
+            assets::register_noto_sans_thin(cx);
+            let user_themes = crate::util::config_dir()
+                .map(|directory| theme::load_user_themes(cx, directory.join("themes")))
+                .unwrap_or_default();
+
+            // Parameters can only be changed through the GUI context, so MIDI mapped CCs are applied
+            // through this context
+            midi_learn.set_gui_context(gui_context.clone());
+            // The parameter widgets show the mod matrix's modulation, and sources can be dragged onto
+            // them to add routes
+            cx.emit(ModulationEvent::SetProvider(mod_matrix.clone()));
+
+            // The filter responses are computed for the sample rate the plugin was initialized with
+            let sample_rate = analyzer.lock().unwrap().sample_rate();
+
+            let mut data = Data {
+                params: params.clone(),
+                meter: meter.clone(),
+                analyzer: analyzer.clone(),
+                keyboard: keyboard.clone(),
+                mod_matrix: mod_matrix.clone(),
+                async_executor: async_executor.clone(),
+                gui_context,
+                presets: PresetLibrary::load(
+                    crate::util::config_dir().map(|directory| directory.join("presets")),
+                ),
+
+                selected_operator: 0,
+                wavetable_path: String::new(),
+                sample_path: String::new(),
+                breakpoints_status: String::new(),
+                velocity_table_status: String::new(),
+
+                show_preset_browser: false,
+                current_preset: None,
+                current_preset_name: String::from("No preset"),
+                preset_filter: PresetFilter::default(),
+                preset_rows: Vec::new(),
+                preset_categories: Vec::new(),
+                preset_tags: Vec::new(),
+                save_name: String::new(),
+                save_author: String::new(),
+                save_category: String::new(),
+                save_tags: String::new(),
+                preset_status: String::new(),
+            };
+            data.update_preset_rows();
+            data.build(cx);
+
+            VStack::new(cx, |cx| {
+                header(cx);
+                preset_browser(cx);
+
+                // The preset browser takes the place of the other sections while it's open
+                VStack::new(cx, |cx| {
+                    operator_section(cx);
+                    envelope_section(cx);
+                    modulation_section(cx);
+                    velocity_section(cx);
+                    expression_section(cx);
+                    arpeggiator_section(cx);
+                    filter_section(cx, sample_rate);
+                    wavetable_section(cx);
+                    sample_section(cx);
+                    output_section(cx);
+                    analyzer_section(cx);
+                    keyboard_section(cx);
+
+                    ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+                        GenericUi::new(cx, Data::params);
+                    })
+                    .width(Percentage(100.0));
+                })
+                .row_between(Pixels(5.0))
+                .child_left(Stretch(1.0))
+                .child_right(Stretch(1.0))
+                .display(Data::show_preset_browser.map(|show| !show));
+            })
+            .row_between(Pixels(5.0))
+            .child_left(Stretch(1.0))
+            .child_right(Stretch(1.0));
+
+            // These are placed in the bottom right corner, next to the resize handle
+            ThemeSelector::new(cx, user_themes);
+            ZoomSelector::new(cx);
+            ResizeHandle::new(cx);
+
+            // This covers the entire window while it's open, so it needs to be added last
+            ParamContextMenu::with_midi_learn(cx, midi_learn.clone());
+        },
+    )
+}
+
+/// The plugin's name, and the name of the current preset with buttons to open the preset browser
+/// and to step through the presets.
+fn header(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        Label::new(cx, "AM Synth")
+            .font_family(vec![FamilyOwned::Name(String::from(assets::NOTO_SANS))])
+            .font_weight(FontWeightKeyword::Thin)
+            .font_size(30.0)
+            .height(Pixels(50.0))
+            .child_top(Stretch(1.0))
+            .child_bottom(Pixels(0.0));
+
+        HStack::new(cx, |cx| {
+            Button::new(
+                cx,
+                |cx| cx.emit(EditorEvent::StepPreset(-1)),
+                |cx| Label::new(cx, "<"),
+            );
+            Button::new(
+                cx,
+                |cx| cx.emit(EditorEvent::TogglePresetBrowser),
+                |cx| Label::new(cx, Data::current_preset_name),
+            )
+            .checked(Data::show_preset_browser)
+            .width(Pixels(180.0));
+            Button::new(
+                cx,
+                |cx| cx.emit(EditorEvent::StepPreset(1)),
+                |cx| Label::new(cx, ">"),
+            );
+        })
+        .col_between(Pixels(5.0))
+        .left(Stretch(1.0))
+        .top(Stretch(1.0))
+        .height(Auto)
+        .width(Auto);
+    })
+    .height(Pixels(50.0))
+    .width(Pixels(520.0));
+}
+
+/// Search, category, tag, and favourite filters, the list of presets, and controls for saving the
+/// current state as a user preset.
+fn preset_browser(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            Textbox::new(cx, Data::preset_filter.map(|filter| filter.search.clone()))
+                .on_edit(|cx, search| cx.emit(EditorEvent::SetPresetSearch(search)))
+                .width(Stretch(1.0));
+            Button::new(
+                cx,
+                |cx| cx.emit(EditorEvent::ToggleFavouritesOnly),
+                |cx| Label::new(cx, "Favourites"),
+            )
+            .checked(Data::preset_filter.map(|filter| filter.favourites_only));
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+
+        Binding::new(cx, Data::preset_categories, |cx, categories| {
+            filter_buttons(
+                cx,
+                "Category",
+                categories.get(cx),
+                Data::preset_filter.map(|filter| filter.category.clone()),
+                EditorEvent::SetPresetCategory,
+            );
+        });
+        Binding::new(cx, Data::preset_tags, |cx, tags| {
+            filter_buttons(
+                cx,
+                "Tag",
+                tags.get(cx),
+                Data::preset_filter.map(|filter| filter.tag.clone()),
+                EditorEvent::SetPresetTag,
+            );
+        });
+
+        ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+            Binding::new(cx, Data::preset_rows, |cx, rows| {
+                VStack::new(cx, |cx| {
+                    for row in rows.get(cx) {
+                        HStack::new(cx, |cx| {
+                            let idx = row.idx;
+                            Button::new(
+                                cx,
+                                move |cx| cx.emit(EditorEvent::ToggleFavourite(idx)),
+                                |cx| Label::new(cx, "Fav"),
+                            )
+                            .checked(row.favourite);
+                            Button::new(
+                                cx,
+                                move |cx| cx.emit(EditorEvent::LoadPreset(idx)),
+                                |cx| Label::new(cx, &row.name),
+                            )
+                            .checked(Data::current_preset.map(move |current| *current == Some(idx)))
+                            .width(Stretch(1.0));
+                            Label::new(cx, &row.details).width(Pixels(180.0));
+                        })
+                        .col_between(Pixels(5.0))
+                        .height(Auto);
+                    }
+                })
+                .row_between(Pixels(2.0))
+                .height(Auto);
+            });
+        })
+        .height(Pixels(300.0))
+        .width(Percentage(100.0));
+
+        HStack::new(cx, |cx| {
+            labeled_textbox(cx, "Name", Data::save_name, EditorEvent::SetSaveName)
+                .width(Stretch(1.0));
+            labeled_textbox(cx, "Author", Data::save_author, EditorEvent::SetSaveAuthor)
+                .width(Pixels(100.0));
+            labeled_textbox(
+                cx,
+                "Category",
+                Data::save_category,
+                EditorEvent::SetSaveCategory,
+            )
+            .width(Pixels(80.0));
+            labeled_textbox(cx, "Tags", Data::save_tags, EditorEvent::SetSaveTags)
+                .width(Pixels(100.0));
+            Button::new(
+                cx,
+                |cx| cx.emit(EditorEvent::SavePreset),
+                |cx| Label::new(cx, "Save"),
+            )
+            .top(Stretch(1.0));
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+
+        Label::new(cx, Data::preset_status);
+    })
+    .row_between(Pixels(5.0))
+    .height(Auto)
+    .width(Pixels(520.0))
+    .display(Data::show_preset_browser);
+}
+
+/// A row of buttons for filtering the preset list by one of `values`, with an `All` button that
+/// removes the filter.
+fn filter_buttons<L>(
+    cx: &mut Context,
+    name: &str,
+    values: Vec<String>,
+    selected: L,
+    event: fn(Option<String>) -> EditorEvent,
+) where
+    L: Lens<Target = Option<String>> + Clone,
+{
+    HStack::new(cx, |cx| {
+        Label::new(cx, name).class("label").width(Pixels(60.0));
+        Button::new(
+            cx,
+            move |cx| cx.emit(event(None)),
+            |cx| Label::new(cx, "All"),
+        )
+        .checked(selected.clone().map(|selected| selected.is_none()));
+        for value in values {
+            let label = value.clone();
+            let is_selected = selected.clone().map({
+                let value = value.clone();
+                move |selected| selected.as_ref() == Some(&value)
+            });
+            Button::new(
+                cx,
+                move |cx| cx.emit(event(Some(value.clone()))),
+                move |cx| Label::new(cx, &label),
+            )
+            .checked(is_selected);
+        }
+    })
+    .col_between(Pixels(5.0))
+    .height(Auto);
+}
+
+/// A text box with a label above it. The text is passed to `event` when it's submitted.
+fn labeled_textbox<'a>(
+    cx: &'a mut Context,
+    name: &str,
+    text: impl Lens<Target = String>,
+    event: fn(String) -> EditorEvent,
+) -> Handle<'a, VStack> {
+    VStack::new(cx, |cx| {
+        Label::new(cx, name);
+        Textbox::new(cx, text)
+            .on_submit(move |cx, text, _| cx.emit(event(text)))
+            .width(Stretch(1.0));
+    })
+    .row_between(Pixels(2.0))
+    .height(Auto)
+}
+
+/// An operator selector, and knobs for the selected operator's most important parameters.
+fn operator_section(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Operator").class("label");
+            for operator in 0..MAX_OPERATORS {
+                Button::new(
+                    cx,
+                    move |cx| cx.emit(EditorEvent::SelectOperator(operator)),
+                    move |cx| Label::new(cx, &format!("Op {}", operator + 1)),
+                )
+                .checked(Data::selected_operator.map(move |selected| *selected == operator));
+            }
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+
+        // The knobs need to be rebuilt for the newly selected operator's parameters
+        Binding::new(cx, Data::selected_operator, |cx, selected_operator| {
+            let operator = selected_operator.get(cx);
+            HStack::new(cx, |cx| {
+                labeled_knob(cx, "Ratio", move |params| &params.operator(operator).ratio);
+                labeled_knob(cx, "Level", move |params| &params.operator(operator).level);
+                labeled_knob(cx, "Feedback", move |params| {
+                    &params.operator(operator).feedback
+                });
+                labeled_knob(cx, "Position", move |params| {
+                    &params.operator(operator).wavetable_position
+                });
+                EnvelopeEditor::new(cx, Data::params, move |params| {
+                    let params = params.operator(operator);
+                    [
+                        &params.attack,
+                        &params.decay,
+                        &params.sustain,
+                        &params.release,
+                    ]
+                })
+                .left(Stretch(1.0));
+            })
+            .height(Auto);
+
+            HStack::new(cx, |cx| {
+                labeled_knob(cx, "Delay", move |params| &params.operator(operator).delay);
+                labeled_knob(cx, "Hold", move |params| &params.operator(operator).hold);
+                labeled_knob(cx, "Mode", move |params| {
+                    &params.operator(operator).envelope_mode
+                });
+                breakpoints_textbox(cx, EnvelopeTarget::Operator(operator));
+            })
+            .col_between(Pixels(5.0))
+            .height(Auto);
+        });
+    })
+    .row_between(Pixels(5.0))
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// The global envelope that's applied to the voice's output and the envelope that's only used for
+/// modulation, with the shapes of their segments, their delay and hold times, and their
+/// multi-stage shapes.
+fn envelope_section(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Envelope").class("label");
+            labeled_knob(cx, "A Curve", |params| &params.global_attack_curve);
+            labeled_knob(cx, "D Curve", |params| &params.global_decay_curve);
+            labeled_knob(cx, "R Curve", |params| &params.global_release_curve);
+            EnvelopeEditor::new(cx, Data::params, |params| {
+                [
+                    &params.global_attack,
+                    &params.global_decay,
+                    &params.global_sustain,
+                    &params.global_release,
+                ]
+            })
+            .left(Stretch(1.0));
+        })
+        .col_between(Pixels(10.0))
+        .height(Auto);
+
+        HStack::new(cx, |cx| {
+            labeled_knob(cx, "Delay", |params| &params.global_delay);
+            labeled_knob(cx, "Hold", |params| &params.global_hold);
+            labeled_knob(cx, "Mode", |params| &params.global_envelope_mode);
+            ParamButton::new(cx, Data::params, |params| &params.envelope_sync)
+                .with_label("Sync")
+                .top(Stretch(1.0));
+            breakpoints_textbox(cx, EnvelopeTarget::Global);
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Mod Env").class("label");
+            labeled_knob(cx, "A Curve", |params| &params.mod_env_attack_curve);
+            labeled_knob(cx, "D Curve", |params| &params.mod_env_decay_curve);
+            labeled_knob(cx, "R Curve", |params| &params.mod_env_release_curve);
+            EnvelopeEditor::new(cx, Data::params, |params| {
+                [
+                    &params.mod_env_attack,
+                    &params.mod_env_decay,
+                    &params.mod_env_sustain,
+                    &params.mod_env_release,
+                ]
+            })
+            .left(Stretch(1.0));
+        })
+        .col_between(Pixels(10.0))
+        .height(Auto);
+
+        HStack::new(cx, |cx| {
+            labeled_knob(cx, "Delay", |params| &params.mod_env_delay);
+            labeled_knob(cx, "Hold", |params| &params.mod_env_hold);
+            labeled_knob(cx, "Mode", |params| &params.mod_env_mode);
+            breakpoints_textbox(cx, EnvelopeTarget::Mod);
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+
+        Label::new(cx, Data::breakpoints_status);
+    })
+    .row_between(Pixels(5.0))
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// A text box for an envelope's multi-stage shape. See [`Breakpoints`][crate::envelope::Breakpoints]
+/// for the format.
+fn breakpoints_textbox(cx: &mut Context, target: EnvelopeTarget) {
+    Textbox::new(
+        cx,
+        Data::params.map(move |params| params.breakpoints(target).read().unwrap().to_string()),
+    )
+    .on_submit(move |cx, text, _| cx.emit(EditorEvent::SetBreakpoints(target, text)))
+    .width(Stretch(1.0))
+    .top(Stretch(1.0));
+}
+
+/// The LFOs, the modulation sources that can be dragged onto parameter widgets, and the mod
+/// matrix's routes with their amounts.
+fn modulation_section(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Modulation").class("label");
+            ModulationSources::new(cx);
+        })
+        .col_between(Pixels(10.0))
+        .height(Auto);
+
+        HStack::new(cx, |cx| {
+            for lfo in 0..NUM_LFOS {
+                labeled_knob(cx, &format!("LFO {} Rate", lfo + 1), move |params| {
+                    &params.lfos[lfo].rate
+                });
+                labeled_knob(cx, &format!("LFO {} Shape", lfo + 1), move |params| {
+                    &params.lfos[lfo].shape
+                });
+            }
+        })
+        .height(Auto);
+
+        // The rows need to be rebuilt when a source is dragged onto a parameter or a route is
+        // cleared
+        Binding::new(
+            cx,
+            Data::mod_matrix.map(|mod_matrix| mod_matrix.route_names()),
+            |cx, routes| {
+                let routes = routes.get(cx);
+                if routes.is_empty() {
+                    Label::new(cx, "Drag a source onto a knob or slider to modulate it");
+                }
+                for (slot, name) in routes {
+                    HStack::new(cx, |cx| {
+                        Label::new(cx, &name).width(Stretch(1.0));
+                        ParamSlider::new(cx, Data::params, move |params| {
+                            &params.mod_slots[slot].amount
+                        })
+                        .height(Pixels(24.0));
+                        Button::new(
+                            cx,
+                            move |cx| cx.emit(EditorEvent::ClearModSlot(slot)),
+                            |cx| Label::new(cx, "Clear"),
+                        );
+                    })
+                    .col_between(Pixels(5.0))
+                    .height(Auto);
+                }
+            },
+        );
+    })
+    .row_between(Pixels(5.0))
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// The velocity curve, the user curve's points, and how much the velocity changes the level, the
+/// mod depth, the modulators' envelopes, and the global filter's cutoff.
+fn velocity_section(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Velocity").class("label");
+            labeled_knob(cx, "Curve", |params| &params.velocity_curve);
+            labeled_knob(cx, "Level", |params| &params.velocity_sensitivity);
+            labeled_knob(cx, "Depth", |params| &params.velocity_mod_depth);
+            labeled_knob(cx, "Mod Env", |params| &params.velocity_modulator_envelope);
+            labeled_knob(cx, "Cutoff", |params| &params.velocity_cutoff);
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+
+        // The user curve's points, see `VelocityTable` for the format
+        Textbox::new(
+            cx,
+            Data::params.map(|params| params.velocity_table.read().unwrap().to_string()),
+        )
+        .on_submit(|cx, text, _| cx.emit(EditorEvent::SetVelocityTable(text)))
+        .width(Stretch(1.0));
+        Label::new(cx, Data::velocity_table_status);
+    })
+    .row_between(Pixels(5.0))
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// The MPE settings, and where the notes' pressure and slide go.
+fn expression_section(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        Label::new(cx, "Expression").class("label");
+        ParamButton::new(cx, Data::params, |params| &params.mpe_enabled)
+            .with_label("MPE")
+            .top(Stretch(1.0));
+        labeled_knob(cx, "Zone", |params| &params.mpe_zone);
+        labeled_knob(cx, "Bend", |params| &params.mpe_bend_range);
+        labeled_knob(cx, "Pressure", |params| &params.pressure_target);
+        labeled_knob(cx, "Amount", |params| &params.pressure_amount);
+        labeled_knob(cx, "Slide", |params| &params.slide_amount);
+    })
+    .col_between(Pixels(5.0))
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// The arpeggiator's settings, and a row with every step's on/off button and velocity.
+fn arpeggiator_section(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Arp").class("label");
+            ParamButton::new(cx, Data::params, |params| &params.arp.enabled)
+                .with_label("On")
+                .top(Stretch(1.0));
+            ParamButton::new(cx, Data::params, |params| &params.arp.latch)
+                .with_label("Latch")
+                .top(Stretch(1.0));
+            labeled_knob(cx, "Order", |params| &params.arp.order);
+            labeled_knob(cx, "Octaves", |params| &params.arp.octaves);
+            labeled_knob(cx, "Rate", |params| &params.arp.rate);
+            labeled_knob(cx, "Gate", |params| &params.arp.gate);
+            labeled_knob(cx, "Swing", |params| &params.arp.swing);
+            labeled_knob(cx, "Length", |params| &params.arp.length);
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+
+        HStack::new(cx, |cx| {
+            for step in 0..ARP_STEPS {
+                VStack::new(cx, |cx| {
+                    ParamButton::new(cx, Data::params, move |params| &params.arp.steps[step].on)
+                        .with_label(&(step + 1).to_string())
+                        .width(Stretch(1.0));
+                    ParamSlider::new(cx, Data::params, move |params| {
+                        &params.arp.steps[step].velocity
+                    })
+                    .height(Pixels(20.0))
+                    .width(Stretch(1.0));
+                })
+                .row_between(Pixels(2.0))
+                .height(Auto);
+            }
+        })
+        .col_between(Pixels(2.0))
+        .height(Auto);
+    })
+    .row_between(Pixels(5.0))
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// The response curves of the carrier, modulator, and global filters.
+fn filter_section(cx: &mut Context, sample_rate: f32) {
+    HStack::new(cx, |cx| {
+        filter_response(
+            cx,
+            "Carrier Filter",
+            sample_rate,
+            |params| &params.carrier_filter_enabled,
+            |params| {
+                (
+                    &params.carrier_filter_type,
+                    &params.carrier_filter_cutoff,
+                    &params.carrier_filter_resonance,
+                )
+            },
+        );
+        filter_response(
+            cx,
+            "Modulator Filter",
+            sample_rate,
+            |params| &params.modulator_filter_enabled,
+            |params| {
+                (
+                    &params.modulator_filter_type,
+                    &params.modulator_filter_cutoff,
+                    &params.modulator_filter_resonance,
+                )
+            },
+        );
+        filter_response(
+            cx,
+            "Global Filter",
+            sample_rate,
+            |params| &params.global_filter_enabled,
+            |params| {
+                (
+                    &params.global_filter_type,
+                    &params.global_filter_cutoff,
+                    &params.global_filter_resonance,
+                )
+            },
+        );
+    })
+    .col_between(Stretch(1.0))
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// A [`FilterResponse`] for a filter's type, cutoff, and resonance parameters with the given name
+/// and a button that turns the filter on above it.
+fn filter_response(
+    cx: &mut Context,
+    name: &str,
+    sample_rate: f32,
+    params_to_enabled: impl Fn(&Arc<AmSynthParams>) -> &BoolParam + Copy + 'static,
+    params_to_filter: impl Fn(&Arc<AmSynthParams>) -> (&BoolParam, &FloatParam, &FloatParam)
+        + Copy
+        + 'static,
+) {
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            Label::new(cx, name);
+            ParamButton::new(cx, Data::params, params_to_enabled).with_label("On");
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+        FilterResponse::new(
+            cx,
+            Data::params,
+            move |params| {
+                let (_, cutoff, resonance) = params_to_filter(params);
+                [cutoff, resonance]
+            },
+            move |params, frequency| {
+                let (filter_type, cutoff, resonance) = params_to_filter(params);
+                let mut filter = ResonantFilter::new(sample_rate);
+                filter.set_filter_type(filter_type.value());
+                filter.set_params(cutoff.value(), resonance.value());
+
+                util::gain_to_db(filter.magnitude_response(frequency))
+            },
+        );
+    })
+    .height(Auto)
+    .width(Auto);
+}
+
+/// A [`ParamKnob`] with the given name above it.
+fn labeled_knob<P: Param + 'static>(
+    cx: &mut Context,
+    name: &str,
+    params_to_param: impl Fn(&Arc<AmSynthParams>) -> &P + Copy + 'static,
+) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, name);
+        ParamKnob::new(cx, Data::params, params_to_param);
+    })
+    .child_left(Stretch(1.0))
+    .child_right(Stretch(1.0))
+    .height(Auto)
+    .width(Pixels(65.0));
+}
+
+/// Controls for picking a built-in wavetable or importing a WAV file for the selected operator.
+fn wavetable_section(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        // The source's name needs to be looked up again when a different operator is selected
+        Binding::new(cx, Data::selected_operator, |cx, selected_operator| {
+            let operator = selected_operator.get(cx);
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Wavetable").class("label");
+                Label::new(
+                    cx,
+                    Data::params.map(move |params| {
+                        params.wavetable_source(operator).read().unwrap().name()
+                    }),
+                );
+            })
+            .col_between(Pixels(10.0))
+            .height(Auto);
+        });
+
+        HStack::new(cx, |cx| {
+            for builtin in BuiltinWavetable::ALL {
+                Button::new(
+                    cx,
+                    move |cx| cx.emit(EditorEvent::LoadWavetable(Some(builtin))),
+                    |cx| Label::new(cx, builtin.name()),
+                );
+            }
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+
+        HStack::new(cx, |cx| {
+            Textbox::new(cx, Data::wavetable_path)
+                .on_submit(|cx, path, _| cx.emit(EditorEvent::SetWavetablePath(path)))
+                .width(Stretch(1.0));
+            Button::new(
+                cx,
+                |cx| cx.emit(EditorEvent::LoadWavetable(None)),
+                |cx| Label::new(cx, "Load"),
+            );
+            Button::new(
+                cx,
+                |cx| cx.emit(EditorEvent::EmbedWavetable),
+                |cx| Label::new(cx, "Embed"),
+            );
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+    })
+    .row_between(Pixels(5.0))
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// Controls for loading a WAV or FLAC file into the sample playback engine.
+fn sample_section(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            Label::new(cx, "Sample").class("label");
+            Label::new(
+                cx,
+                Data::params.map(|params| {
+                    params
+                        .sample
+                        .read()
+                        .unwrap()
+                        .as_ref()
+                        .and_then(|path| path.file_name())
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| String::from("None"))
+                }),
+            );
+        })
+        .col_between(Pixels(10.0))
+        .height(Auto);
+
+        HStack::new(cx, |cx| {
+            Textbox::new(cx, Data::sample_path)
+                .on_submit(|cx, path, _| cx.emit(EditorEvent::SetSamplePath(path)))
+                .width(Stretch(1.0));
+            Button::new(
+                cx,
+                |cx| cx.emit(EditorEvent::LoadSample),
+                |cx| Label::new(cx, "Load"),
+            );
+        })
+        .col_between(Pixels(5.0))
+        .height(Auto);
+    })
+    .row_between(Pixels(5.0))
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// Stereo peak and RMS meters for the plugin's output.
+fn output_section(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        Label::new(cx, "Output").class("label");
+        PeakMeter::new_stereo_with_rms(
+            cx,
+            Data::meter.map(|meter| [meter.peak_dbfs(0), meter.peak_dbfs(1)]),
+            Data::meter.map(|meter| [meter.rms_dbfs(0), meter.rms_dbfs(1)]),
+            Some(Duration::from_millis(600)),
+        )
+        .range(-60.0, 6.0)
+        .ticks([
+            MeterTick::new(-60.0, "-inf"),
+            MeterTick::db(-48.0),
+            MeterTick::db(-36.0),
+            MeterTick::db(-24.0),
+            MeterTick::db(-12.0),
+            MeterTick::db(0.0),
+            MeterTick::new(6.0, "dBFS"),
+        ])
+        .width(Stretch(1.0));
+    })
+    .col_between(Pixels(10.0))
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// An oscilloscope and a spectrum analyzer showing the plugin's output.
+fn analyzer_section(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        Oscilloscope::new(
+            cx,
+            Data::analyzer.map(|analyzer| analyzer.lock().unwrap().waveform()),
+        );
+        SpectrumAnalyzer::new(
+            cx,
+            Data::analyzer.map(|analyzer| analyzer.lock().unwrap().spectrum()),
+        )
+        .left(Stretch(1.0));
+    })
+    .height(Auto)
+    .width(Pixels(520.0));
+}
+
+/// A piano keyboard for auditioning the patch without a MIDI controller.
+fn keyboard_section(cx: &mut Context) {
+    PianoKeyboard::new(
+        cx,
+        36..=96,
+        Data::keyboard.map(|keyboard| keyboard.held_notes()),
+    )
+    .on_note_on(|cx, note, velocity| cx.emit(EditorEvent::NoteOn(note, velocity)))
+    .on_note_off(|cx, note| cx.emit(EditorEvent::NoteOff(note)))
+    .height(Pixels(70.0))
+    .width(Pixels(520.0));
+}
@@ -1,76 +1,737 @@
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How steep the most curved segments are. A curve of 1 or -1 corresponds to an exponential curve
+/// that covers this many time constants over the segment.
+const CURVE_STEEPNESS: f32 = 5.0;
+
+/// The maximum number of points in a multi-stage envelope.
+pub const MAX_BREAKPOINTS: usize = 16;
+/// The longest a multi-stage envelope's segment can take, in seconds.
+const MAX_BREAKPOINT_TIME: f32 = 30.0;
+
+/// Tempo synced envelopes take their stage times at this tempo. At other tempos the times are
+/// scaled so they cover the same number of beats.
+pub const SYNC_TEMPO: f64 = 120.0;
+
+/// A DAHDSR envelope with curved segments, which can also follow a multi-stage [`Breakpoints`]
+/// shape instead. Every segment takes exactly its stage's time, rounded to whole samples, to go
+/// from the level it started at to its target. The release always starts from the envelope's
+/// current level, and retriggering a note restarts the envelope from the current level instead of
+/// from zero, so neither causes a click.
 pub struct Envelope {
+    delay: f32,
     attack: f32,
+    hold: f32,
     decay: f32,
     sustain: f32,
     release: f32,
+    curves: EnvelopeCurves,
+    mode: EnvelopeMode,
+    breakpoints: Breakpoints,
+    /// Multiplies every stage's time. Used to sync the stage times to the host's tempo.
+    time_scale: f32,
     stage: EnvelopeStage,
-    output: f32,
+    /// Whether the note has been released. Multi-stage envelopes only loop and stop at their
+    /// sustain point while the note is held.
+    released: bool,
+    value: f32,
+    /// The level the current segment started at.
+    start: f32,
+    /// The number of samples since the current segment started.
+    elapsed: f32,
     sample_rate: f32,
 }
 
+/// The shapes of the attack, decay, and release segments. Curves range from -1 for exponential
+/// segments, through 0 for linear segments, to 1 for logarithmic segments. Exponential segments
+/// rise slowly and fall quickly at first, and logarithmic segments do the opposite. An analog
+/// envelope has a logarithmic attack and an exponential decay and release.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvelopeCurves {
+    pub attack: f32,
+    pub decay: f32,
+    pub release: f32,
+}
+
+/// Whether an envelope uses its DAHDSR stages or its multi-stage shape.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeMode {
+    #[name = "DAHDSR"]
+    Dahdsr,
+    #[name = "Multi-Stage"]
+    MultiStage,
+}
+
+/// A point in a multi-stage envelope. The segment leading up to the point takes `time` seconds to
+/// reach `level`, and its shape is set by `curve`, see [`EnvelopeCurves`] for the range.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Breakpoint {
+    pub time: f32,
+    pub level: f32,
+    pub curve: f32,
+}
+
+/// A multi-stage envelope's shape with up to [`MAX_BREAKPOINTS`] points. The envelope starts at its
+/// current level and moves through the points in order. While the note is held, reaching the loop
+/// end continues with the segment after the loop start, starting from the current level so the
+/// loop never jumps, and reaching the sustain point holds its level. Releasing the note continues
+/// with the segment after the sustain point. If the last point is not at zero, the envelope fades
+/// out over the release time after reaching it.
+///
+/// Shapes are written as comma separated points, each with a time, a level, and an optional curve.
+/// The points can be marked with `sustain`, `loop-start`, and `loop-end`, e.g. `0.5 1 0.5, 1 0.6
+/// sustain, 0.5 0`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SerializedBreakpoints", into = "SerializedBreakpoints")]
+pub struct Breakpoints {
+    /// Only the first `len` points are used. This is a fixed size array so the audio thread can
+    /// copy shapes without allocating.
+    points: [Breakpoint; MAX_BREAKPOINTS],
+    len: usize,
+    sustain: Option<usize>,
+    /// The indices of the loop's start and end points. The start always comes before the end.
+    loop_region: Option<(usize, usize)>,
+}
+
+/// The persisted form of [`Breakpoints`], without the unused points.
+#[derive(Serialize, Deserialize)]
+struct SerializedBreakpoints {
+    points: Vec<Breakpoint>,
+    sustain: Option<usize>,
+    #[serde(rename = "loop")]
+    loop_region: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum EnvelopeStage {
     Idle,
+    Delay,
     Attack,
+    Hold,
     Decay,
     Sustain,
     Release,
+    /// Moving towards the multi-stage shape's point with this index.
+    Breakpoint(usize),
+    /// Holding the multi-stage shape's sustain point.
+    BreakpointSustain,
+}
+
+impl Default for EnvelopeCurves {
+    fn default() -> Self {
+        Self {
+            attack: 0.5,
+            decay: -0.5,
+            release: -0.5,
+        }
+    }
+}
+
+impl Default for Breakpoints {
+    /// A slow swell that keeps moving between two levels while the note is held.
+    fn default() -> Self {
+        let point = |time, level, curve| Breakpoint { time, level, curve };
+        Self::new(
+            &[
+                point(0.5, 1.0, 0.5),
+                point(0.5, 0.6, -0.5),
+                point(1.0, 0.8, 0.0),
+                point(1.0, 0.6, 0.0),
+                point(0.5, 0.0, -0.5),
+            ],
+            Some(3),
+            Some((1, 3)),
+        )
+    }
+}
+
+impl Breakpoints {
+    /// Create a shape from its points. Points beyond [`MAX_BREAKPOINTS`] are dropped, the points'
+    /// values are clamped to their ranges, and a sustain point or a loop that does not fit the
+    /// points is ignored.
+    pub fn new(
+        points: &[Breakpoint],
+        sustain: Option<usize>,
+        loop_region: Option<(usize, usize)>,
+    ) -> Self {
+        let len = points.len().min(MAX_BREAKPOINTS);
+        let mut clamped_points = [Breakpoint {
+            time: 0.0,
+            level: 0.0,
+            curve: 0.0,
+        }; MAX_BREAKPOINTS];
+        for (clamped, point) in clamped_points.iter_mut().zip(&points[..len]) {
+            *clamped = Breakpoint {
+                time: point.time.clamp(0.0, MAX_BREAKPOINT_TIME),
+                level: point.level.clamp(0.0, 1.0),
+                curve: point.curve.clamp(-1.0, 1.0),
+            };
+        }
+
+        Self {
+            points: clamped_points,
+            len,
+            sustain: sustain.filter(|sustain| *sustain < len),
+            loop_region: loop_region.filter(|(start, end)| start < end && *end < len),
+        }
+    }
+
+    pub fn points(&self) -> &[Breakpoint] {
+        &self.points[..self.len]
+    }
+
+    /// The stage after the sustain point, used when the note is released.
+    fn after_sustain(&self, sustain: usize) -> EnvelopeStage {
+        if sustain + 1 < self.len {
+            EnvelopeStage::Breakpoint(sustain + 1)
+        } else {
+            EnvelopeStage::Release
+        }
+    }
+}
+
+impl From<SerializedBreakpoints> for Breakpoints {
+    fn from(serialized: SerializedBreakpoints) -> Self {
+        Self::new(
+            &serialized.points,
+            serialized.sustain,
+            serialized.loop_region,
+        )
+    }
+}
+
+impl From<Breakpoints> for SerializedBreakpoints {
+    fn from(breakpoints: Breakpoints) -> Self {
+        Self {
+            points: breakpoints.points().to_vec(),
+            sustain: breakpoints.sustain,
+            loop_region: breakpoints.loop_region,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, point) in self.points().iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {} {}", point.time, point.level, point.curve)?;
+
+            if self.loop_region.is_some_and(|(start, _)| start == idx) {
+                write!(f, " loop-start")?;
+            }
+            if self.loop_region.is_some_and(|(_, end)| end == idx) {
+                write!(f, " loop-end")?;
+            }
+            if self.sustain == Some(idx) {
+                write!(f, " sustain")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Breakpoints {
+    type Err = String;
+
+    /// Parse the format written by the [`Display`][fmt::Display] implementation.
+    fn from_str(string: &str) -> Result<Self, String> {
+        let mut points = Vec::new();
+        let mut sustain = None;
+        let mut loop_start = None;
+        let mut loop_end = None;
+        for (idx, point) in string.split(',').enumerate() {
+            let mut numbers = Vec::new();
+            for token in point.split_whitespace() {
+                match token {
+                    "sustain" => sustain = Some(idx),
+                    "loop-start" => loop_start = Some(idx),
+                    "loop-end" => loop_end = Some(idx),
+                    _ => numbers.push(
+                        token
+                            .parse::<f32>()
+                            .map_err(|_| format!("'{token}' is not a number"))?,
+                    ),
+                }
+            }
+
+            points.push(match numbers[..] {
+                [time, level] => Breakpoint {
+                    time,
+                    level,
+                    curve: 0.0,
+                },
+                [time, level, curve] => Breakpoint { time, level, curve },
+                _ => {
+                    return Err(format!(
+                        "Point {} needs a time, a level, and an optional curve",
+                        idx + 1
+                    ))
+                }
+            });
+        }
+
+        if points.len() > MAX_BREAKPOINTS {
+            return Err(format!(
+                "Envelopes can have at most {MAX_BREAKPOINTS} points"
+            ));
+        }
+        let loop_region = match (loop_start, loop_end) {
+            (None, None) => None,
+            (Some(start), Some(end)) if start < end => Some((start, end)),
+            _ => return Err(String::from("The loop needs to start before it ends")),
+        };
+
+        Ok(Self::new(&points, sustain, loop_region))
+    }
 }
 
 impl Envelope {
     pub fn new(sample_rate: f32) -> Self {
         Self {
+            delay: 0.0,
             attack: 0.01,
+            hold: 0.0,
             decay: 0.1,
             sustain: 0.5,
             release: 0.1,
+            curves: EnvelopeCurves::default(),
+            mode: EnvelopeMode::Dahdsr,
+            breakpoints: Breakpoints::default(),
+            time_scale: 1.0,
             stage: EnvelopeStage::Idle,
-            output: 0.0,
+            released: false,
+            value: 0.0,
+            start: 0.0,
+            elapsed: 0.0,
             sample_rate,
         }
     }
 
-    pub fn set_params(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
+    /// Set the stage times in seconds and the sustain level. These can be changed at any point,
+    /// and a segment that's in progress continues from its current position.
+    pub fn set_parameters(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
         self.attack = attack;
         self.decay = decay;
         self.sustain = sustain;
         self.release = release;
     }
 
+    /// Set the time in seconds before the attack starts, and the time the envelope stays at its
+    /// peak before the decay. Stages shorter than a sample are skipped.
+    pub fn set_delay_and_hold(&mut self, delay: f32, hold: f32) {
+        self.delay = delay;
+        self.hold = hold;
+    }
+
+    pub fn set_curves(&mut self, curves: EnvelopeCurves) {
+        self.curves = curves;
+    }
+
+    /// Choose between the DAHDSR stages and the multi-stage shape. This takes effect the next time
+    /// the envelope is triggered.
+    pub fn set_mode(&mut self, mode: EnvelopeMode) {
+        self.mode = mode;
+    }
+
+    /// Set the shape used in the multi-stage mode. If the envelope is moving towards a point that
+    /// no longer exists, it's released.
+    pub fn set_breakpoints(&mut self, breakpoints: &Breakpoints) {
+        self.breakpoints = *breakpoints;
+        if matches!(self.stage, EnvelopeStage::Breakpoint(point) if point >= breakpoints.len) {
+            self.start_segment(EnvelopeStage::Release);
+        }
+    }
+
+    /// Multiply every stage's time by `time_scale`, e.g. to follow the host's tempo.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale;
+    }
+
+    /// Change the sample rate. A segment that's in progress continues from the same point in time.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.elapsed = self.elapsed * sample_rate / self.sample_rate;
+        self.sample_rate = sample_rate;
+    }
+
+    /// Start the envelope from its current level.
     pub fn trigger(&mut self) {
-        self.stage = EnvelopeStage::Attack;
-        self.output = 0.0;
+        self.released = false;
+        if self.mode == EnvelopeMode::MultiStage && self.breakpoints.len > 0 {
+            self.start_segment(EnvelopeStage::Breakpoint(0));
+        } else {
+            self.start_segment(EnvelopeStage::Delay);
+        }
     }
 
+    /// Start the release from the envelope's current level. Does nothing if the envelope is idle.
+    /// Multi-stage envelopes continue after their sustain point, or play their remaining points
+    /// without looping if they don't have one.
     pub fn release(&mut self) {
-        self.stage = EnvelopeStage::Release;
+        if self.stage == EnvelopeStage::Idle {
+            return;
+        }
+
+        self.released = true;
+        let next_stage = match (self.stage, self.breakpoints.sustain) {
+            (EnvelopeStage::Breakpoint(point), Some(sustain)) if point <= sustain => {
+                self.breakpoints.after_sustain(sustain)
+            }
+            (EnvelopeStage::Breakpoint(_), _) => return,
+            (EnvelopeStage::BreakpointSustain, Some(sustain)) => {
+                self.breakpoints.after_sustain(sustain)
+            }
+            _ => EnvelopeStage::Release,
+        };
+        self.start_segment(next_stage);
+    }
+
+    /// The envelope's last output.
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Whether the note has not been released yet.
+    pub fn is_held(&self) -> bool {
+        !self.released && self.stage != EnvelopeStage::Idle
+    }
+
+    /// Whether the release has finished, or the envelope has never been triggered.
+    pub fn is_idle(&self) -> bool {
+        self.stage == EnvelopeStage::Idle
     }
 
     pub fn process(&mut self) -> f32 {
+        let (time, target, curve) = match self.stage {
+            EnvelopeStage::Idle => {
+                self.value = 0.0;
+                return self.value;
+            }
+            EnvelopeStage::Sustain => {
+                self.value = self.sustain;
+                return self.value;
+            }
+            EnvelopeStage::BreakpointSustain => return self.value,
+            // The delay and the hold keep the level the stage started at
+            EnvelopeStage::Delay => (self.delay, self.start, 0.0),
+            EnvelopeStage::Attack => (self.attack, 1.0, self.curves.attack),
+            EnvelopeStage::Hold => (self.hold, self.start, 0.0),
+            EnvelopeStage::Decay => (self.decay, self.sustain, self.curves.decay),
+            EnvelopeStage::Release => (self.release, 0.0, self.curves.release),
+            EnvelopeStage::Breakpoint(point) => {
+                let point = self.breakpoints.points[point];
+                (point.time, point.level, point.curve)
+            }
+        };
+
+        self.elapsed += 1.0;
+        let length = self.length(time).max(1.0);
+        if self.elapsed >= length {
+            self.value = target;
+            self.start_segment(self.next_stage());
+        } else {
+            self.value = segment_value(self.start, target, self.elapsed / length, curve);
+        }
+
+        self.value
+    }
+
+    /// The length of a stage that takes `time` seconds, in whole samples.
+    fn length(&self, time: f32) -> f32 {
+        (time * self.time_scale * self.sample_rate).round()
+    }
+
+    /// The stage that follows the current stage once it has reached its target.
+    fn next_stage(&self) -> EnvelopeStage {
         match self.stage {
-            EnvelopeStage::Idle => {}
-            EnvelopeStage::Attack => {
-                self.output += 1.0 / (self.attack * self.sample_rate);
-                if self.output >= 1.0 {
-                    self.output = 1.0;
-                    self.stage = EnvelopeStage::Decay;
+            EnvelopeStage::Delay => EnvelopeStage::Attack,
+            EnvelopeStage::Attack => EnvelopeStage::Hold,
+            EnvelopeStage::Hold => EnvelopeStage::Decay,
+            EnvelopeStage::Decay => EnvelopeStage::Sustain,
+            EnvelopeStage::Release => EnvelopeStage::Idle,
+            EnvelopeStage::Breakpoint(point) => {
+                let held = !self.released;
+                match self.breakpoints.loop_region {
+                    Some((start, end)) if held && end == point => {
+                        EnvelopeStage::Breakpoint(start + 1)
+                    }
+                    _ if held && self.breakpoints.sustain == Some(point) => {
+                        EnvelopeStage::BreakpointSustain
+                    }
+                    _ if point + 1 < self.breakpoints.len => EnvelopeStage::Breakpoint(point + 1),
+                    _ if self.value > 0.0 => EnvelopeStage::Release,
+                    _ => EnvelopeStage::Idle,
                 }
             }
-            EnvelopeStage::Decay => {
-                self.output -= (1.0 - self.sustain) / (self.decay * self.sample_rate);
-                if self.output <= self.sustain {
-                    self.output = self.sustain;
-                    self.stage = EnvelopeStage::Sustain;
-                }
+            stage => stage,
+        }
+    }
+
+    fn start_segment(&mut self, stage: EnvelopeStage) {
+        self.stage = stage;
+        self.start = self.value;
+        self.elapsed = 0.0;
+
+        // A delay or hold that's shorter than a sample is skipped so it doesn't add any latency
+        match stage {
+            EnvelopeStage::Delay if self.length(self.delay) == 0.0 => {
+                self.start_segment(EnvelopeStage::Attack)
             }
-            EnvelopeStage::Sustain => {}
-            EnvelopeStage::Release => {
-                self.output -= self.sustain / (self.release * self.sample_rate);
-                if self.output <= 0.0 {
-                    self.output = 0.0;
-                    self.stage = EnvelopeStage::Idle;
-                }
+            EnvelopeStage::Hold if self.length(self.hold) == 0.0 => {
+                self.start_segment(EnvelopeStage::Decay)
             }
+            _ => (),
+        }
+    }
+}
+
+/// The level at position `t` in `[0, 1]` of a segment that goes from `start` to `target`. See
+/// [`EnvelopeCurves`] for the curve's range.
+fn segment_value(start: f32, target: f32, t: f32, curve: f32) -> f32 {
+    // The curve describes the segment's shape, so a falling segment's progress needs to be bent the
+    // other way to get the same shape
+    let k = if target >= start { curve } else { -curve } * CURVE_STEEPNESS;
+    let progress = if k.abs() < 1e-3 {
+        t
+    } else {
+        (1.0 - (-k * t).exp()) / (1.0 - (-k).exp())
+    };
+
+    start + (target - start) * progress
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    /// Process the envelope until it reaches `stage`, and return the number of samples that took.
+    fn samples_until(envelope: &mut Envelope, stage: EnvelopeStage) -> usize {
+        let mut samples = 0;
+        while envelope.stage != stage {
+            envelope.process();
+            samples += 1;
+            assert!(
+                samples < SAMPLE_RATE as usize * 10,
+                "{stage:?} was never reached"
+            );
+        }
+
+        samples
+    }
+
+    #[test]
+    fn exact_stage_times() {
+        for sample_rate in [44100.0, 48000.0, 96000.0, 192000.0] {
+            let mut envelope = Envelope::new(sample_rate);
+            envelope.set_parameters(0.01, 0.1, 0.5, 0.2);
+            envelope.trigger();
+
+            assert_eq!(
+                samples_until(&mut envelope, EnvelopeStage::Decay),
+                (0.01 * sample_rate).round() as usize
+            );
+            assert_eq!(envelope.value(), 1.0);
+            assert_eq!(
+                samples_until(&mut envelope, EnvelopeStage::Sustain),
+                (0.1 * sample_rate).round() as usize
+            );
+            assert_eq!(envelope.value(), 0.5);
+
+            envelope.release();
+            assert_eq!(
+                samples_until(&mut envelope, EnvelopeStage::Idle),
+                (0.2 * sample_rate).round() as usize
+            );
+            assert_eq!(envelope.value(), 0.0);
+        }
+    }
+
+    #[test]
+    fn sample_rate_change_keeps_position() {
+        let mut envelope = Envelope::new(SAMPLE_RATE);
+        envelope.set_parameters(0.1, 0.1, 0.5, 0.1);
+        envelope.trigger();
+        for _ in 0..(0.05 * SAMPLE_RATE) as usize {
+            envelope.process();
+        }
+        let value = envelope.value();
+
+        // The attack is halfway done, so the other half takes 50 ms at the new sample rate
+        envelope.set_sample_rate(96000.0);
+        assert_eq!(samples_until(&mut envelope, EnvelopeStage::Decay), 4800);
+        assert!(value > 0.0 && value < 1.0);
+    }
+
+    #[test]
+    fn curves() {
+        // Halfway through the segment, exponential segments are below the linear segment and
+        // logarithmic segments are above it, both when rising and when falling
+        for (start, target) in [(0.0, 1.0), (1.0, 0.0)] {
+            let linear = segment_value(start, target, 0.5, 0.0);
+            assert!((linear - 0.5).abs() < 1e-6);
+            assert!(segment_value(start, target, 0.5, -1.0) < linear);
+            assert!(segment_value(start, target, 0.5, 1.0) > linear);
+
+            for curve in [-1.0, -0.5, 0.5, 1.0] {
+                assert!((segment_value(start, target, 0.0, curve) - start).abs() < 1e-6);
+                assert!((segment_value(start, target, 1.0, curve) - target).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn release_and_retrigger_from_current_level() {
+        let mut envelope = Envelope::new(SAMPLE_RATE);
+        envelope.trigger();
+        for _ in 0..100 {
+            envelope.process();
+        }
+
+        // Releasing during the attack falls from the attack's current level
+        let level = envelope.value();
+        assert!(level > 0.0 && level < 1.0);
+        envelope.release();
+        let released = envelope.process();
+        assert!(released < level && level - released < 0.01);
+
+        // And retriggering rises from the release's current level
+        let level = envelope.value();
+        envelope.trigger();
+        let retriggered = envelope.process();
+        assert!(retriggered > level && retriggered - level < 0.05);
+    }
+
+    #[test]
+    fn delay_hold_and_tempo_sync() {
+        for time_scale in [1.0, 0.5] {
+            let mut envelope = Envelope::new(SAMPLE_RATE);
+            envelope.set_parameters(0.01, 0.1, 0.5, 0.2);
+            envelope.set_delay_and_hold(0.05, 0.02);
+            envelope.set_time_scale(time_scale);
+            envelope.trigger();
+
+            let samples = |time: f32| (time * time_scale * SAMPLE_RATE).round() as usize;
+            assert_eq!(
+                samples_until(&mut envelope, EnvelopeStage::Attack),
+                samples(0.05)
+            );
+            assert_eq!(envelope.value(), 0.0);
+            assert_eq!(
+                samples_until(&mut envelope, EnvelopeStage::Hold),
+                samples(0.01)
+            );
+            assert_eq!(
+                samples_until(&mut envelope, EnvelopeStage::Decay),
+                samples(0.02)
+            );
+            assert_eq!(envelope.value(), 1.0);
+        }
+    }
+
+    #[test]
+    fn multi_stage_loop() {
+        let mut envelope = Envelope::new(SAMPLE_RATE);
+        envelope.set_mode(EnvelopeMode::MultiStage);
+        envelope.set_breakpoints(
+            &"0.01 1 loop-start, 0.01 0.5, 0.01 0.8 loop-end, 0.01 0.3 sustain, 0.01 0"
+                .parse()
+                .unwrap(),
+        );
+        envelope.trigger();
+
+        assert_eq!(
+            samples_until(&mut envelope, EnvelopeStage::Breakpoint(2)),
+            882
+        );
+        assert_eq!(envelope.value(), 0.5);
+        // Reaching the loop end continues with the segment after the loop start
+        for _ in 0..3 {
+            assert_eq!(
+                samples_until(&mut envelope, EnvelopeStage::Breakpoint(1)),
+                441
+            );
+            assert_eq!(envelope.value(), 0.8);
+            assert_eq!(
+                samples_until(&mut envelope, EnvelopeStage::Breakpoint(2)),
+                441
+            );
+            assert_eq!(envelope.value(), 0.5);
+        }
+
+        // Releasing skips to the segment after the sustain point
+        envelope.release();
+        assert_eq!(envelope.stage, EnvelopeStage::Breakpoint(4));
+        assert_eq!(samples_until(&mut envelope, EnvelopeStage::Idle), 441);
+        assert_eq!(envelope.value(), 0.0);
+    }
+
+    #[test]
+    fn multi_stage_sustain() {
+        let mut envelope = Envelope::new(SAMPLE_RATE);
+        envelope.set_parameters(0.01, 0.1, 0.5, 0.1);
+        envelope.set_mode(EnvelopeMode::MultiStage);
+        envelope.set_breakpoints(&"0.01 1, 0.01 0.5 sustain, 0.01 0.2".parse().unwrap());
+        envelope.trigger();
+
+        assert_eq!(
+            samples_until(&mut envelope, EnvelopeStage::BreakpointSustain),
+            882
+        );
+        for _ in 0..1000 {
+            assert_eq!(envelope.process(), 0.5);
+        }
+        assert!(envelope.is_held());
+
+        // The last point is not at zero, so the envelope fades out from there
+        envelope.release();
+        assert!(!envelope.is_held());
+        assert_eq!(samples_until(&mut envelope, EnvelopeStage::Release), 441);
+        assert_eq!(envelope.value(), 0.2);
+        assert_eq!(samples_until(&mut envelope, EnvelopeStage::Idle), 4410);
+    }
+
+    #[test]
+    fn parse_breakpoints() {
+        let shape: Breakpoints = "0.5 1 0.5, 1 0.6 loop-start sustain, 2 0.8 loop-end, 0.5 0"
+            .parse()
+            .unwrap();
+        assert_eq!(shape.points().len(), 4);
+        assert_eq!(
+            shape.points()[1],
+            Breakpoint {
+                time: 1.0,
+                level: 0.6,
+                curve: 0.0
+            }
+        );
+        assert_eq!(shape.sustain, Some(1));
+        assert_eq!(shape.loop_region, Some((1, 2)));
+
+        for shape in [shape, Breakpoints::default()] {
+            assert_eq!(shape.to_string().parse(), Ok(shape));
+            let json = serde_json::to_string(&shape).unwrap();
+            assert_eq!(serde_json::from_str::<Breakpoints>(&json).unwrap(), shape);
+        }
+
+        let too_many = vec!["0.1 0.5"; MAX_BREAKPOINTS + 1].join(", ");
+        for invalid in [
+            "",
+            "1",
+            "1 x",
+            "1 1 loop-end, 1 0 loop-start",
+            too_many.as_str(),
+        ] {
+            assert!(
+                invalid.parse::<Breakpoints>().is_err(),
+                "'{invalid}' was parsed"
+            );
         }
-        self.output
     }
 }
@@ -1,7 +1,16 @@
+use realfft::num_complex::Complex32;
+use std::f32::consts::PI;
+
 pub struct ResonantFilter {
     cutoff: f32,
     resonance: f32,
     filter_type: bool, // True for Moog, false for Roland
+    sample_rate: f32,
+    /// The one-pole stages' feedback and gain coefficients, and the resonance feedback amount.
+    /// These are only recomputed when the cutoff, the resonance, or the sample rate changes.
+    k: f32,
+    p: f32,
+    r: f32,
     y1: f32,
     y2: f32,
     y3: f32,
@@ -13,11 +22,15 @@ pub struct ResonantFilter {
 }
 
 impl ResonantFilter {
-    pub fn new() -> Self {
-        Self {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut filter = Self {
             cutoff: 1000.0,
             resonance: 0.0,
             filter_type: true, // Default to Moog
+            sample_rate,
+            k: 0.0,
+            p: 0.0,
+            r: 0.0,
             y1: 0.0,
             y2: 0.0,
             y3: 0.0,
@@ -26,27 +39,74 @@ impl ResonantFilter {
             oldy1: 0.0,
             oldy2: 0.0,
             oldy3: 0.0,
-        }
+        };
+        filter.update_coefficients();
+
+        filter
     }
 
     pub fn set_params(&mut self, cutoff: f32, resonance: f32) {
-        self.cutoff = cutoff.clamp(20.0, 20000.0);
-        self.resonance = resonance.clamp(0.0, 1.0);
+        let cutoff = cutoff.clamp(20.0, 20000.0);
+        let resonance = resonance.clamp(0.0, 1.0);
+        if cutoff != self.cutoff || resonance != self.resonance {
+            self.cutoff = cutoff;
+            self.resonance = resonance;
+            self.update_coefficients();
+        }
+    }
+
+    /// Change the sample rate without resetting the filter's state.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
     }
 
     pub fn set_filter_type(&mut self, filter_type: bool) {
-        self.filter_type = filter_type;
+        if filter_type != self.filter_type {
+            self.filter_type = filter_type;
+            // The Roland filter doesn't use the fourth stage, so that stage's state is stale
+            self.y4 = 0.0;
+            self.oldy3 = 0.0;
+            self.update_coefficients();
+        }
     }
 
-    pub fn process(&mut self, input: f32, sample_rate: f32) -> f32 {
-        if self.filter_type {
-            // Moog-Charakteristik
-            let f = 2.0 * self.cutoff / sample_rate;
-            let k = 3.6 * f - 1.6 * f * f - 1.0;
-            let p = (k + 1.0) * 0.5;
-            let scale = (1.0 - p) * 1.386249;
-            let r = self.resonance * scale;
+    fn update_coefficients(&mut self) {
+        // The cutoff can't go above the Nyquist frequency at low sample rates
+        let f = (2.0 * self.cutoff / self.sample_rate).min(1.0);
+        let k = 3.6 * f - 1.6 * f * f - 1.0;
+        let p = (k + 1.0) * 0.5;
+        // With three stages every stage only needs to shift the phase by 60 degrees instead of 45
+        // degrees before the feedback turns positive, where each stage attenuates the signal more,
+        // so the Roland filter needs twice as much feedback to self-oscillate
+        let scale = (1.0 - p) * 1.386249 * if self.filter_type { 1.0 } else { 2.0 };
+
+        self.k = k;
+        self.p = p;
+        self.r = self.resonance * scale;
+    }
+
+    /// The filter's gain at `frequency` for its current settings, computed from its transfer
+    /// function. This ignores the output's clipping and does not touch the filter's state, so the
+    /// editor can use its own instance to draw the filter's response.
+    pub fn magnitude_response(&self, frequency: f32) -> f32 {
+        let (k, p, r) = (self.k, self.p, self.r);
+
+        // Every stage is `p * (1 + z^-1) / (1 + k * z^-1)`, and the last stage's previous output is
+        // subtracted from the input
+        let z_inv = Complex32::from_polar(1.0, -2.0 * PI * frequency / self.sample_rate);
+        let stage = (z_inv + 1.0) * p / (z_inv * k + 1.0);
+        let stages = stage.powu(if self.filter_type { 4 } else { 3 });
+        let response = stages / (z_inv * stages * r + 1.0);
+
+        response.norm()
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let (k, p, r) = (self.k, self.p, self.r);
 
+        if self.filter_type {
+            // Moog-Charakteristik: four stages, 24 dB per octave
             let x = input - r * self.y4;
 
             self.y1 = x * p + self.oldx * p - k * self.y1;
@@ -63,28 +123,184 @@ impl ResonantFilter {
 
             self.y4
         } else {
-            // Roland-Charakteristik
-            let f = 2.0 * self.cutoff / sample_rate;
-            let k = 3.6 * f - 1.6 * f * f - 1.0;
-            let p = (k + 1.0) * 0.5;
-            let scale = (1.0 - p) * 1.386249;
-            let r = self.resonance * scale;
-
-            let x = input - r * self.y4;
+            // Roland-Charakteristik: three stages like the TB-303's, 18 dB per octave, with the
+            // feedback taken from the third stage
+            let x = input - r * self.y3;
 
             self.y1 = x * p + self.oldx * p - k * self.y1;
             self.y2 = self.y1 * p + self.oldy1 * p - k * self.y2;
             self.y3 = self.y2 * p + self.oldy2 * p - k * self.y3;
-            self.y4 = self.y3 * p + self.oldy3 * p - k * self.y4;
 
-            self.y4 = self.y4.clamp(-1.0, 1.0);
+            self.y3 = self.y3.clamp(-1.0, 1.0);
 
             self.oldx = x;
             self.oldy1 = self.y1;
             self.oldy2 = self.y2;
-            self.oldy3 = self.y3;
 
-            self.y4
+            self.y3
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    /// Measure the filter's gain in decibels for a sine wave at `frequency`. The first half second
+    /// is discarded to let the filter settle.
+    fn measure_gain_db(filter: &mut ResonantFilter, frequency: f32) -> f32 {
+        let mut input_energy = 0.0;
+        let mut output_energy = 0.0;
+        for n in 0..SAMPLE_RATE as usize {
+            let input = 0.5 * (2.0 * PI * frequency * n as f32 / SAMPLE_RATE).sin();
+            let output = filter.process(input);
+            if n >= SAMPLE_RATE as usize / 2 {
+                input_energy += input * input;
+                output_energy += output * output;
+            }
+        }
+
+        10.0 * (output_energy / input_energy).log10()
+    }
+
+    #[test]
+    fn magnitude_response() {
+        for filter_type in [true, false] {
+            for cutoff in [200.0, 1000.0, 5000.0] {
+                let mut filter = ResonantFilter::new(SAMPLE_RATE);
+                filter.set_filter_type(filter_type);
+                filter.set_params(cutoff, 0.0);
+
+                let passband = measure_gain_db(&mut filter, 20.0);
+                let at_cutoff = measure_gain_db(&mut filter, cutoff);
+                let stopband = measure_gain_db(&mut filter, cutoff * 4.0);
+
+                assert!(passband.abs() < 0.5, "{cutoff} Hz passband: {passband} dB");
+                assert!(
+                    (-12.0..-3.0).contains(&at_cutoff),
+                    "{cutoff} Hz at cutoff: {at_cutoff} dB"
+                );
+                assert!(stopband < -30.0, "{cutoff} Hz stopband: {stopband} dB");
+            }
+        }
+    }
+
+    #[test]
+    fn computed_response_matches_measured_response() {
+        for filter_type in [true, false] {
+            for resonance in [0.0, 0.5] {
+                let mut filter = ResonantFilter::new(SAMPLE_RATE);
+                filter.set_filter_type(filter_type);
+                filter.set_params(1000.0, resonance);
+
+                for frequency in [100.0, 700.0, 1000.0, 2000.0] {
+                    let computed = 20.0 * filter.magnitude_response(frequency).log10();
+                    let measured = measure_gain_db(&mut filter, frequency);
+                    assert!(
+                        (computed - measured).abs() < 0.5,
+                        "{frequency} Hz: computed {computed} dB, measured {measured} dB"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cutoff_independent_of_sample_rate() {
+        for cutoff in [200.0, 1000.0] {
+            let mut filter = ResonantFilter::new(SAMPLE_RATE);
+            filter.set_params(cutoff, 0.0);
+            let reference = 20.0 * filter.magnitude_response(cutoff).log10();
+
+            for sample_rate in [48000.0, 96000.0, 192000.0] {
+                filter.set_sample_rate(sample_rate);
+                let gain = 20.0 * filter.magnitude_response(cutoff).log10();
+                assert!(
+                    (gain - reference).abs() < 1.0,
+                    "{cutoff} Hz at {sample_rate} Hz: {gain} dB, expected {reference} dB"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn resonance_boosts_cutoff() {
+        for filter_type in [true, false] {
+            let mut filter = ResonantFilter::new(SAMPLE_RATE);
+            filter.set_filter_type(filter_type);
+            filter.set_params(1000.0, 0.0);
+            let without_resonance = measure_gain_db(&mut filter, 1000.0);
+
+            let mut filter = ResonantFilter::new(SAMPLE_RATE);
+            filter.set_filter_type(filter_type);
+            filter.set_params(1000.0, 1.0);
+            let with_resonance = measure_gain_db(&mut filter, 1000.0);
+
+            assert!(with_resonance > without_resonance);
+        }
+    }
+
+    #[test]
+    fn filter_types_differ() {
+        for resonance in [0.0, 0.5] {
+            let mut moog = ResonantFilter::new(SAMPLE_RATE);
+            moog.set_params(1000.0, resonance);
+            let mut roland = ResonantFilter::new(SAMPLE_RATE);
+            roland.set_filter_type(false);
+            roland.set_params(1000.0, resonance);
+
+            // The Roland filter has one stage less, so it falls off less steeply
+            for frequency in [2000.0, 4000.0] {
+                let moog_gain = 20.0 * moog.magnitude_response(frequency).log10();
+                let roland_gain = 20.0 * roland.magnitude_response(frequency).log10();
+                assert!(
+                    roland_gain - moog_gain > 3.0,
+                    "{frequency} Hz: Moog {moog_gain} dB, Roland {roland_gain} dB"
+                );
+
+                let moog_gain = measure_gain_db(&mut moog, frequency);
+                let roland_gain = measure_gain_db(&mut roland, frequency);
+                assert!(
+                    roland_gain - moog_gain > 3.0,
+                    "{frequency} Hz: measured Moog {moog_gain} dB, Roland {roland_gain} dB"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stable_at_max_resonance() {
+        for (filter_type, cutoff) in [true, false]
+            .into_iter()
+            .flat_map(|filter_type| [20.0, 1000.0, 20000.0].map(|cutoff| (filter_type, cutoff)))
+        {
+            let mut filter = ResonantFilter::new(SAMPLE_RATE);
+            filter.set_filter_type(filter_type);
+            filter.set_params(cutoff, 1.0);
+
+            // Drive the filter with white noise, then let it ring out
+            let mut seed: u32 = 1;
+            for _ in 0..SAMPLE_RATE as usize {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                let input = (seed >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+                let output = filter.process(input);
+                assert!(output.is_finite() && output.abs() <= 1.0);
+            }
+
+            let mut tail = 0.0f32;
+            for n in 0..SAMPLE_RATE as usize {
+                let output = filter.process(0.0);
+                assert!(output.is_finite());
+                if n >= SAMPLE_RATE as usize / 2 {
+                    tail = tail.max(output.abs());
+                }
+            }
+            assert!(
+                tail < 1e-6,
+                "{cutoff} Hz filter (Moog: {filter_type}) did not settle: {tail}"
+            );
         }
     }
 }
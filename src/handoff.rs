@@ -0,0 +1,80 @@
+use crossbeam::queue::ArrayQueue;
+
+/// The number of values that can be waiting for the audio thread at once. Values sent while the
+/// queue is full are dropped.
+const QUEUE_CAPACITY: usize = 16;
+
+/// Passes values that are built on a background thread, like a newly loaded wavetable, to the audio
+/// thread without locking. The audio thread sends the values it replaces back through a second
+/// queue, so they get deallocated on the next background task instead of on the audio thread.
+pub struct Handoff<T> {
+    incoming: ArrayQueue<T>,
+    retired: ArrayQueue<T>,
+}
+
+impl<T> Default for Handoff<T> {
+    fn default() -> Self {
+        Self {
+            incoming: ArrayQueue::new(QUEUE_CAPACITY),
+            retired: ArrayQueue::new(QUEUE_CAPACITY),
+        }
+    }
+}
+
+impl<T> Handoff<T> {
+    /// Send a value to the audio thread. This is called from a background thread, and it also
+    /// deallocates the values the audio thread has replaced since the last call. Returns `false`
+    /// if the queue is full, in which case the value is dropped.
+    pub fn send(&self, value: T) -> bool {
+        while self.retired.pop().is_some() {}
+
+        self.incoming.push(value).is_ok()
+    }
+
+    /// The next value sent to the audio thread. This is called from the audio thread.
+    pub fn receive(&self) -> Option<T> {
+        self.incoming.pop()
+    }
+
+    /// Pass a value the audio thread replaced back to the background thread. This is called from
+    /// the audio thread. The retired values are cleared before every new value is sent, so this
+    /// queue cannot fill up and the value is never dropped here.
+    pub fn retire(&self, value: T) {
+        let _ = self.retired.push(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn retired_values_are_dropped_on_send() {
+        let handoff = Handoff::default();
+        assert!(handoff.send(Arc::new(1)));
+
+        // The audio thread replaces its value and hands the old one back
+        let mut current = Arc::new(0);
+        let replaced = Arc::downgrade(&current);
+        while let Some(value) = handoff.receive() {
+            handoff.retire(std::mem::replace(&mut current, value));
+        }
+        assert_eq!(*current, 1);
+
+        // The replaced value is only dropped once the next value is sent
+        assert!(replaced.upgrade().is_some());
+        assert!(handoff.send(Arc::new(2)));
+        assert!(replaced.upgrade().is_none());
+    }
+
+    #[test]
+    fn full_queue_drops_values() {
+        let handoff = Handoff::default();
+        for value in 0..QUEUE_CAPACITY {
+            assert!(handoff.send(value));
+        }
+        assert!(!handoff.send(QUEUE_CAPACITY));
+        assert_eq!(handoff.receive(), Some(0));
+    }
+}
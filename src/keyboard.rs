@@ -0,0 +1,101 @@
+use crossbeam::queue::ArrayQueue;
+use nih_plug::prelude::NoteEvent;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The number of notes that can be played from the editor between two process calls. Notes played
+/// while the queue is full are dropped.
+const QUEUE_CAPACITY: usize = 256;
+
+/// Connects the editor's on-screen keyboard to the audio thread. Notes played in the editor are
+/// sent through a lock-free queue that the audio thread merges with the host's events, and the
+/// audio thread reports the notes that are held by the voices back to the editor.
+pub struct VirtualKeyboard {
+    events: ArrayQueue<NoteEvent<()>>,
+    /// A bit for every MIDI note, split into the lower and the upper 64 notes.
+    held_notes: [AtomicU64; 2],
+}
+
+impl Default for VirtualKeyboard {
+    fn default() -> Self {
+        Self {
+            events: ArrayQueue::new(QUEUE_CAPACITY),
+            held_notes: Default::default(),
+        }
+    }
+}
+
+impl VirtualKeyboard {
+    /// Send a note event to the audio thread. This is called from the GUI thread.
+    pub fn send(&self, event: NoteEvent<()>) {
+        // Dropping the event is the only sensible option if the audio thread isn't processing
+        let _ = self.events.push(event);
+    }
+
+    /// The next note event from the editor. This is called from the audio thread.
+    pub fn next_event(&self) -> Option<NoteEvent<()>> {
+        self.events.pop()
+    }
+
+    /// Replace the held notes. This is called from the audio thread.
+    pub fn set_held_notes(&self, notes: impl IntoIterator<Item = u8>) {
+        let mut held_notes = 0u128;
+        for note in notes {
+            held_notes |= 1 << (note & 127);
+        }
+
+        self.held_notes[0].store(held_notes as u64, Ordering::Relaxed);
+        self.held_notes[1].store((held_notes >> 64) as u64, Ordering::Relaxed);
+    }
+
+    /// A bit mask containing the notes that are held by the voices.
+    pub fn held_notes(&self) -> u128 {
+        let low = self.held_notes[0].load(Ordering::Relaxed) as u128;
+        let high = self.held_notes[1].load(Ordering::Relaxed) as u128;
+
+        low | (high << 64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_notes_round_trip() {
+        let keyboard = VirtualKeyboard::default();
+        keyboard.set_held_notes([0, 60, 64, 127]);
+
+        let held_notes = keyboard.held_notes();
+        assert_eq!(held_notes.count_ones(), 4);
+        for note in [0, 60, 64, 127] {
+            assert!(held_notes & (1 << note) != 0);
+        }
+
+        keyboard.set_held_notes([]);
+        assert_eq!(keyboard.held_notes(), 0);
+    }
+
+    #[test]
+    fn events_arrive_in_order() {
+        let keyboard = VirtualKeyboard::default();
+        let note = |idx: usize| (idx % 128) as u8;
+        for idx in 0..QUEUE_CAPACITY + 10 {
+            keyboard.send(NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: note(idx),
+                velocity: 0.0,
+            });
+        }
+
+        // The events that didn't fit are dropped
+        let notes: Vec<u8> = std::iter::from_fn(|| keyboard.next_event())
+            .map(|event| match event {
+                NoteEvent::NoteOff { note, .. } => note,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(notes, (0..QUEUE_CAPACITY).map(note).collect::<Vec<_>>());
+    }
+}
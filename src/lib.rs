@@ -1,134 +1,843 @@
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
 use nih_plug::prelude::*;
-use std::sync::Arc;
+use std::f32::consts::TAU;
 use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-mod params;
-mod util;
-mod filter;
+mod algorithm;
+mod analyzer;
+mod arpeggiator;
+mod block;
 mod bridge;
+mod editor;
+mod envelope;
+mod filter;
+mod handoff;
+mod keyboard;
+mod meter;
+mod midi_learn;
+mod modulation;
+mod params;
+mod presets;
+mod smoothing;
 mod synth;
+mod util;
+mod velocity;
 
-use params::AmSynthParams;
+use algorithm::{Routing, MAX_OPERATORS};
+use analyzer::{AnalyzerInput, AnalyzerOutput};
+use arpeggiator::{Arpeggiator, TransportPosition};
+use block::{SineLanes, BLOCK_SIZE, LANES};
 use bridge::am::AmBridge;
-use synth::sine::SineOscillator;
+use bridge::feedback::FeedbackBridge;
+use envelope::{Envelope, EnvelopeCurves, EnvelopeMode, SYNC_TEMPO};
 use filter::ResonantFilter;
+use handoff::Handoff;
+use keyboard::VirtualKeyboard;
+use meter::{MeterWeights, OutputMeter};
+use midi_learn::MidiLearn;
+use modulation::{Destination, ModMatrix, ModOffsets, Modulator, OperatorDestination};
+use params::{
+    AmSynthParams, EnvelopeParams, EnvelopeTarget, OperatorParams, OscillatorEngine,
+    PressureTarget, SampleLoopMode,
+};
+use smoothing::Smoothers;
+use synth::sample::{LoopRegion, Sample, SamplePlayer};
+use synth::sine::SineOscillator;
+use synth::wavetable::{BuiltinWavetable, Wavetable, WavetableOscillator, WavetableSource};
+use velocity::VelocityTable;
 
-struct AmSynth {
+pub struct AmSynth {
     params: Arc<AmSynthParams>,
     sample_rate: f32,
     voices: Vec<Voice>,
+    /// The mono output for the current block before it's copied to every channel. This is sized
+    /// for the host's maximum block size in [`AmSynth::prepare()`].
+    output: Vec<f32>,
+    /// Renders the sine operators of several voices at once.
+    sine_lanes: Box<SineLanes>,
+    /// The tables used by every operator's wavetable engine. New tables are loaded on the
+    /// background thread and sent through `engine_updates`.
+    wavetables: [Arc<Wavetable>; MAX_OPERATORS],
+    /// Passes the tables and samples loaded on the background thread to the audio thread, and the
+    /// replaced ones back so they never get deallocated on the audio thread.
+    engine_updates: Arc<Handoff<EngineUpdate>>,
+    /// The sample used by the sample playback engine. This is replaced the same way as the
+    /// wavetables.
+    sample: Arc<Sample>,
+    /// The output levels shown in the editor.
+    meter: Arc<OutputMeter>,
+    meter_weights: MeterWeights,
+    /// Publishes the output for the editor's oscilloscope and spectrum analyzer.
+    analyzer_input: AnalyzerInput,
+    /// The GUI thread's side of `analyzer_input`. This is replaced when the sample rate changes.
+    analyzer_output: Arc<Mutex<AnalyzerOutput>>,
+    /// Notes played on the editor's keyboard.
+    keyboard: Arc<VirtualKeyboard>,
+    /// Binds MIDI CCs to parameters.
+    midi_learn: Arc<MidiLearn>,
+    /// The smoothers for every parameter, which follow the smoothing time parameter.
+    smoothers: Smoothers,
+    /// The LFOs and the resolved mod matrix routes.
+    modulator: Modulator,
+    /// The mod matrix's routes and the modulation ranges shown in the editor.
+    mod_matrix: Arc<ModMatrix>,
+    /// The expressions last received on every MIDI channel in MPE mode. New notes start out with
+    /// their channel's pitch bend and slide, since controllers send those before the note on.
+    channel_expressions: [Expression; 16],
+    /// The pitch bend from the MPE zone's master channel in semitones, which bends every note.
+    zone_bend: f32,
+    /// The user velocity curve. This is copied from the parameters when a note starts, and the
+    /// previous copy is used while the GUI thread is changing it.
+    velocity_table: VelocityTable,
+    /// Plays the held notes as a pattern when it's enabled. Played notes go through the
+    /// arpeggiator before they reach the voices.
+    arpeggiator: Arpeggiator,
+}
+
+/// A table or a sample loaded on the background thread for the non-sine engines.
+enum EngineUpdate {
+    /// A table for an operator's wavetable engine.
+    Wavetable(usize, Arc<Wavetable>),
+    Sample(Arc<Sample>),
+}
+
+/// The data the non-sine engines read from while rendering.
+struct EngineData<'a> {
+    tables: [&'a Wavetable; MAX_OPERATORS],
+    sample: &'a Sample,
+    loop_region: Option<LoopRegion>,
+}
+
+/// Work that's done on a background thread because it allocates or touches the file system.
+enum Task {
+    /// Build the wavetable for an operator's source and swap it in. The source is stored in the
+    /// parameters so it gets saved with the plugin's state.
+    LoadWavetable(usize, WavetableSource),
+    /// The same as `LoadWavetable`, but the file's contents are embedded in the plugin's state.
+    EmbedWavetable(usize, PathBuf),
+    /// Load a WAV or FLAC file for the sample playback engine. The path is stored in the
+    /// parameters.
+    LoadSample(PathBuf),
+    /// Apply the CC messages queued by [`MidiLearn::process_cc()`] to their parameters. This runs
+    /// on the GUI thread.
+    ApplyMidiCcs,
+}
+
+/// How long voices take to fade out when they're choked or when the plugin is reset, in seconds.
+/// Cutting them off immediately would click.
+const FADE_OUT_TIME: f32 = 0.005;
+
+/// The pitch bend range of the MPE zone's master channel in semitones. This is the MPE
+/// specification's default.
+const MPE_MASTER_BEND_RANGE: f32 = 2.0;
+/// The CC that MPE controllers send a note's slide on, the finger's position along the key.
+const MPE_SLIDE_CC: u8 = 74;
+
+/// A note's own pitch bend, pressure, and slide, from an MPE member channel or from CLAP note
+/// expressions.
+#[derive(Debug, Clone, Copy)]
+struct Expression {
+    /// The pitch bend in semitones.
+    bend: f32,
+    /// The pressure between 0 and 1, or `None` for notes that don't send any pressure. Those are
+    /// not affected by the pressure target.
+    pressure: Option<f32>,
+    /// The slide between 0 and 1. The global filter's cutoff is not changed at 0.5.
+    slide: f32,
+}
+
+impl Default for Expression {
+    fn default() -> Self {
+        Self {
+            bend: 0.0,
+            pressure: None,
+            slide: 0.5,
+        }
+    }
 }
 
 struct Voice {
-    carrier: SineOscillator,
-    modulator: SineOscillator,
+    operators: [Operator; MAX_OPERATORS],
     bridge: AmBridge,
-    carrier_filter: ResonantFilter,
-    modulator_filter: ResonantFilter,
     global_filter: ResonantFilter,
     active: bool,
+    /// The note's identifiers. Note events with a voice ID only apply to the voice that was started
+    /// with that ID, other events apply to every voice playing the same note on the same channel.
+    voice_id: Option<i32>,
+    channel: u8,
     note: u8,
     velocity: f32,
+    /// The gain applied to the voice's output, and how much it decreases every sample while the
+    /// voice is fading out. The voice is deactivated once the gain reaches zero.
+    fade: f32,
+    fade_step: f32,
+    expression: Expression,
+    /// The MPE zone's pitch bend in semitones, on top of the note's own pitch bend.
+    zone_bend: f32,
+    /// The gain from the note's pressure at the end of the previous block. The gain is ramped
+    /// towards the new gain over every block so pressure changes don't cause zipper noise.
+    amplitude: f32,
     envelope: Envelope, // Added Envelope structure here
+    /// An envelope that's only heard through the mod matrix.
+    mod_envelope: Envelope,
+    /// The mod matrix's offsets for the last rendered sample.
+    mod_offsets: ModOffsets,
+    /// The global envelope times the gain from the note's pressure, the modulated mod depth, and
+    /// the modulated global filter cutoff for every sample in the current block.
+    envelope_values: [f32; BLOCK_SIZE],
+    mod_depth: [f32; BLOCK_SIZE],
+    filter_cutoff: [f32; BLOCK_SIZE],
+}
+
+/// A single operator in a voice. The operator's engine generates a signal that's modulated by the
+/// operator's modulators, and the result is either heard or used to modulate other operators.
+struct Operator {
+    sine: SineOscillator,
+    wavetable: WavetableOscillator,
+    player: SamplePlayer,
+    feedback: FeedbackBridge,
+    envelope: Envelope,
+    /// The carrier filter if the operator is heard, or the modulator filter if it only modulates
+    /// other operators.
+    filter: ResonantFilter,
+    /// The operator's frequency, feedback amount, and wavetable position for every sample in the
+    /// current block, with the voice's modulation applied.
+    freq: [f32; BLOCK_SIZE],
+    feedback_amount: [f32; BLOCK_SIZE],
+    wavetable_position: [f32; BLOCK_SIZE],
+    /// The gain applied to the operator's signal, starting out as its level times its envelope. The
+    /// amplitude modulation is multiplied in once the operator's modulators have been rendered.
+    gain: [f32; BLOCK_SIZE],
+    /// The operator's output for the current block, read by the operators it modulates.
+    output: [f32; BLOCK_SIZE],
+}
+
+/// The smoothed parameter values for the current block. The smoothers are shared by every voice, so
+/// they're read once per block and then used for every voice. Parameters that can be modulated are
+/// read for every sample, while the envelopes and the tuning are updated once per block.
+struct BlockValues {
+    len: usize,
+    tuning: f32,
+    mod_depth: [f32; BLOCK_SIZE],
+    global_filter_cutoff: [f32; BLOCK_SIZE],
+    global_filter_resonance: f32,
+    carrier_filter: FilterValues,
+    modulator_filter: FilterValues,
+    global_envelope: EnvelopeValues,
+    mod_envelope: EnvelopeValues,
+    operators: [OperatorValues; MAX_OPERATORS],
+    pressure_target: PressureTarget,
+    pressure_amount: f32,
+    slide_amount: f32,
+    velocity_sensitivity: f32,
+    velocity_mod_depth: f32,
+    velocity_modulator_envelope: f32,
+    velocity_cutoff: f32,
+}
+
+/// The carrier or modulator filter's parameter values for the current block.
+struct FilterValues {
+    enabled: bool,
+    filter_type: bool,
+    cutoff: [f32; BLOCK_SIZE],
+    resonance: f32,
 }
 
-struct Envelope {
+/// An envelope's smoothed parameter values for the current block.
+struct EnvelopeValues {
+    mode: EnvelopeMode,
+    delay: f32,
     attack: f32,
+    hold: f32,
     decay: f32,
     sustain: f32,
     release: f32,
-    stage: EnvelopeStage,
-    value: f32,
-    sample_rate: f32,
+    curves: EnvelopeCurves,
 }
 
-enum EnvelopeStage {
-    Idle,
-    Attack,
-    Decay,
-    Sustain,
-    Release,
+/// An operator's smoothed parameter values for the current block.
+struct OperatorValues {
+    engine: OscillatorEngine,
+    keyboard: bool,
+    ratio: [f32; BLOCK_SIZE],
+    freq: [f32; BLOCK_SIZE],
+    level: [f32; BLOCK_SIZE],
+    feedback: [f32; BLOCK_SIZE],
+    wavetable_position: [f32; BLOCK_SIZE],
+    envelope: EnvelopeValues,
 }
 
-impl Envelope {
+impl Voice {
     fn new(sample_rate: f32) -> Self {
         Self {
-            attack: 0.01,
-            decay: 0.1,
-            sustain: 0.5,
-            release: 0.1,
-            stage: EnvelopeStage::Idle,
-            value: 0.0,
-            sample_rate,
+            operators: std::array::from_fn(|_| Operator::new(sample_rate)),
+            bridge: AmBridge::new(),
+            global_filter: ResonantFilter::new(sample_rate),
+            active: false,
+            voice_id: None,
+            channel: 0,
+            note: 0,
+            velocity: 0.0,
+            fade: 1.0,
+            fade_step: 0.0,
+            expression: Expression::default(),
+            zone_bend: 0.0,
+            amplitude: 1.0,
+            envelope: Envelope::new(sample_rate), // Initialize Envelope
+            mod_envelope: Envelope::new(sample_rate),
+            mod_offsets: ModOffsets::default(),
+            envelope_values: [0.0; BLOCK_SIZE],
+            mod_depth: [0.0; BLOCK_SIZE],
+            filter_cutoff: [0.0; BLOCK_SIZE],
         }
     }
 
-    fn trigger(&mut self) {
-        self.stage = EnvelopeStage::Attack;
-        self.value = 0.0;
+    /// Change the sample rate of every oscillator, filter, and envelope in the voice. Notes that
+    /// are playing continue at the same pitch and from the same point in their envelopes.
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        for operator in &mut self.operators {
+            operator.set_sample_rate(sample_rate);
+        }
+        self.global_filter.set_sample_rate(sample_rate);
+        self.envelope.set_sample_rate(sample_rate);
+        self.mod_envelope.set_sample_rate(sample_rate);
+    }
+
+    /// Start a new note on this voice.
+    fn trigger(&mut self, voice_id: Option<i32>, channel: u8, note: u8, velocity: f32) {
+        self.active = true;
+        self.voice_id = voice_id;
+        self.channel = channel;
+        self.note = note;
+        self.velocity = velocity;
+        self.fade = 1.0;
+        self.fade_step = 0.0;
+        self.expression = Expression::default();
+        self.zone_bend = 0.0;
+        self.amplitude = 1.0;
+        self.envelope.trigger();
+        self.mod_envelope.trigger();
+        for operator in &mut self.operators {
+            operator.trigger();
+        }
     }
 
+    /// Start the release of every envelope in the voice.
     fn release(&mut self) {
-        self.stage = EnvelopeStage::Release;
+        self.envelope.release();
+        self.mod_envelope.release();
+        for operator in &mut self.operators {
+            operator.envelope.release();
+        }
     }
 
-    fn process(&mut self) -> f32 {
-        match self.stage {
-            EnvelopeStage::Idle => {}
-            EnvelopeStage::Attack => {
-                self.value += 1.0 / (self.attack * self.sample_rate);
-                if self.value >= 1.0 {
-                    self.value = 1.0;
-                    self.stage = EnvelopeStage::Decay;
-                }
+    /// Fade the voice out over [`FADE_OUT_TIME`] and then deactivate it, regardless of where its
+    /// envelopes are.
+    fn fade_out(&mut self, sample_rate: f32) {
+        if self.fade_step == 0.0 {
+            self.fade_step = 1.0 / (FADE_OUT_TIME * sample_rate);
+        }
+    }
+
+    /// Whether a note event is meant for this voice. Events with a voice ID only match the voice
+    /// that was started with that ID.
+    fn matches(&self, voice_id: Option<i32>, channel: u8, note: u8) -> bool {
+        self.active
+            && match voice_id {
+                Some(voice_id) => self.voice_id == Some(voice_id),
+                None => self.channel == channel && self.note == note,
             }
-            EnvelopeStage::Decay => {
-                self.value -= (1.0 - self.sustain) / (self.decay * self.sample_rate);
-                if self.value <= self.sustain {
-                    self.value = self.sustain;
-                    self.stage = EnvelopeStage::Sustain;
-                }
+    }
+
+    /// Whether the note is still held, i.e. it has not been released, choked, or faded out.
+    fn is_held(&self) -> bool {
+        self.active && self.envelope.is_held() && self.fade_step == 0.0
+    }
+
+    /// Whether the voice has become silent, either because the global envelope's release has
+    /// finished or because it has been faded out.
+    fn is_finished(&self) -> bool {
+        self.envelope.is_idle() || self.fade <= 0.0
+    }
+
+    /// Process the voice's envelopes and compute its modulated parameter values for the current
+    /// block.
+    fn prepare_block(
+        &mut self,
+        params: &AmSynthParams,
+        values: &BlockValues,
+        modulator: &Modulator,
+        routing: &Routing,
+        num_operators: usize,
+    ) {
+        let bend = self.expression.bend + self.zone_bend;
+        let note_freq =
+            util::midi_note_to_freq(self.note, values.tuning) * 2.0f32.powf(bend / 12.0);
+        let operator_params = params.operators();
+
+        // The note's pressure, slide, and velocity are added to the mod matrix's offsets
+        let (pressure_offset, amplitude) = match (self.expression.pressure, values.pressure_target)
+        {
+            (Some(pressure), PressureTarget::ModDepth) => (pressure * values.pressure_amount, 1.0),
+            (Some(pressure), PressureTarget::Amplitude) => {
+                (0.0, 1.0 - values.pressure_amount * (1.0 - pressure))
+            }
+            (None, _) => (0.0, 1.0),
+        };
+        let slide_offset = (self.expression.slide - 0.5) * 2.0 * values.slide_amount;
+        let mod_depth_offset = pressure_offset + self.velocity * values.velocity_mod_depth;
+        let cutoff_offset = slide_offset + self.velocity * values.velocity_cutoff;
+        let amplitude_step = (amplitude - self.amplitude) / values.len as f32;
+
+        // Softer notes scale down the envelopes of the operators that are only heard through the
+        // operators they modulate
+        let modulator_envelope = 1.0 - values.velocity_modulator_envelope * (1.0 - self.velocity);
+        let envelope_scale: [f32; MAX_OPERATORS] = std::array::from_fn(|operator| {
+            if routing.is_output(operator) {
+                1.0
+            } else {
+                modulator_envelope
+            }
+        });
+
+        values.mod_envelope.apply(&mut self.mod_envelope);
+        values.global_envelope.apply(&mut self.envelope);
+        for (operator, values) in self
+            .operators
+            .iter_mut()
+            .zip(&values.operators)
+            .take(num_operators)
+        {
+            values.envelope.apply(&mut operator.envelope);
+        }
+
+        for sample in 0..values.len {
+            let mod_envelope = self.mod_envelope.process();
+            self.mod_offsets = if modulator.is_active() {
+                modulator.offsets(sample, self.envelope.value(), mod_envelope, self.velocity)
+            } else {
+                ModOffsets::default()
+            };
+            self.mod_offsets
+                .add(Destination::ModDepth, mod_depth_offset);
+            self.mod_offsets
+                .add(Destination::GlobalFilterCutoff, cutoff_offset);
+            let offsets = self.mod_offsets;
+            self.mod_depth[sample] = offsets.apply(
+                Destination::ModDepth,
+                &params.mod_depth,
+                values.mod_depth[sample],
+            );
+
+            for (operator_idx, operator) in
+                self.operators.iter_mut().enumerate().take(num_operators)
+            {
+                let params = operator_params[operator_idx];
+                let values = &values.operators[operator_idx];
+                let modulated = |parameter, param: &FloatParam, values: &[f32; BLOCK_SIZE]| {
+                    offsets.apply(
+                        Destination::Operator(operator_idx, parameter),
+                        param,
+                        values[sample],
+                    )
+                };
+
+                operator.freq[sample] = if values.keyboard {
+                    note_freq * modulated(OperatorDestination::Ratio, &params.ratio, &values.ratio)
+                } else {
+                    modulated(OperatorDestination::Freq, &params.freq, &values.freq)
+                };
+                operator.feedback_amount[sample] = modulated(
+                    OperatorDestination::Feedback,
+                    &params.feedback,
+                    &values.feedback,
+                );
+                operator.wavetable_position[sample] = modulated(
+                    OperatorDestination::WavetablePosition,
+                    &params.wavetable_position,
+                    &values.wavetable_position,
+                );
+                operator.gain[sample] =
+                    modulated(OperatorDestination::Level, &params.level, &values.level)
+                        * operator.envelope.process()
+                        * envelope_scale[operator_idx];
+            }
+
+            self.amplitude += amplitude_step;
+            self.envelope_values[sample] = self.envelope.process() * self.amplitude;
+            self.filter_cutoff[sample] = offsets.apply(
+                Destination::GlobalFilterCutoff,
+                &params.global_filter_cutoff,
+                values.global_filter_cutoff[sample],
+            );
+        }
+    }
+
+    /// Multiply the amplitude modulation from an operator's modulators into the operator's gain.
+    /// The modulators need to have been rendered already.
+    fn apply_modulators(&mut self, operator_idx: usize, routing: &Routing, len: usize) {
+        for modulator_idx in routing.modulators(operator_idx) {
+            let (carriers, modulators) = self.operators.split_at_mut(modulator_idx);
+            let operator = &mut carriers[operator_idx];
+            for sample in 0..len {
+                operator.gain[sample] = self.bridge.process(
+                    operator.gain[sample],
+                    modulators[0].output[sample],
+                    self.mod_depth[sample],
+                );
             }
-            EnvelopeStage::Sustain => {}
-            EnvelopeStage::Release => {
-                self.value -= self.value / (self.release * self.sample_rate);
-                if self.value <= 0.001 {
-                    self.value = 0.0;
-                    self.stage = EnvelopeStage::Idle;
+        }
+    }
+
+    /// Mix the voice's output operators into `output`, and apply the global envelope and filter.
+    fn mix(
+        &mut self,
+        params: &AmSynthParams,
+        values: &BlockValues,
+        routing: &Routing,
+        num_operators: usize,
+        output: &mut [f32],
+    ) {
+        let filter_enabled = params.global_filter_enabled.value();
+        if filter_enabled {
+            self.global_filter
+                .set_filter_type(params.global_filter_type.value());
+        }
+
+        // Keep the level consistent between algorithms with a different number of outputs
+        let velocity_gain =
+            1.0 - values.velocity_sensitivity + values.velocity_sensitivity * self.velocity;
+        let level = velocity_gain / routing.num_outputs() as f32;
+        for (sample, output) in output.iter_mut().enumerate() {
+            let mut voice_output = 0.0;
+            for operator_idx in (0..num_operators).rev() {
+                if routing.is_output(operator_idx) {
+                    voice_output += self.operators[operator_idx].output[sample];
                 }
             }
+            voice_output *= level;
+            voice_output *= self.envelope_values[sample];
+            voice_output *= self.fade;
+            self.fade = (self.fade - self.fade_step).max(0.0);
+
+            if filter_enabled {
+                self.global_filter
+                    .set_params(self.filter_cutoff[sample], values.global_filter_resonance);
+                voice_output = self.global_filter.process(voice_output);
+            }
+
+            *output += voice_output;
         }
-        self.value
     }
 }
 
-impl Voice {
+impl Operator {
     fn new(sample_rate: f32) -> Self {
         Self {
-            carrier: SineOscillator::new(sample_rate),
-            modulator: SineOscillator::new(sample_rate),
-            bridge: AmBridge::new(),
-            carrier_filter: ResonantFilter::new(),
-            modulator_filter: ResonantFilter::new(),
-            global_filter: ResonantFilter::new(),
-            active: false,
-            note: 0,
-            velocity: 0.0,
-            envelope: Envelope::new(sample_rate), // Initialize Envelope
+            sine: SineOscillator::new(sample_rate),
+            wavetable: WavetableOscillator::new(sample_rate),
+            player: SamplePlayer::new(sample_rate),
+            feedback: FeedbackBridge::new(),
+            envelope: Envelope::new(sample_rate),
+            filter: ResonantFilter::new(sample_rate),
+            freq: [0.0; BLOCK_SIZE],
+            feedback_amount: [0.0; BLOCK_SIZE],
+            wavetable_position: [0.0; BLOCK_SIZE],
+            gain: [0.0; BLOCK_SIZE],
+            output: [0.0; BLOCK_SIZE],
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sine.set_sample_rate(sample_rate);
+        self.wavetable.set_sample_rate(sample_rate);
+        self.player.set_sample_rate(sample_rate);
+        self.envelope.set_sample_rate(sample_rate);
+        self.filter.set_sample_rate(sample_rate);
+    }
+
+    fn trigger(&mut self) {
+        self.envelope.trigger();
+        self.player.reset();
+        self.feedback.reset();
+    }
+
+    fn set_frequency(&mut self, freq: f32, root_freq: f32) {
+        self.sine.set_frequency(freq);
+        self.wavetable.set_frequency(freq);
+        self.player.set_frequency(freq, root_freq);
+    }
+
+    /// Generate the next sample with the selected engine, before any modulation is applied. The
+    /// operator's own feedback is applied to the oscillators' phase, and to the sample's amplitude
+    /// since samples cannot be phase modulated.
+    fn generate(
+        &mut self,
+        engine: OscillatorEngine,
+        feedback_amount: f32,
+        table: &Wavetable,
+        engine_data: &EngineData,
+    ) -> f32 {
+        let feedback = self.feedback.process(feedback_amount);
+        match engine {
+            OscillatorEngine::Sine => self.sine.generate_with_phase_offset(feedback),
+            OscillatorEngine::Wavetable => {
+                self.wavetable.generate_with_phase_offset(table, feedback)
+            }
+            OscillatorEngine::Sample => {
+                self.player
+                    .generate(engine_data.sample, engine_data.loop_region)
+                    * (1.0 + feedback)
+            }
+        }
+    }
+
+    /// Render the current block one sample at a time. This is used for the engines that can't be
+    /// rendered with [`SineLanes`].
+    fn render(
+        &mut self,
+        engine: OscillatorEngine,
+        table: &Wavetable,
+        engine_data: &EngineData,
+        root_freq: f32,
+        len: usize,
+    ) {
+        for sample in 0..len {
+            self.set_frequency(self.freq[sample], root_freq);
+            self.wavetable.set_position(self.wavetable_position[sample]);
+            let output = self.generate(engine, self.feedback_amount[sample], table, engine_data)
+                * self.gain[sample];
+            self.feedback.push(output);
+            self.output[sample] = output;
+        }
+    }
+
+    /// Run the operator's output for the current block through its filter.
+    fn apply_filter(&mut self, values: &FilterValues, len: usize) {
+        self.filter.set_filter_type(values.filter_type);
+        for (sample, output) in self.output[..len].iter_mut().enumerate() {
+            self.filter
+                .set_params(values.cutoff[sample], values.resonance);
+            *output = self.filter.process(*output);
+        }
+    }
+}
+
+/// Render a sine operator for up to [`LANES`] voices at once.
+fn render_sine_lanes(
+    lanes: &mut SineLanes,
+    mut voices: [Option<&mut Voice>; LANES],
+    operator_idx: usize,
+    sample_rate: f32,
+    len: usize,
+) {
+    for (lane, voice) in voices.iter().enumerate() {
+        let Some(voice) = voice else {
+            lanes.clear_lane(lane);
+            continue;
+        };
+
+        let operator = &voice.operators[operator_idx];
+        let [newest, oldest] = operator.feedback.history();
+        lanes.phase[lane] = operator.sine.phase();
+        lanes.history[0][lane] = newest;
+        lanes.history[1][lane] = oldest;
+        for sample in 0..len {
+            lanes.phase_delta[sample][lane] = TAU * operator.freq[sample] / sample_rate;
+            lanes.feedback[sample][lane] = operator.feedback_amount[sample];
+            lanes.gain[sample][lane] = operator.gain[sample];
+        }
+    }
+
+    lanes.render(len);
+
+    for (lane, voice) in voices.iter_mut().enumerate() {
+        let Some(voice) = voice else {
+            continue;
+        };
+
+        let operator = &mut voice.operators[operator_idx];
+        operator.sine.set_phase(lanes.phase[lane]);
+        operator
+            .feedback
+            .set_history([lanes.history[0][lane], lanes.history[1][lane]]);
+        for sample in 0..len {
+            operator.output[sample] = lanes.output[sample][lane];
+        }
+    }
+}
+
+/// The next `len` values of a parameter's smoother.
+fn smoothed_block(smoothers: &mut Smoothers, param: &FloatParam, len: usize) -> [f32; BLOCK_SIZE] {
+    let mut values = [0.0; BLOCK_SIZE];
+    smoothers.next_block(param, &mut values, len);
+
+    values
+}
+
+impl BlockValues {
+    fn next(params: &AmSynthParams, smoothers: &mut Smoothers, len: usize) -> Self {
+        let operator_params = params.operators();
+        Self {
+            len,
+            tuning: smoothers.next_step(&params.tuning, len as u32),
+            mod_depth: smoothed_block(smoothers, &params.mod_depth, len),
+            global_filter_cutoff: smoothed_block(smoothers, &params.global_filter_cutoff, len),
+            global_filter_resonance: smoothers
+                .next_step(&params.global_filter_resonance, len as u32),
+            carrier_filter: FilterValues::next(
+                &params.carrier_filter_enabled,
+                &params.carrier_filter_type,
+                &params.carrier_filter_cutoff,
+                &params.carrier_filter_resonance,
+                smoothers,
+                len,
+            ),
+            modulator_filter: FilterValues::next(
+                &params.modulator_filter_enabled,
+                &params.modulator_filter_type,
+                &params.modulator_filter_cutoff,
+                &params.modulator_filter_resonance,
+                smoothers,
+                len,
+            ),
+            global_envelope: EnvelopeValues::next(
+                params.envelope(EnvelopeTarget::Global),
+                smoothers,
+                len,
+            ),
+            mod_envelope: EnvelopeValues::next(
+                params.envelope(EnvelopeTarget::Mod),
+                smoothers,
+                len,
+            ),
+            operators: std::array::from_fn(|operator| {
+                OperatorValues::next(
+                    operator_params[operator],
+                    params.envelope(EnvelopeTarget::Operator(operator)),
+                    smoothers,
+                    len,
+                )
+            }),
+            pressure_target: params.pressure_target.value(),
+            pressure_amount: smoothers.next_step(&params.pressure_amount, len as u32),
+            slide_amount: smoothers.next_step(&params.slide_amount, len as u32),
+            velocity_sensitivity: smoothers.next_step(&params.velocity_sensitivity, len as u32),
+            velocity_mod_depth: smoothers.next_step(&params.velocity_mod_depth, len as u32),
+            velocity_modulator_envelope: smoothers
+                .next_step(&params.velocity_modulator_envelope, len as u32),
+            velocity_cutoff: smoothers.next_step(&params.velocity_cutoff, len as u32),
+        }
+    }
+}
+
+impl FilterValues {
+    fn next(
+        enabled: &BoolParam,
+        filter_type: &BoolParam,
+        cutoff: &FloatParam,
+        resonance: &FloatParam,
+        smoothers: &mut Smoothers,
+        len: usize,
+    ) -> Self {
+        Self {
+            enabled: enabled.value(),
+            filter_type: filter_type.value(),
+            cutoff: smoothed_block(smoothers, cutoff, len),
+            resonance: smoothers.next_step(resonance, len as u32),
+        }
+    }
+}
+
+impl EnvelopeValues {
+    fn next(params: EnvelopeParams, smoothers: &mut Smoothers, len: usize) -> Self {
+        let steps = len as u32;
+        Self {
+            mode: params.mode.value(),
+            delay: smoothers.next_step(&params.delay, steps),
+            attack: smoothers.next_step(&params.attack, steps),
+            hold: smoothers.next_step(&params.hold, steps),
+            decay: smoothers.next_step(&params.decay, steps),
+            sustain: smoothers.next_step(&params.sustain, steps),
+            release: smoothers.next_step(&params.release, steps),
+            curves: EnvelopeCurves {
+                attack: smoothers.next_step(&params.attack_curve, steps),
+                decay: smoothers.next_step(&params.decay_curve, steps),
+                release: smoothers.next_step(&params.release_curve, steps),
+            },
+        }
+    }
+
+    fn apply(&self, envelope: &mut Envelope) {
+        envelope.set_parameters(self.attack, self.decay, self.sustain, self.release);
+        envelope.set_delay_and_hold(self.delay, self.hold);
+        envelope.set_curves(self.curves);
+        envelope.set_mode(self.mode);
+    }
+}
+
+impl OperatorValues {
+    fn next(
+        params: &OperatorParams,
+        envelope: EnvelopeParams,
+        smoothers: &mut Smoothers,
+        len: usize,
+    ) -> Self {
+        Self {
+            engine: params.engine.value(),
+            keyboard: params.keyboard.value(),
+            ratio: smoothed_block(smoothers, &params.ratio, len),
+            freq: smoothed_block(smoothers, &params.freq, len),
+            level: smoothed_block(smoothers, &params.level, len),
+            feedback: smoothed_block(smoothers, &params.feedback, len),
+            wavetable_position: smoothed_block(smoothers, &params.wavetable_position, len),
+            envelope: EnvelopeValues::next(envelope, smoothers, len),
         }
     }
 }
 
 impl Default for AmSynth {
     fn default() -> Self {
-        Self {
-            params: Arc::new(AmSynthParams::default()),
-            sample_rate: 44100.0,
-            voices: (0..8).map(|_| Voice::new(44100.0)).collect(),
+        Self::with_params(Arc::new(AmSynthParams::default()))
+    }
+}
+
+/// Build the wavetable for a source on the background thread and send it to the audio thread. The
+/// source is only stored in the parameters if it could be loaded.
+fn swap_wavetable(
+    params: &AmSynthParams,
+    engine_updates: &Handoff<EngineUpdate>,
+    operator: usize,
+    source: Result<WavetableSource, String>,
+) {
+    let table =
+        source.and_then(|source| Wavetable::from_source(&source).map(|table| (table, source)));
+    match table {
+        Ok((table, source)) => {
+            if engine_updates.send(EngineUpdate::Wavetable(operator, Arc::new(table))) {
+                *params.wavetable_source(operator).write().unwrap() = source;
+            } else {
+                nih_error!(
+                    "Failed to load wavetable: too many tables are waiting for the audio thread"
+                );
+            }
         }
+        Err(err) => nih_error!("Failed to load wavetable: {err}"),
     }
 }
 
+/// Build the wavetable for a source, falling back to the default built-in table if the source
+/// cannot be loaded. Files may have been moved since the state was saved.
+fn load_wavetable(source: &WavetableSource) -> Wavetable {
+    Wavetable::from_source(source).unwrap_or_else(|err| {
+        nih_error!("Failed to load wavetable: {err}");
+        Wavetable::builtin(BuiltinWavetable::default())
+    })
+}
+
 impl Plugin for AmSynth {
     const NAME: &'static str = "AM Synth";
     const VENDOR: &'static str = "The Muzikar";
@@ -144,87 +853,721 @@ impl Plugin for AmSynth {
         names: PortNames::const_default(),
     }];
 
-    type BackgroundTask = ();
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    type BackgroundTask = Task;
     type SysExMessage = ();
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
-    fn initialize(&mut self, _audio_io_layout: &AudioIOLayout, buffer_config: &BufferConfig, _context: &mut impl InitContext<Self>) -> bool {
-        self.sample_rate = buffer_config.sample_rate;
-        self.voices = (0..8).map(|_| Voice::new(self.sample_rate)).collect();
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let params = self.params.clone();
+        let engine_updates = self.engine_updates.clone();
+        let midi_learn = self.midi_learn.clone();
+
+        Box::new(move |task| match task {
+            Task::LoadWavetable(operator, source) => {
+                swap_wavetable(&params, &engine_updates, operator, Ok(source));
+            }
+            Task::EmbedWavetable(operator, path) => {
+                swap_wavetable(
+                    &params,
+                    &engine_updates,
+                    operator,
+                    WavetableSource::embed(&path),
+                );
+            }
+            Task::LoadSample(path) => match Sample::load(&path) {
+                Ok(sample) => {
+                    if engine_updates.send(EngineUpdate::Sample(Arc::new(sample))) {
+                        *params.sample.write().unwrap() = Some(path);
+                    } else {
+                        nih_error!("Failed to load sample: too many samples are waiting for the audio thread");
+                    }
+                }
+                Err(err) => nih_error!("Failed to load sample: {err}"),
+            },
+            Task::ApplyMidiCcs => midi_learn.apply_pending(),
+        })
+    }
+
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            self.params.clone(),
+            self.meter.clone(),
+            self.analyzer_output.clone(),
+            self.keyboard.clone(),
+            self.midi_learn.clone(),
+            self.mod_matrix.clone(),
+            self.params.editor_state.clone(),
+            async_executor,
+        )
+    }
+
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.prepare(
+            buffer_config.sample_rate,
+            buffer_config.max_buffer_size as usize,
+        );
+        // A restored preset's values shouldn't be smoothed
+        self.reset_smoothers();
+
+        // The wavetable sources may have changed when restoring a preset
+        for (operator, table) in self.wavetables.iter_mut().enumerate() {
+            *table = Arc::new(load_wavetable(
+                &self.params.wavetable_source(operator).read().unwrap(),
+            ));
+        }
+
+        let sample = match &*self.params.sample.read().unwrap() {
+            Some(path) => Sample::load(path).unwrap_or_else(|err| {
+                nih_error!("Failed to load sample: {err}");
+                Sample::empty()
+            }),
+            None => Sample::empty(),
+        };
+        self.sample = Arc::new(sample);
+
         true
     }
 
     fn reset(&mut self) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.active) {
+            voice.fade_out(self.sample_rate);
+        }
+        self.channel_expressions = [Expression::default(); 16];
+        self.zone_bend = 0.0;
+        self.arpeggiator = Arpeggiator::default();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        let transport = context.transport();
+        let transport = TransportPosition {
+            tempo: transport.tempo,
+            playing: transport.playing,
+            pos_beats: transport.pos_beats(),
+        };
+
+        let apply_midi_ccs =
+            self.process_buffer(buffer.as_slice(), transport, || context.next_event());
+        if apply_midi_ccs {
+            context.execute_gui(Task::ApplyMidiCcs);
+        }
+        if self.params.editor_state.is_open() {
+            self.keyboard.set_held_notes(
+                self.voices
+                    .iter()
+                    .filter(|voice| voice.is_held())
+                    .map(|voice| voice.note),
+            );
+        }
+
+        ProcessStatus::Normal
+    }
+}
+
+/// The DSP components and the headless renderer used by the benchmarks in `benches/`. This is not
+/// a stable API.
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::block::BLOCK_SIZE;
+    pub use crate::bridge::am::AmBridge;
+    pub use crate::envelope::{Envelope, EnvelopeCurves};
+    pub use crate::filter::ResonantFilter;
+    pub use crate::params::{AmSynthParams, OperatorParams};
+    pub use crate::synth::sine::{fast_sin, SineOscillator};
+    pub use crate::AmSynth;
+}
+
+impl AmSynth {
+    /// Create the plugin for a set of parameters. Everything that reads from the parameters is
+    /// created for these, so tests can use non-default parameters.
+    fn with_params(params: Arc<AmSynthParams>) -> Self {
+        let (analyzer_input, analyzer_output) = analyzer::analyzer(44100.0);
+
+        Self {
+            params: params.clone(),
+            sample_rate: 44100.0,
+            voices: (0..8).map(|_| Voice::new(44100.0)).collect(),
+            output: Vec::new(),
+            sine_lanes: Box::new(SineLanes::new()),
+            wavetables: std::array::from_fn(|_| {
+                Arc::new(Wavetable::builtin(BuiltinWavetable::default()))
+            }),
+            engine_updates: Arc::default(),
+            sample: Arc::new(Sample::empty()),
+            meter: Arc::new(OutputMeter::new()),
+            meter_weights: MeterWeights::new(44100.0),
+            analyzer_input,
+            analyzer_output: Arc::new(Mutex::new(analyzer_output)),
+            keyboard: Arc::default(),
+            midi_learn: Arc::new(MidiLearn::new(params.clone())),
+            smoothers: Smoothers::new(&params, 44100.0),
+            modulator: Modulator::new(44100.0),
+            mod_matrix: Arc::new(ModMatrix::new(params)),
+            channel_expressions: [Expression::default(); 16],
+            zone_bend: 0.0,
+            velocity_table: VelocityTable::default(),
+            arpeggiator: Arpeggiator::default(),
+        }
+    }
+
+    /// Prepare every DSP component for a sample rate and a maximum block size. This is called
+    /// whenever the host (re)initializes the plugin. The voices and the LFOs keep their state, so
+    /// notes that are playing continue at the same pitch and from the same point in their
+    /// envelopes, and the coefficients that depend on the sample rate are computed here instead of
+    /// while rendering.
+    pub fn prepare(&mut self, sample_rate: f32, max_block: usize) {
+        self.sample_rate = sample_rate;
         for voice in &mut self.voices {
-            voice.active = false;
+            voice.set_sample_rate(sample_rate);
+        }
+        self.smoothers.set_sample_rate(sample_rate);
+        self.modulator.set_sample_rate(sample_rate);
+        self.meter_weights = MeterWeights::new(sample_rate);
+        self.output.resize(max_block, 0.0);
+
+        // The analyzer's history is only valid at the sample rate it was recorded at
+        let mut analyzer_output = self.analyzer_output.lock().unwrap();
+        if analyzer_output.sample_rate() != sample_rate {
+            let (new_input, new_output) = analyzer::analyzer(sample_rate);
+            self.analyzer_input = new_input;
+            *analyzer_output = new_output;
         }
     }
 
-    fn process(&mut self, buffer: &mut Buffer, _aux: &mut AuxiliaryBuffers, context: &mut impl ProcessContext<Self>) -> ProcessStatus {
-        let tuning = self.params.tuning.value();
+    /// Replace the voices with `num_voices` new voices. The plugin always has eight voices, the
+    /// benchmarks use this to measure how rendering scales with the number of voices.
+    pub fn set_polyphony(&mut self, num_voices: usize) {
+        self.voices = (0..num_voices)
+            .map(|_| Voice::new(self.sample_rate))
+            .collect();
+    }
 
-        for mut channel_samples in buffer.iter_samples() {
-            let mut output = 0.0;
+    /// Snap every smoother to its parameter's value, so parameter changes before this point don't
+    /// get smoothed.
+    pub fn reset_smoothers(&mut self) {
+        self.smoothers.snap();
+    }
 
-            for voice in &mut self.voices {
-                if voice.active {
-                    let carrier_freq = if self.params.carrier_keyboard.value() {
-                        util::midi_note_to_freq(voice.note, tuning)
-                    } else {
-                        self.params.carrier_freq.value()
-                    };
+    /// Render the next block into every channel of `output`. This is everything `process()` does
+    /// besides handling the incoming events, so the plugin can be rendered without a host. The
+    /// block cannot be longer than the maximum block size passed to [`AmSynth::prepare()`].
+    pub fn render(&mut self, output: &mut [&mut [f32]], tempo: Option<f64>) {
+        self.render_split(output, tempo, |_, _| usize::MAX);
+    }
 
-                    let modulator_freq = if self.params.modulator_keyboard.value() {
-                        util::midi_note_to_freq(voice.note, tuning)
-                    } else {
-                        self.params.modulator_freq.value()
-                    };
+    /// Handle the events and render the next buffer into every channel of `output`. The host's
+    /// events from `next_event` and the arpeggiator's notes are applied at their exact samples by
+    /// splitting the buffer at them. Returns whether MIDI learn received CCs that need to be
+    /// applied on the GUI thread.
+    pub fn process_buffer(
+        &mut self,
+        output: &mut [&mut [f32]],
+        transport: TransportPosition,
+        mut next_event: impl FnMut() -> Option<NoteEvent<()>>,
+    ) -> bool {
+        let num_samples = output.first().map_or(0, |channel| channel.len());
+        if let Some(event) =
+            self.arpeggiator
+                .start_block(&self.params.arp, transport, self.sample_rate)
+        {
+            self.handle_event(event);
+        }
+
+        // The notes played on the editor's keyboard are merged with the host's events at the start
+        // of the buffer
+        let mut apply_midi_ccs = false;
+        while let Some(event) = self.keyboard.next_event() {
+            apply_midi_ccs |= self.route_event(event);
+        }
+
+        let mut pending = next_event();
+        self.render_split(output, transport.tempo, |synth, position| {
+            while let Some(event) = pending.filter(|event| event.timing() as usize <= position) {
+                apply_midi_ccs |= synth.route_event(event);
+                pending = next_event();
+            }
+            for event in synth
+                .arpeggiator
+                .events_at(&synth.params.arp, position)
+                .into_iter()
+                .flatten()
+            {
+                synth.handle_event(event);
+            }
+
+            let next_event_position = pending.map_or(usize::MAX, |event| event.timing() as usize);
+            next_event_position.min(synth.arpeggiator.next_event().unwrap_or(usize::MAX))
+        });
+
+        // Events timed past the end of the buffer still need to be applied
+        while let Some(event) = pending {
+            apply_midi_ccs |= self.route_event(event);
+            pending = next_event();
+        }
+        self.arpeggiator.end_block(num_samples);
+
+        apply_midi_ccs
+    }
+
+    /// The same as [`AmSynth::render()`], but the buffer is split into blocks that end at the
+    /// positions returned by `next_split`. This is called with the start of every block so it can
+    /// change the synth's state at that sample, and it returns the position of the next split.
+    fn render_split(
+        &mut self,
+        output: &mut [&mut [f32]],
+        tempo: Option<f64>,
+        mut next_split: impl FnMut(&mut Self, usize) -> usize,
+    ) {
+        let num_samples = output.first().map_or(0, |channel| channel.len());
+
+        // The engines keep rendering with the previous table or sample until a new one has been
+        // loaded
+        self.receive_engine_updates();
+        self.smoothers.set_time(self.params.smoothing_time.value());
+        let tables = self.wavetables.clone();
+        let sample = self.sample.clone();
+        let engine_data =
+            self.engine_data(std::array::from_fn(|operator| &*tables[operator]), &sample);
+        self.modulator.update_routes(&self.params);
+        self.update_envelopes(tempo);
+
+        // The voices are rendered once, and the mono output is copied to every channel
+        let mut mono = std::mem::take(&mut self.output);
+        let samples = &mut mono[..num_samples];
+        let mut position = 0;
+        while position < num_samples {
+            let split = next_split(self, position).clamp(position + 1, num_samples);
+            for block in samples[position..split].chunks_mut(BLOCK_SIZE) {
+                self.render_block(&engine_data, block);
+            }
+            position = split;
+        }
+        for channel in output.iter_mut() {
+            channel.copy_from_slice(samples);
+        }
+        self.output = mono;
+
+        // The meters and the analyzer are only computed while they can be seen. The output is
+        // mono, so the analyzer only needs the first channel.
+        if self.params.editor_state.is_open() {
+            for (channel, samples) in output.iter().take(2).enumerate() {
+                self.meter.update(channel, samples, self.meter_weights);
+            }
+            if let Some(samples) = output.first() {
+                self.analyzer_input.process(samples);
+            }
+            self.mod_matrix.publish(
+                self.voices
+                    .iter()
+                    .filter(|voice| voice.active)
+                    .map(|voice| &voice.mod_offsets),
+            );
+        }
+    }
+
+    /// Swap in the tables and samples that were loaded on the background thread. The replaced ones
+    /// are handed back so they get deallocated there.
+    fn receive_engine_updates(&mut self) {
+        while let Some(update) = self.engine_updates.receive() {
+            let replaced = match update {
+                EngineUpdate::Wavetable(operator, table) => EngineUpdate::Wavetable(
+                    operator,
+                    std::mem::replace(&mut self.wavetables[operator], table),
+                ),
+                EngineUpdate::Sample(sample) => {
+                    EngineUpdate::Sample(std::mem::replace(&mut self.sample, sample))
+                }
+            };
+            self.engine_updates.retire(replaced);
+        }
+    }
 
-                    voice.carrier.set_frequency(carrier_freq);
-                    voice.modulator.set_frequency(modulator_freq);
+    /// Gather the engines' shared data for a block.
+    fn engine_data<'a>(
+        &self,
+        tables: [&'a Wavetable; MAX_OPERATORS],
+        sample: &'a Sample,
+    ) -> EngineData<'a> {
+        let loop_region = match self.params.sample_loop_mode.value() {
+            SampleLoopMode::Loop => Some(sample.loop_region(
+                self.params.sample_loop_start.value(),
+                self.params.sample_loop_end.value(),
+                self.params.sample_crossfade.value(),
+            )),
+            _ => None,
+        };
 
-                    let carrier_sample = voice.carrier.generate();
-                    let modulator_sample = voice.modulator.generate();
-                    let modulated = voice.bridge.process(carrier_sample, modulator_sample, self.params.mod_depth.value());
+        EngineData {
+            tables,
+            sample,
+            loop_region,
+        }
+    }
+
+    /// Pass the multi-stage shapes and the tempo sync to every voice's envelopes. If the editor is
+    /// changing a shape at the same time then the envelopes keep the previous block's shape.
+    fn update_envelopes(&mut self, tempo: Option<f64>) {
+        let time_scale = match tempo {
+            Some(tempo) if self.params.envelope_sync.value() => (SYNC_TEMPO / tempo) as f32,
+            _ => 1.0,
+        };
+        let shape = |target| {
+            self.params
+                .breakpoints(target)
+                .try_read()
+                .ok()
+                .map(|shape| *shape)
+        };
+        let global_shape = shape(EnvelopeTarget::Global);
+        let mod_shape = shape(EnvelopeTarget::Mod);
+        let operator_shapes: [_; MAX_OPERATORS] =
+            std::array::from_fn(|operator| shape(EnvelopeTarget::Operator(operator)));
+
+        for voice in &mut self.voices {
+            let envelopes = [
+                (&mut voice.envelope, global_shape),
+                (&mut voice.mod_envelope, mod_shape),
+            ]
+            .into_iter()
+            .chain(
+                voice
+                    .operators
+                    .iter_mut()
+                    .map(|operator| &mut operator.envelope)
+                    .zip(operator_shapes),
+            );
+            for (envelope, shape) in envelopes {
+                envelope.set_time_scale(time_scale);
+                if let Some(shape) = shape {
+                    envelope.set_breakpoints(&shape);
+                }
+            }
+        }
+    }
+
+    /// Render the next block of at most [`BLOCK_SIZE`] samples by summing all active voices into
+    /// `output`.
+    fn render_block(&mut self, engine_data: &EngineData, output: &mut [f32]) {
+        let len = output.len();
+        let values = BlockValues::next(&self.params, &mut self.smoothers, len);
+        let root_freq =
+            util::midi_note_to_freq(self.params.sample_root_key.value() as u8, values.tuning);
+        let num_operators = self.params.operator_count.value() as usize;
+        let routing = self.params.algorithm.value().routing(num_operators);
+        self.modulator
+            .advance(&self.params, &mut self.smoothers, len);
+        output.fill(0.0);
+
+        for voice in self.voices.iter_mut().filter(|voice| voice.active) {
+            voice.prepare_block(
+                &self.params,
+                &values,
+                &self.modulator,
+                &routing,
+                num_operators,
+            );
+        }
 
-                    let mut voice_output = modulated * voice.velocity;
+        // Modulators always have a higher index than their carriers, so this renders every
+        // operator's modulators before the operator itself
+        for operator_idx in (0..num_operators).rev() {
+            for voice in self.voices.iter_mut().filter(|voice| voice.active) {
+                voice.apply_modulators(operator_idx, &routing, len);
+            }
+
+            let engine = values.operators[operator_idx].engine;
+            if engine == OscillatorEngine::Sine {
+                let mut voices = self.voices.iter_mut().filter(|voice| voice.active);
+                loop {
+                    let group: [_; LANES] = std::array::from_fn(|_| voices.next());
+                    if group[0].is_none() {
+                        break;
+                    }
 
-                    // Process envelope
-                    voice_output *= voice.envelope.process();
+                    render_sine_lanes(
+                        &mut self.sine_lanes,
+                        group,
+                        operator_idx,
+                        self.sample_rate,
+                        len,
+                    );
+                }
+            } else {
+                for voice in self.voices.iter_mut().filter(|voice| voice.active) {
+                    voice.operators[operator_idx].render(
+                        engine,
+                        engine_data.tables[operator_idx],
+                        engine_data,
+                        root_freq,
+                        len,
+                    );
+                }
+            }
 
-                    output += voice_output;
+            // The operator's carriers read its output from here on, so the modulator filter
+            // changes the modulation while the carrier filter changes what's heard
+            let filter = if routing.is_output(operator_idx) {
+                &values.carrier_filter
+            } else {
+                &values.modulator_filter
+            };
+            if filter.enabled {
+                for voice in self.voices.iter_mut().filter(|voice| voice.active) {
+                    voice.operators[operator_idx].apply_filter(filter, len);
                 }
             }
+        }
 
-            for sample in channel_samples.iter_mut() {
-                *sample = output;
+        for voice in self.voices.iter_mut().filter(|voice| voice.active) {
+            voice.mix(&self.params, &values, &routing, num_operators, output);
+            if voice.is_finished() {
+                voice.active = false;
             }
         }
+    }
 
-        // MIDI events processing
-        while let Some(event) = context.next_event() {
-            match event {
-                NoteEvent::NoteOn { note, velocity, .. } => {
-                    if let Some(voice) = self.voices.iter_mut().find(|v| !v.active) {
-                        voice.active = true;
-                        voice.note = note;
-                        voice.velocity = velocity;
-                        voice.envelope.trigger();
+    /// Apply a single note event to the voices. A note off releases every voice that's playing
+    /// the note, so a key that's played twice before it's released doesn't leave a hanging voice.
+    pub fn handle_event(&mut self, event: NoteEvent<()>) {
+        match event {
+            NoteEvent::NoteOn {
+                voice_id,
+                channel,
+                note,
+                velocity,
+                ..
+            } => {
+                let velocity = self.shape_velocity(velocity);
+                if let Some(voice) = self.voices.iter_mut().find(|v| !v.active) {
+                    voice.trigger(voice_id, channel, note, velocity);
+                    if self.params.mpe_enabled.value() {
+                        // MPE notes start without any pressure, but with their channel's pitch bend
+                        // and slide
+                        voice.expression = Expression {
+                            pressure: Some(0.0),
+                            ..self.channel_expressions[usize::from(channel) % 16]
+                        };
+                        voice.zone_bend = self.zone_bend;
                     }
                 }
-                NoteEvent::NoteOff { note, .. } => {
-                    if let Some(voice) = self.voices.iter_mut().find(|v| v.active && v.note == note) {
-                        voice.envelope.release();
+            }
+            NoteEvent::NoteOff {
+                voice_id,
+                channel,
+                note,
+                ..
+            } => {
+                for voice in self
+                    .voices
+                    .iter_mut()
+                    .filter(|v| v.is_held() && v.matches(voice_id, channel, note))
+                {
+                    voice.release();
+                }
+            }
+            NoteEvent::Choke {
+                voice_id,
+                channel,
+                note,
+                ..
+            } => {
+                for voice in self
+                    .voices
+                    .iter_mut()
+                    .filter(|v| v.matches(voice_id, channel, note))
+                {
+                    voice.fade_out(self.sample_rate);
+                }
+            }
+            NoteEvent::MidiCC {
+                channel,
+                cc: control_change::ALL_NOTES_OFF,
+                ..
+            } => {
+                for voice in self
+                    .voices
+                    .iter_mut()
+                    .filter(|v| v.is_held() && v.channel == channel)
+                {
+                    voice.release();
+                }
+            }
+            NoteEvent::MidiCC {
+                channel,
+                cc: control_change::ALL_SOUND_OFF,
+                ..
+            } => {
+                for voice in self
+                    .voices
+                    .iter_mut()
+                    .filter(|v| v.active && v.channel == channel)
+                {
+                    voice.fade_out(self.sample_rate);
+                }
+            }
+
+            // MPE, where every note has its own channel
+            NoteEvent::MidiPitchBend { channel, value, .. } if self.params.mpe_enabled.value() => {
+                if channel == self.params.mpe_zone.value().master_channel() {
+                    self.zone_bend = (value * 2.0 - 1.0) * MPE_MASTER_BEND_RANGE;
+                    for voice in &mut self.voices {
+                        voice.zone_bend = self.zone_bend;
                     }
+                } else {
+                    let bend = (value * 2.0 - 1.0) * self.params.mpe_bend_range.value() as f32;
+                    self.update_channel_expression(channel, |expression| expression.bend = bend);
                 }
-                _ => (),
             }
+            NoteEvent::MidiChannelPressure {
+                channel, pressure, ..
+            } if self.params.mpe_enabled.value() => {
+                self.update_channel_expression(channel, |expression| {
+                    expression.pressure = Some(pressure)
+                });
+            }
+            NoteEvent::MidiCC {
+                channel,
+                cc: MPE_SLIDE_CC,
+                value,
+                ..
+            } if self.params.mpe_enabled.value() => {
+                self.update_channel_expression(channel, |expression| expression.slide = value);
+            }
+
+            // CLAP note expressions and polyphonic aftertouch, which work the same way as MPE
+            NoteEvent::PolyTuning {
+                voice_id,
+                channel,
+                note,
+                tuning,
+                ..
+            } => {
+                self.update_note_expression(voice_id, channel, note, |expression| {
+                    expression.bend = tuning
+                });
+            }
+            NoteEvent::PolyPressure {
+                voice_id,
+                channel,
+                note,
+                pressure,
+                ..
+            } => {
+                self.update_note_expression(voice_id, channel, note, |expression| {
+                    expression.pressure = Some(pressure)
+                });
+            }
+            NoteEvent::PolyBrightness {
+                voice_id,
+                channel,
+                note,
+                brightness,
+                ..
+            } => {
+                self.update_note_expression(voice_id, channel, note, |expression| {
+                    expression.slide = brightness
+                });
+            }
+            _ => (),
         }
+    }
 
-        ProcessStatus::Normal
+    /// Apply an event from the host or from the editor's keyboard. CCs that aren't handled by the
+    /// voices go to MIDI learn, which moves the bound parameters' smoothers, and notes go to the
+    /// arpeggiator while it's enabled. Returns whether MIDI learn queued a CC.
+    fn route_event(&mut self, event: NoteEvent<()>) -> bool {
+        let latch = self.params.arp.latch.value();
+        match event {
+            NoteEvent::MidiCC { cc, value, .. } if !self.is_voice_cc(cc) => {
+                let smoothers = &mut self.smoothers;
+                return self
+                    .midi_learn
+                    .process_cc(cc, value, |param, value| smoothers.set_target(param, value));
+            }
+            NoteEvent::NoteOn {
+                channel,
+                note,
+                velocity,
+                ..
+            } if self.arpeggiator.is_enabled() => {
+                self.arpeggiator.note_on(channel, note, velocity, latch);
+            }
+            NoteEvent::NoteOff { channel, note, .. } if self.arpeggiator.is_enabled() => {
+                self.arpeggiator.note_off(channel, note, latch);
+            }
+            NoteEvent::MidiCC {
+                cc: control_change::ALL_NOTES_OFF | control_change::ALL_SOUND_OFF,
+                ..
+            } => {
+                self.arpeggiator.clear();
+                self.handle_event(event);
+            }
+            _ => self.handle_event(event),
+        }
+
+        false
+    }
+
+    /// Apply the velocity curve to a note's velocity.
+    fn shape_velocity(&mut self, velocity: f32) -> f32 {
+        let curve = self.params.velocity_curve.value();
+        if let Ok(table) = self.params.velocity_table.try_read() {
+            self.velocity_table = *table;
+        }
+
+        curve.apply(velocity, &self.velocity_table)
+    }
+
+    /// Whether a CC is handled by the voices instead of MIDI learn. CCs 120 and up are channel
+    /// mode messages, and CC 74 is the note's slide in MPE mode.
+    fn is_voice_cc(&self, cc: u8) -> bool {
+        cc >= control_change::ALL_SOUND_OFF
+            || (cc == MPE_SLIDE_CC && self.params.mpe_enabled.value())
+    }
+
+    /// Change an MPE member channel's expressions, and those of the notes held on that channel.
+    /// Notes that have been released keep their expressions, since the controller may already be
+    /// using the channel for a new note.
+    fn update_channel_expression(&mut self, channel: u8, update: impl Fn(&mut Expression)) {
+        update(&mut self.channel_expressions[usize::from(channel) % 16]);
+        for voice in self
+            .voices
+            .iter_mut()
+            .filter(|v| v.is_held() && v.channel == channel)
+        {
+            update(&mut voice.expression);
+        }
+    }
+
+    /// Change the expressions of the voices a CLAP note expression is meant for.
+    fn update_note_expression(
+        &mut self,
+        voice_id: Option<i32>,
+        channel: u8,
+        note: u8,
+        update: impl Fn(&mut Expression),
+    ) {
+        for voice in self
+            .voices
+            .iter_mut()
+            .filter(|v| v.matches(voice_id, channel, note))
+        {
+            update(&mut voice.expression);
+        }
     }
 }
 
@@ -251,3 +1594,730 @@ impl Vst3Plugin for AmSynth {
 
 nih_export_clap!(AmSynth);
 nih_export_vst3!(AmSynth);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arpeggiator::ArpParams;
+    use std::path::PathBuf;
+    use std::sync::RwLock;
+    use velocity::VelocityCurve;
+
+    /// The longest render the headless plugin instances are prepared for, one second at the
+    /// highest sample rate the tests use.
+    const MAX_RENDER_SAMPLES: usize = 192000;
+
+    /// Render `num_samples` mono samples with a freshly initialized plugin instance. Events are
+    /// applied right before the sample they're timed at.
+    fn render_headless(events: &[NoteEvent<()>], num_samples: usize) -> Vec<f32> {
+        render_headless_at(44100.0, events, num_samples)
+    }
+
+    /// The same as [`render_headless()`], but at a different sample rate.
+    fn render_headless_at(
+        sample_rate: f32,
+        events: &[NoteEvent<()>],
+        num_samples: usize,
+    ) -> Vec<f32> {
+        let mut synth = headless_synth(sample_rate);
+        render_events(&mut synth, events, num_samples)
+    }
+
+    fn headless_synth(sample_rate: f32) -> AmSynth {
+        let mut synth = AmSynth::default();
+        synth.prepare(sample_rate, MAX_RENDER_SAMPLES);
+        synth.reset_smoothers();
+
+        synth
+    }
+
+    /// The same as [`headless_synth()`], but with non-default parameters.
+    fn headless_synth_with(params: AmSynthParams) -> AmSynth {
+        let mut synth = AmSynth::with_params(Arc::new(params));
+        synth.prepare(44100.0, MAX_RENDER_SAMPLES);
+        synth.reset_smoothers();
+
+        synth
+    }
+
+    /// Continue rendering with an existing plugin instance, so its voices can be inspected
+    /// afterwards. The samples are rendered as a single buffer through
+    /// [`AmSynth::process_buffer()`], the same way `process()` renders the host's buffers.
+    fn render_events(
+        synth: &mut AmSynth,
+        events: &[NoteEvent<()>],
+        num_samples: usize,
+    ) -> Vec<f32> {
+        assert!(num_samples <= MAX_RENDER_SAMPLES);
+
+        let mut output = vec![0.0; num_samples];
+        let mut events = events.iter().copied();
+        synth.process_buffer(&mut [&mut output], TransportPosition::default(), || {
+            events.next()
+        });
+
+        output
+    }
+
+    /// Compare `output` against the reference buffer stored in `tests/reference/{name}.f32`. If
+    /// the `AM_SYNTH_BLESS` environment variable is set then the reference is (re)written instead.
+    /// A missing reference is a failure, so a fresh checkout can't silently pass.
+    fn assert_matches_reference(name: &str, output: &[f32]) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("reference")
+            .join(format!("{name}.f32"));
+
+        if std::env::var_os("AM_SYNTH_BLESS").is_some() {
+            let bytes: Vec<u8> = output
+                .iter()
+                .flat_map(|sample| sample.to_le_bytes())
+                .collect();
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, bytes).unwrap();
+            eprintln!("Wrote reference buffer to '{}'", path.display());
+            return;
+        }
+
+        let bytes = std::fs::read(&path).unwrap_or_else(|err| {
+            panic!(
+                "Could not read the reference buffer '{}': {err}. Run the tests with AM_SYNTH_BLESS=1 to write it.",
+                path.display()
+            )
+        });
+        let reference: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(
+            reference.len(),
+            output.len(),
+            "Reference length mismatch for '{name}'"
+        );
+
+        for (idx, (actual, expected)) in output.iter().zip(reference.iter()).enumerate() {
+            assert!(
+                (actual - expected).abs() <= 1e-4,
+                "'{name}' differs from its reference at sample {idx}: {actual} != {expected}"
+            );
+        }
+    }
+
+    fn note_on(timing: u32, note: u8, velocity: f32) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity,
+        }
+    }
+
+    fn note_off(timing: u32, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOff {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    fn midi_cc(timing: u32, channel: u8, cc: u8) -> NoteEvent<()> {
+        NoteEvent::MidiCC {
+            timing,
+            channel,
+            cc,
+            value: 0.0,
+        }
+    }
+
+    fn held_notes(synth: &AmSynth) -> Vec<(u8, u8)> {
+        synth
+            .voices
+            .iter()
+            .filter(|voice| voice.is_held())
+            .map(|voice| (voice.channel, voice.note))
+            .collect()
+    }
+
+    fn active_voices(synth: &AmSynth) -> usize {
+        synth.voices.iter().filter(|voice| voice.active).count()
+    }
+
+    #[test]
+    fn silent_without_notes() {
+        let output = render_headless(&[], 1024);
+        assert!(output.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn golden_single_note() {
+        let output = render_headless(&[note_on(0, 69, 1.0), note_off(4410, 69)], 8820);
+        assert!(output.iter().all(|sample| sample.is_finite()));
+        assert!(output.iter().any(|sample| sample.abs() > 0.1));
+
+        assert_matches_reference("single_note", &output);
+    }
+
+    #[test]
+    fn golden_chord() {
+        let output = render_headless(
+            &[
+                note_on(0, 60, 0.8),
+                note_on(100, 64, 0.6),
+                note_on(200, 67, 0.4),
+                note_off(4410, 60),
+                note_off(4410, 64),
+                note_off(4410, 67),
+            ],
+            8820,
+        );
+        assert!(output.iter().all(|sample| sample.is_finite()));
+
+        assert_matches_reference("chord", &output);
+    }
+
+    #[test]
+    fn note_off_releases_every_matching_voice() {
+        let mut synth = headless_synth(44100.0);
+        let on_channel = |channel, note| NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 1.0,
+        };
+        let off_channel = |channel, note| NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 0.0,
+        };
+
+        // The same key twice, and the same key on another channel
+        render_events(
+            &mut synth,
+            &[note_on(0, 60, 1.0), note_on(0, 60, 1.0), on_channel(1, 60)],
+            64,
+        );
+        render_events(&mut synth, &[note_off(0, 60)], 64);
+        assert_eq!(held_notes(&synth), [(1, 60)]);
+
+        render_events(&mut synth, &[off_channel(1, 60)], 64);
+        assert!(held_notes(&synth).is_empty());
+
+        // Once the release has finished the voices can be used again
+        let output = render_events(&mut synth, &[], 44100);
+        assert_eq!(active_voices(&synth), 0);
+        assert!(output[output.len() - 64..]
+            .iter()
+            .all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn note_off_with_voice_id_releases_only_that_voice() {
+        let mut synth = headless_synth(44100.0);
+        let on_id = |voice_id| NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: Some(voice_id),
+            channel: 0,
+            note: 60,
+            velocity: 1.0,
+        };
+
+        render_events(&mut synth, &[on_id(1), on_id(2)], 64);
+        render_events(
+            &mut synth,
+            &[NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: Some(2),
+                channel: 0,
+                note: 60,
+                velocity: 0.0,
+            }],
+            64,
+        );
+        let held: Vec<_> = synth
+            .voices
+            .iter()
+            .filter(|voice| voice.is_held())
+            .map(|voice| voice.voice_id)
+            .collect();
+        assert_eq!(held, [Some(1)]);
+    }
+
+    #[test]
+    fn voices_are_reused_after_release() {
+        let mut synth = headless_synth(44100.0);
+        for note in 0..2 * synth.voices.len() as u8 {
+            render_events(
+                &mut synth,
+                &[note_on(0, 40 + note, 1.0), note_off(64, 40 + note)],
+                44100,
+            );
+        }
+
+        // Every note got a voice, so the last one played as well
+        let output = render_events(&mut synth, &[note_on(0, 60, 1.0)], 4410);
+        assert!(output.iter().any(|sample| sample.abs() > 0.1));
+    }
+
+    #[test]
+    fn choke_and_all_sound_off_fade_out() {
+        let fade_samples = (FADE_OUT_TIME * 44100.0).ceil() as usize;
+        let choke = NoteEvent::Choke {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+        };
+        for event in [choke, midi_cc(0, 0, control_change::ALL_SOUND_OFF)] {
+            let mut synth = headless_synth(44100.0);
+            render_events(&mut synth, &[note_on(0, 60, 1.0)], 4410);
+            let output = render_events(&mut synth, &[event], 4410);
+
+            // The voice fades out instead of cutting off, without waiting for the release
+            assert!(output[..fade_samples / 2]
+                .iter()
+                .any(|sample| sample.abs() > 0.01));
+            assert!(output[fade_samples + 1..]
+                .iter()
+                .all(|sample| *sample == 0.0));
+            assert_eq!(active_voices(&synth), 0);
+        }
+    }
+
+    #[test]
+    fn all_notes_off_releases_the_channel() {
+        let mut synth = headless_synth(44100.0);
+        let on_channel = |channel, note| NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 1.0,
+        };
+
+        render_events(
+            &mut synth,
+            &[on_channel(0, 60), on_channel(0, 64), on_channel(1, 67)],
+            64,
+        );
+        let output = render_events(
+            &mut synth,
+            &[midi_cc(0, 0, control_change::ALL_NOTES_OFF)],
+            64,
+        );
+        assert_eq!(held_notes(&synth), [(1, 67)]);
+        // The notes are released normally, so they don't stop right away
+        assert_eq!(active_voices(&synth), 3);
+        assert!(output.iter().any(|sample| sample.abs() > 0.01));
+    }
+
+    #[test]
+    fn reset_fades_out() {
+        let fade_samples = (FADE_OUT_TIME * 44100.0).ceil() as usize;
+        let mut synth = headless_synth(44100.0);
+        render_events(
+            &mut synth,
+            &[note_on(0, 60, 1.0), note_on(0, 64, 1.0)],
+            4410,
+        );
+        synth.reset();
+
+        let output = render_events(&mut synth, &[], 4410);
+        assert!(output[..fade_samples / 2]
+            .iter()
+            .any(|sample| sample.abs() > 0.01));
+        assert!(output[fade_samples + 1..]
+            .iter()
+            .all(|sample| *sample == 0.0));
+        assert_eq!(active_voices(&synth), 0);
+    }
+
+    #[test]
+    fn note_expressions_apply_to_one_voice() {
+        let mut synth = headless_synth(44100.0);
+        let on_id = |voice_id| NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: Some(voice_id),
+            channel: 0,
+            note: 57,
+            velocity: 1.0,
+        };
+        render_events(
+            &mut synth,
+            &[
+                on_id(1),
+                on_id(2),
+                NoteEvent::PolyTuning {
+                    timing: 0,
+                    voice_id: Some(1),
+                    channel: 0,
+                    note: 57,
+                    tuning: 12.0,
+                },
+                NoteEvent::PolyPressure {
+                    timing: 0,
+                    voice_id: Some(2),
+                    channel: 0,
+                    note: 57,
+                    pressure: 0.5,
+                },
+            ],
+            64,
+        );
+
+        let voice = |voice_id| {
+            synth
+                .voices
+                .iter()
+                .find(|voice| voice.voice_id == Some(voice_id))
+                .unwrap()
+        };
+        assert_eq!(voice(1).expression.bend, 12.0);
+        assert_eq!(voice(1).expression.pressure, None);
+        assert_eq!(voice(2).expression.bend, 0.0);
+        // The pressure goes to the mod depth by default, which is at 50%
+        assert_eq!(voice(1).mod_depth[0], 0.5);
+        assert!((voice(2).mod_depth[0] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn note_tuning_changes_the_pitch() {
+        let mut synth = headless_synth(44100.0);
+        render_events(
+            &mut synth,
+            &[
+                note_on(0, 57, 1.0),
+                NoteEvent::PolyTuning {
+                    timing: 0,
+                    voice_id: None,
+                    channel: 0,
+                    note: 57,
+                    tuning: 12.0,
+                },
+            ],
+            64,
+        );
+
+        // A above middle C bent up an octave
+        let output = render_events(&mut synth, &[], 22050);
+        let crossings = output
+            .windows(2)
+            .filter(|window| window[0] < 0.0 && window[1] >= 0.0)
+            .count();
+        assert!(
+            crossings.abs_diff(220) <= 1,
+            "{crossings} periods, expected 220"
+        );
+    }
+
+    #[test]
+    fn mpe_member_channels() {
+        let mut synth = headless_synth_with(AmSynthParams {
+            mpe_enabled: BoolParam::new("MPE", true),
+            ..AmSynthParams::default()
+        });
+        let on_channel = |channel, note| NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 1.0,
+        };
+        // A 48 semitone bend range, so this is 12 semitones
+        let bend = |channel| NoteEvent::MidiPitchBend {
+            timing: 0,
+            channel,
+            value: 0.625,
+        };
+
+        // Controllers send the pitch bend before the note on
+        render_events(
+            &mut synth,
+            &[bend(1), on_channel(1, 60), on_channel(2, 64)],
+            64,
+        );
+        render_events(
+            &mut synth,
+            &[
+                NoteEvent::MidiChannelPressure {
+                    timing: 0,
+                    channel: 2,
+                    pressure: 0.8,
+                },
+                NoteEvent::MidiCC {
+                    timing: 0,
+                    channel: 2,
+                    cc: MPE_SLIDE_CC,
+                    value: 1.0,
+                },
+            ],
+            64,
+        );
+
+        let voice = |channel| {
+            synth
+                .voices
+                .iter()
+                .find(|voice| voice.active && voice.channel == channel)
+                .unwrap()
+        };
+        assert_eq!(voice(1).expression.bend, 12.0);
+        assert_eq!(voice(1).expression.pressure, Some(0.0));
+        assert_eq!(voice(1).expression.slide, 0.5);
+        assert_eq!(voice(2).expression.bend, 0.0);
+        assert_eq!(voice(2).expression.pressure, Some(0.8));
+        assert_eq!(voice(2).expression.slide, 1.0);
+        assert!(synth.is_voice_cc(MPE_SLIDE_CC));
+
+        // The lower zone's master channel bends every note by up to two semitones
+        render_events(
+            &mut synth,
+            &[NoteEvent::MidiPitchBend {
+                timing: 0,
+                channel: 0,
+                value: 0.0,
+            }],
+            64,
+        );
+        assert!(synth
+            .voices
+            .iter()
+            .filter(|voice| voice.active)
+            .all(|voice| voice.zone_bend == -2.0));
+    }
+
+    #[test]
+    fn velocity_curves() {
+        let hard = headless_synth_with(AmSynthParams {
+            velocity_curve: EnumParam::new("Velocity Curve", VelocityCurve::Hard),
+            ..AmSynthParams::default()
+        });
+        let user = headless_synth_with(AmSynthParams {
+            velocity_curve: EnumParam::new("Velocity Curve", VelocityCurve::User),
+            velocity_table: RwLock::new("0 0.5, 1 0.5".parse().unwrap()),
+            ..AmSynthParams::default()
+        });
+
+        for (mut synth, expected) in [(hard, 0.25), (user, 0.5)] {
+            synth.handle_event(note_on(0, 60, 0.5));
+            assert_eq!(synth.voices[0].velocity, expected);
+        }
+    }
+
+    #[test]
+    fn velocity_sensitivity() {
+        let render = |velocity| {
+            let mut synth = headless_synth_with(AmSynthParams {
+                velocity_sensitivity: FloatParam::new(
+                    "Velocity Sensitivity",
+                    0.0,
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                ),
+                ..AmSynthParams::default()
+            });
+            render_events(&mut synth, &[note_on(0, 60, velocity)], 4410)
+        };
+
+        // Without any sensitivity every note plays at full level
+        assert_eq!(render(0.2), render(1.0));
+    }
+
+    #[test]
+    fn velocity_destinations() {
+        let amount = |name, value| {
+            FloatParam::new(
+                name,
+                value,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+        };
+        let mut synth = headless_synth_with(AmSynthParams {
+            velocity_mod_depth: amount("Velocity to Mod Depth", 0.5),
+            velocity_modulator_envelope: amount("Velocity to Modulator Envelope", 1.0),
+            velocity_cutoff: amount("Velocity to Cutoff", 0.5),
+            ..AmSynthParams::default()
+        });
+        let mut reference = headless_synth(44100.0);
+        render_events(&mut synth, &[note_on(0, 60, 0.5)], 64);
+        render_events(&mut reference, &[note_on(0, 60, 0.5)], 64);
+
+        let voice = &synth.voices[0];
+        let reference_voice = &reference.voices[0];
+        let cutoff = &synth.params.global_filter_cutoff;
+        assert!((voice.mod_depth[63] - 0.75).abs() < 1e-6);
+        assert!(
+            (cutoff.preview_normalized(voice.filter_cutoff[63])
+                - cutoff.preview_normalized(1000.0)
+                - 0.25)
+                .abs()
+                < 1e-4
+        );
+
+        // The modulator is only heard through the carrier, so its envelope follows the velocity
+        let gain = |voice: &Voice| voice.operators[1].gain[63];
+        assert!(gain(reference_voice) > 0.0);
+        assert!((gain(voice) / gain(reference_voice) - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn carrier_and_modulator_filters() {
+        let cutoff = |name| {
+            FloatParam::new(
+                name,
+                20.0,
+                FloatRange::Linear {
+                    min: 20.0,
+                    max: 20000.0,
+                },
+            )
+        };
+        let energy = |output: &[f32]| output.iter().map(|sample| sample * sample).sum::<f32>();
+        let events = [note_on(0, 69, 1.0)];
+
+        let mut reference = headless_synth(44100.0);
+        let reference_output = render_events(&mut reference, &events, 4410);
+
+        // The carrier is the only operator that's heard, so filtering it filters the whole output
+        let mut synth = headless_synth_with(AmSynthParams {
+            carrier_filter_enabled: BoolParam::new("Carrier Filter", true),
+            carrier_filter_cutoff: cutoff("Carrier Filter Cutoff"),
+            ..AmSynthParams::default()
+        });
+        let output = render_events(&mut synth, &events, 4410);
+        assert!(energy(&output) < energy(&reference_output) * 0.01);
+
+        // The modulator filter only changes the modulator's output before it reaches the carrier.
+        // The modulator's default frequency is below the lowest cutoff, so it's raised here.
+        let modulator = || {
+            OperatorParams::new(
+                2,
+                1000.0,
+                FloatRange::Linear {
+                    min: 0.1,
+                    max: 1000.0,
+                },
+                false,
+            )
+        };
+        let mut reference = headless_synth_with(AmSynthParams {
+            modulator: modulator(),
+            ..AmSynthParams::default()
+        });
+        let reference_output = render_events(&mut reference, &events, 4410);
+        let mut synth = headless_synth_with(AmSynthParams {
+            modulator: modulator(),
+            modulator_filter_enabled: BoolParam::new("Modulator Filter", true),
+            modulator_filter_cutoff: cutoff("Modulator Filter Cutoff"),
+            ..AmSynthParams::default()
+        });
+        let output = render_events(&mut synth, &events, 4410);
+        let modulator_energy = |synth: &AmSynth| energy(&synth.voices[0].operators[1].output);
+        assert!(modulator_energy(&synth) < modulator_energy(&reference) * 0.01);
+        assert!(energy(&output) > energy(&reference_output) * 0.1);
+        assert_ne!(output, reference_output);
+    }
+
+    #[test]
+    fn mapped_ccs_apply_without_the_editor() {
+        let mut synth = headless_synth(44100.0);
+        synth
+            .params
+            .midi_mappings
+            .write()
+            .unwrap()
+            .insert(String::from("mod_depth"), 7);
+
+        // The editor was never opened, so the CC is applied on the audio thread, and MIDI learn
+        // keeps its value for the host until the editor is opened. The default smoothing time is
+        // 10 milliseconds.
+        render_events(
+            &mut synth,
+            &[NoteEvent::MidiCC {
+                timing: 0,
+                channel: 0,
+                cc: 7,
+                value: 0.25,
+            }],
+            441,
+        );
+        let params = synth.params.clone();
+        assert_eq!(synth.smoothers.next_step(&params.mod_depth, 1), 0.25);
+    }
+
+    #[test]
+    fn arpeggiator_is_sample_accurate() {
+        let mut synth = headless_synth_with(AmSynthParams {
+            arp: ArpParams {
+                enabled: BoolParam::new("Arp", true),
+                ..ArpParams::default()
+            },
+            ..AmSynthParams::default()
+        });
+
+        // At 120 BPM a sixteenth note is 5512.5 samples long, so the note that's played at sample
+        // 100 starts at the second step in the middle of a buffer
+        let num_samples = 11 * 512;
+        let mut output = vec![0.0; num_samples];
+        let mut events = vec![note_on(100, 60, 1.0)];
+        for (buffer_idx, buffer) in output.chunks_mut(512).enumerate() {
+            let transport = TransportPosition {
+                tempo: Some(120.0),
+                playing: true,
+                pos_beats: Some((buffer_idx * 512) as f64 / 22050.0),
+            };
+            let mut buffer_events = std::mem::take(&mut events).into_iter();
+            synth.process_buffer(&mut [buffer], transport, || buffer_events.next());
+        }
+
+        let expected = render_headless(&[note_on(5513, 60, 1.0)], num_samples);
+        assert!(output[..5513].iter().all(|sample| *sample == 0.0));
+        assert!(output[5513..].iter().any(|sample| *sample != 0.0));
+        for (idx, (actual, expected)) in output.iter().zip(&expected).enumerate() {
+            assert!(
+                (actual - expected).abs() < 1e-6,
+                "sample {idx}: {actual} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn sample_rate_independence() {
+        let mut reference_release = None;
+        for sample_rate in [44100.0, 48000.0, 96000.0, 192000.0] {
+            let note_length = (0.5 * sample_rate) as usize;
+            let output = render_headless_at(
+                sample_rate,
+                &[note_on(0, 69, 1.0), note_off(note_length as u32, 69)],
+                sample_rate as usize,
+            );
+
+            // The modulator only changes the carrier's amplitude, so the output crosses zero at the
+            // note's frequency
+            let crossings = output[..note_length]
+                .windows(2)
+                .filter(|window| window[0] < 0.0 && window[1] >= 0.0)
+                .count();
+            assert!(
+                crossings.abs_diff(220) <= 1,
+                "{crossings} periods at {sample_rate} Hz, expected 220"
+            );
+
+            // The output is silent once every envelope has been released
+            let last_sound = output.iter().rposition(|sample| *sample != 0.0).unwrap();
+            let release = (last_sound - note_length) as f32 / sample_rate;
+            let reference_release = *reference_release.get_or_insert(release);
+            assert!(
+                (release - reference_release).abs() < 0.001,
+                "{release} s release at {sample_rate} Hz, expected {reference_release} s"
+            );
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use atomic_float::AtomicF32;
+use nih_plug::prelude::util;
+use std::sync::atomic::Ordering;
+
+/// The time it takes for the peak level to drop by 12 dB after the signal stops.
+const PEAK_DECAY_MS: f32 = 150.0;
+/// The averaging time for the RMS level.
+const RMS_WINDOW_MS: f32 = 300.0;
+
+/// The output levels shown by the editor's meters. This is updated by the audio thread and read
+/// by the GUI thread. The audio thread only computes these while the editor is open.
+pub struct OutputMeter {
+    /// The decaying peak level for the left and right channels, as linear gain.
+    peak: [AtomicF32; 2],
+    /// The running mean of every channel's squared samples. The RMS level is its square root.
+    mean_square: [AtomicF32; 2],
+}
+
+/// The per-sample smoothing weights for an [`OutputMeter`] at a specific sample rate.
+#[derive(Debug, Clone, Copy)]
+pub struct MeterWeights {
+    peak_decay: f32,
+    rms: f32,
+}
+
+impl MeterWeights {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            peak_decay: 0.25f32.powf((sample_rate * PEAK_DECAY_MS / 1000.0).recip()),
+            rms: (-1.0 / (sample_rate * RMS_WINDOW_MS / 1000.0)).exp(),
+        }
+    }
+}
+
+impl OutputMeter {
+    pub fn new() -> Self {
+        Self {
+            peak: Default::default(),
+            mean_square: Default::default(),
+        }
+    }
+
+    /// Update a channel's levels with a block of that channel's output.
+    pub fn update(&self, channel: usize, samples: &[f32], weights: MeterWeights) {
+        let mut peak = self.peak[channel].load(Ordering::Relaxed);
+        let mut mean_square = self.mean_square[channel].load(Ordering::Relaxed);
+        for sample in samples {
+            let amplitude = sample.abs();
+            peak = if amplitude > peak {
+                amplitude
+            } else {
+                peak * weights.peak_decay
+            };
+            mean_square = (mean_square * weights.rms) + (sample * sample * (1.0 - weights.rms));
+        }
+
+        self.peak[channel].store(peak, Ordering::Relaxed);
+        self.mean_square[channel].store(mean_square, Ordering::Relaxed);
+    }
+
+    pub fn peak_dbfs(&self, channel: usize) -> f32 {
+        util::gain_to_db(self.peak[channel].load(Ordering::Relaxed))
+    }
+
+    pub fn rms_dbfs(&self, channel: usize) -> f32 {
+        util::gain_to_db(self.mean_square[channel].load(Ordering::Relaxed).sqrt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn sine_levels() {
+        let sample_rate = 48000.0;
+        let weights = MeterWeights::new(sample_rate);
+        let sine: Vec<f32> = (0..48000 * 3)
+            .map(|n| (2.0 * PI * 1000.0 * n as f32 / sample_rate).sin())
+            .collect();
+
+        let meter = OutputMeter::new();
+        meter.update(0, &sine, weights);
+        assert!(meter.peak_dbfs(0).abs() < 0.1, "{}", meter.peak_dbfs(0));
+        assert!(
+            (meter.rms_dbfs(0) + 3.01).abs() < 0.1,
+            "{}",
+            meter.rms_dbfs(0)
+        );
+        assert_eq!(meter.peak_dbfs(1), util::MINUS_INFINITY_DB);
+
+        // After the signal stops the peak level should drop by 12 dB in `PEAK_DECAY_MS`
+        let silence = vec![0.0; (sample_rate * PEAK_DECAY_MS / 1000.0) as usize];
+        meter.update(0, &silence, weights);
+        assert!(
+            (meter.peak_dbfs(0) + 12.0).abs() < 0.1,
+            "{}",
+            meter.peak_dbfs(0)
+        );
+        assert!(meter.rms_dbfs(0) < -3.01);
+    }
+}
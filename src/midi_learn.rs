@@ -0,0 +1,227 @@
+use crossbeam::queue::ArrayQueue;
+use nih_plug::prelude::{FloatParam, GuiContext, Param, ParamPtr, Params};
+use nih_plug_vizia::widgets::MidiLearnHandler;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::params::AmSynthParams;
+
+/// The number of CC messages that can be received between two GUI updates. Messages received while
+/// the queue is full are dropped.
+const QUEUE_CAPACITY: usize = 512;
+
+/// The persisted CC bindings, from parameter IDs to CC numbers. A CC can only be bound to a single
+/// parameter.
+pub type MidiMappings = BTreeMap<String, u8>;
+
+/// Binds MIDI CCs to parameters. The audio thread applies the bound CCs to the continuous
+/// parameters' smoothers right away, so they work without the editor, and passes the relevant
+/// messages to the GUI thread through a lock-free queue. Parameters can only be changed through the
+/// host's [`GuiContext`], so the GUI thread then binds the CC to the parameter that's being learned
+/// and turns the messages into regular parameter changes. Those are recorded by the host, and
+/// they're the only way CCs change discrete parameters. Until the editor has been opened, the
+/// latest value of every CC is kept and applied as soon as the context is available.
+pub struct MidiLearn {
+    params: Arc<AmSynthParams>,
+    /// Every parameter's ID and pointer, sorted by ID so the audio thread can find the bound
+    /// parameters without allocating.
+    param_ptrs: Vec<(String, ParamPtr)>,
+
+    /// The ID of the parameter that the next CC gets bound to.
+    learning: Mutex<Option<String>>,
+    /// Whether `learning` contains a parameter, so the audio thread doesn't need to lock it.
+    is_learning: AtomicBool,
+    /// The CC messages that still need to be applied as `(cc, normalized_value)` pairs.
+    messages: ArrayQueue<(u8, f32)>,
+
+    /// The context used to change parameters. This is set when the editor is opened.
+    gui_context: Mutex<Option<Arc<dyn GuiContext>>>,
+    /// The latest value of every CC received before there was a context to apply it with.
+    deferred: Mutex<BTreeMap<u8, f32>>,
+}
+
+impl MidiLearn {
+    pub fn new(params: Arc<AmSynthParams>) -> Self {
+        let mut param_ptrs: Vec<_> = params
+            .param_map()
+            .into_iter()
+            .map(|(param_id, param_ptr, _)| (param_id, param_ptr))
+            .collect();
+        param_ptrs.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        Self {
+            params,
+            param_ptrs,
+
+            learning: Mutex::new(None),
+            is_learning: AtomicBool::new(false),
+            messages: ArrayQueue::new(QUEUE_CAPACITY),
+
+            gui_context: Mutex::new(None),
+            deferred: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Handle a CC message on the audio thread. `apply` is called with every continuous parameter
+    /// the CC is bound to and the CC's value as a plain value for that parameter. Returns `true`
+    /// if the message was queued and [`apply_pending()`][Self::apply_pending()] should be called
+    /// on the GUI thread. This never blocks or allocates. If the GUI thread is changing the
+    /// bindings at the same time then the message is dropped.
+    pub fn process_cc(&self, cc: u8, value: f32, mut apply: impl FnMut(&FloatParam, f32)) -> bool {
+        let mut is_bound = false;
+        if let Ok(mappings) = self.params.midi_mappings.try_read() {
+            for (param_id, _) in mappings.iter().filter(|(_, mapped_cc)| **mapped_cc == cc) {
+                is_bound = true;
+                if let Some(ParamPtr::FloatParam(param)) = self.param_ptr(param_id) {
+                    // The pointers point into `self.params`
+                    let param = unsafe { &*param };
+                    apply(param, param.preview_plain(value));
+                }
+            }
+        }
+
+        (is_bound || self.is_learning.load(Ordering::Relaxed))
+            && self.messages.push((cc, value)).is_ok()
+    }
+
+    /// Bind the parameter being learned to the first queued CC, and apply the queued CC messages to
+    /// their parameters. Without a context, the messages are kept until
+    /// [`set_gui_context()`][Self::set_gui_context()] is called. This is called on the GUI thread.
+    pub fn apply_pending(&self) {
+        let gui_context = self.gui_context.lock().unwrap().clone();
+
+        while let Some((cc, value)) = self.messages.pop() {
+            if self.is_learning.swap(false, Ordering::Relaxed) {
+                if let Some(param_id) = self.learning.lock().unwrap().take() {
+                    let mut mappings = self.params.midi_mappings.write().unwrap();
+                    mappings.retain(|_, mapped_cc| *mapped_cc != cc);
+                    mappings.insert(param_id, cc);
+                }
+            }
+
+            match &gui_context {
+                Some(gui_context) => self.set_mapped_params(gui_context.as_ref(), cc, value),
+                None => {
+                    let mappings = self.params.midi_mappings.read().unwrap();
+                    if mappings.values().any(|mapped_cc| *mapped_cc == cc) {
+                        self.deferred.lock().unwrap().insert(cc, value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Store the context used to change parameters, and apply the CCs received before it was
+    /// available. Called on the GUI thread when the editor is opened.
+    pub fn set_gui_context(&self, gui_context: Arc<dyn GuiContext>) {
+        let deferred = std::mem::take(&mut *self.deferred.lock().unwrap());
+        for (cc, value) in deferred {
+            self.set_mapped_params(gui_context.as_ref(), cc, value);
+        }
+        *self.gui_context.lock().unwrap() = Some(gui_context);
+    }
+
+    /// Change every parameter bound to `cc` to the CC's normalized value through the host.
+    fn set_mapped_params(&self, gui_context: &dyn GuiContext, cc: u8, value: f32) {
+        let mappings = self.params.midi_mappings.read().unwrap();
+        for (param_id, param_ptr) in &self.param_ptrs {
+            if mappings.get(param_id) == Some(&cc) {
+                unsafe {
+                    gui_context.raw_begin_set_parameter(*param_ptr);
+                    gui_context.raw_set_parameter_normalized(*param_ptr, value);
+                    gui_context.raw_end_set_parameter(*param_ptr);
+                }
+            }
+        }
+    }
+
+    /// The ID of a parameter from the editor.
+    fn param_id(&self, param: ParamPtr) -> Option<String> {
+        self.param_ptrs
+            .iter()
+            .find(|(_, param_ptr)| *param_ptr == param)
+            .map(|(param_id, _)| param_id.clone())
+    }
+
+    /// The parameter with an ID.
+    fn param_ptr(&self, param_id: &str) -> Option<ParamPtr> {
+        let index = self
+            .param_ptrs
+            .binary_search_by(|(id, _)| id.as_str().cmp(param_id))
+            .ok()?;
+        Some(self.param_ptrs[index].1)
+    }
+}
+
+impl MidiLearnHandler for MidiLearn {
+    fn learn(&self, param: ParamPtr) {
+        *self.learning.lock().unwrap() = self.param_id(param);
+        self.is_learning.store(true, Ordering::Relaxed);
+    }
+
+    fn forget(&self, param: ParamPtr) {
+        let Some(param_id) = self.param_id(param) else {
+            return;
+        };
+
+        let mut learning = self.learning.lock().unwrap();
+        if learning.as_ref() == Some(&param_id) {
+            *learning = None;
+            self.is_learning.store(false, Ordering::Relaxed);
+        }
+        self.params.midi_mappings.write().unwrap().remove(&param_id);
+    }
+
+    fn mapped_cc(&self, param: ParamPtr) -> Option<u8> {
+        let param_id = self.param_id(param)?;
+        self.params
+            .midi_mappings
+            .read()
+            .unwrap()
+            .get(&param_id)
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn learns_first_cc() {
+        let params = Arc::new(AmSynthParams::default());
+        let midi_learn = MidiLearn::new(params.clone());
+        let mod_depth = params.mod_depth.as_ptr();
+        let ignore = |_: &FloatParam, _: f32| ();
+
+        // Unmapped CCs are ignored
+        assert!(!midi_learn.process_cc(7, 0.5, ignore));
+
+        midi_learn.learn(mod_depth);
+        assert!(midi_learn.process_cc(7, 0.5, ignore));
+        assert!(midi_learn.process_cc(10, 0.5, ignore));
+        midi_learn.apply_pending();
+        assert_eq!(midi_learn.mapped_cc(mod_depth), Some(7));
+
+        // Only the bound CC is still passed on, and it's applied to the parameter right away
+        let mut applied = None;
+        assert!(midi_learn.process_cc(7, 0.25, |param, value| applied =
+            Some((param.name().to_owned(), value))));
+        assert_eq!(applied, Some((String::from("Mod Depth"), 0.25)));
+        assert!(!midi_learn.process_cc(10, 0.25, ignore));
+        midi_learn.apply_pending();
+
+        // The editor was never opened, so only the CC's latest value is kept for the host
+        assert!(midi_learn.process_cc(7, 0.75, ignore));
+        midi_learn.apply_pending();
+        assert_eq!(
+            *midi_learn.deferred.lock().unwrap(),
+            BTreeMap::from([(7, 0.75)])
+        );
+
+        midi_learn.forget(mod_depth);
+        assert_eq!(midi_learn.mapped_cc(mod_depth), None);
+        assert!(!midi_learn.process_cc(7, 0.5, ignore));
+    }
+}
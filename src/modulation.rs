@@ -0,0 +1,600 @@
+use atomic_float::AtomicF32;
+use nih_plug::prelude::{
+    formatters, Enum, EnumParam, FloatParam, FloatRange, Param, ParamPtr, Params,
+};
+use nih_plug_vizia::widgets::ModulationProvider;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::algorithm::MAX_OPERATORS;
+use crate::block::BLOCK_SIZE;
+use crate::params::{linear_smoothing, logarithmic_smoothing, AmSynthParams, OPERATOR_ID_PREFIXES};
+use crate::smoothing::Smoothers;
+
+/// The number of LFOs shared by all voices.
+pub const NUM_LFOS: usize = 2;
+/// The number of routes in the mod matrix.
+pub const MOD_SLOTS: usize = 8;
+
+/// The number of parameters that can be modulated. `mod_depth` and the global filter's cutoff come
+/// first, followed by every operator's parameters.
+const NUM_DESTINATIONS: usize = 2 + MAX_OPERATORS * OperatorDestination::ALL.len();
+
+/// The persisted mod matrix routes, indexed by slot. Every slot has an amount parameter.
+pub type ModRoutes = [Option<ModRoute>; MOD_SLOTS];
+
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+}
+
+#[derive(Params)]
+pub struct LfoParams {
+    #[id = "lfo_rate"]
+    pub rate: FloatParam,
+    #[id = "lfo_shape"]
+    pub shape: EnumParam<LfoShape>,
+}
+
+/// The amount for one of the mod matrix's routes. The route itself is stored in
+/// [`AmSynthParams::mod_routes`].
+#[derive(Params)]
+pub struct ModSlotParams {
+    /// The modulation's depth in normalized parameter values. Negative amounts invert the source.
+    #[id = "mod_amount"]
+    pub amount: FloatParam,
+}
+
+/// A signal that can be routed to parameters through the mod matrix. The LFOs are bipolar, the
+/// other sources are unipolar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModSource {
+    Lfo1,
+    Lfo2,
+    /// The voice's global envelope.
+    Envelope,
+    Velocity,
+    /// The voice's envelope that's only used for modulation.
+    ModEnvelope,
+}
+
+/// A mod matrix route from a source to a parameter. The parameter is stored by its ID so the routes
+/// can be saved with the plugin's state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModRoute {
+    pub source: ModSource,
+    pub destination: String,
+}
+
+/// A parameter that can be modulated by the mod matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    ModDepth,
+    GlobalFilterCutoff,
+    /// One of an operator's parameters, for the operator with this index.
+    Operator(usize, OperatorDestination),
+}
+
+/// The parameters every operator has that can be modulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperatorDestination {
+    Ratio,
+    Freq,
+    Level,
+    Feedback,
+    WavetablePosition,
+}
+
+/// Every destination's offset in normalized parameter values for a single voice.
+#[derive(Debug, Clone, Copy)]
+pub struct ModOffsets([f32; NUM_DESTINATIONS]);
+
+/// The mod matrix's state on the audio thread. The LFOs are free running and shared by every
+/// voice.
+pub struct Modulator {
+    sample_rate: f32,
+    lfo_phases: [f32; NUM_LFOS],
+    /// The LFOs' output for every sample in the current block.
+    lfo_values: [[f32; BLOCK_SIZE]; NUM_LFOS],
+    /// The slots' smoothed amounts for every sample in the current block.
+    amounts: [[f32; BLOCK_SIZE]; MOD_SLOTS],
+    /// The routes that resolved to a destination at the start of the block, indexed by slot.
+    routes: [Option<(ModSource, Destination)>; MOD_SLOTS],
+}
+
+/// The editor's view of the mod matrix. The audio thread publishes the range every destination
+/// covers across the active voices while the editor is open, and the editor routes sources to
+/// parameters by changing the persisted routes.
+pub struct ModMatrix {
+    params: Arc<AmSynthParams>,
+    /// The parameters that can be modulated, so the editor's parameter widgets can be mapped to
+    /// destinations without going through the parameter IDs.
+    destinations: Vec<(ParamPtr, Destination)>,
+    /// The lowest and highest offset for every destination across the active voices. These always
+    /// include zero, so the editor shows the range between the parameter's value and the furthest
+    /// modulated values.
+    ranges: [(AtomicF32, AtomicF32); NUM_DESTINATIONS],
+}
+
+impl LfoParams {
+    pub fn new(number: usize) -> Self {
+        Self {
+            rate: FloatParam::new(
+                format!("LFO {number} Rate"),
+                2.0,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2))
+            .with_smoother(logarithmic_smoothing()),
+            shape: EnumParam::new(format!("LFO {number} Shape"), LfoShape::Sine),
+        }
+    }
+}
+
+impl ModSlotParams {
+    pub fn new(number: usize) -> Self {
+        Self {
+            amount: FloatParam::new(
+                format!("Mod {number} Amount"),
+                0.5,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_smoother(linear_smoothing()),
+        }
+    }
+}
+
+impl LfoShape {
+    /// The LFO's bipolar output at a phase in `[0, 1)`.
+    fn value(self, phase: f32) -> f32 {
+        match self {
+            LfoShape::Sine => (phase * TAU).sin(),
+            LfoShape::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            LfoShape::Saw => 2.0 * phase - 1.0,
+            LfoShape::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+        }
+    }
+}
+
+impl ModSource {
+    pub const ALL: [ModSource; 5] = [
+        ModSource::Lfo1,
+        ModSource::Lfo2,
+        ModSource::Envelope,
+        ModSource::Velocity,
+        ModSource::ModEnvelope,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ModSource::Lfo1 => "LFO 1",
+            ModSource::Lfo2 => "LFO 2",
+            ModSource::Envelope => "Envelope",
+            ModSource::Velocity => "Velocity",
+            ModSource::ModEnvelope => "Mod Env",
+        }
+    }
+}
+
+impl Destination {
+    /// Find the destination for a parameter ID. Returns `None` if the parameter cannot be
+    /// modulated.
+    pub fn from_param_id(param_id: &str) -> Option<Self> {
+        match param_id {
+            "mod_depth" => return Some(Destination::ModDepth),
+            "global_filter_cutoff" => return Some(Destination::GlobalFilterCutoff),
+            _ => (),
+        }
+
+        let (prefix, id) = param_id.split_once('_')?;
+        let operator = OPERATOR_ID_PREFIXES
+            .iter()
+            .position(|operator_prefix| *operator_prefix == prefix)?;
+        let parameter = OperatorDestination::ALL
+            .into_iter()
+            .find(|parameter| parameter.id() == id)?;
+
+        Some(Destination::Operator(operator, parameter))
+    }
+
+    pub fn param_id(self) -> String {
+        match self {
+            Destination::ModDepth => String::from("mod_depth"),
+            Destination::GlobalFilterCutoff => String::from("global_filter_cutoff"),
+            Destination::Operator(operator, parameter) => {
+                format!("{}_{}", OPERATOR_ID_PREFIXES[operator], parameter.id())
+            }
+        }
+    }
+
+    pub fn param(self, params: &AmSynthParams) -> &FloatParam {
+        match self {
+            Destination::ModDepth => &params.mod_depth,
+            Destination::GlobalFilterCutoff => &params.global_filter_cutoff,
+            Destination::Operator(operator, parameter) => {
+                let operator = params.operator(operator);
+                match parameter {
+                    OperatorDestination::Ratio => &operator.ratio,
+                    OperatorDestination::Freq => &operator.freq,
+                    OperatorDestination::Level => &operator.level,
+                    OperatorDestination::Feedback => &operator.feedback,
+                    OperatorDestination::WavetablePosition => &operator.wavetable_position,
+                }
+            }
+        }
+    }
+
+    /// The destination's index in a [`ModOffsets`].
+    fn index(self) -> usize {
+        match self {
+            Destination::ModDepth => 0,
+            Destination::GlobalFilterCutoff => 1,
+            Destination::Operator(operator, parameter) => {
+                2 + operator * OperatorDestination::ALL.len() + parameter as usize
+            }
+        }
+    }
+}
+
+impl OperatorDestination {
+    const ALL: [OperatorDestination; 5] = [
+        OperatorDestination::Ratio,
+        OperatorDestination::Freq,
+        OperatorDestination::Level,
+        OperatorDestination::Feedback,
+        OperatorDestination::WavetablePosition,
+    ];
+
+    /// The parameter's ID within the operator's nested parameters.
+    fn id(self) -> &'static str {
+        match self {
+            OperatorDestination::Ratio => "ratio",
+            OperatorDestination::Freq => "freq",
+            OperatorDestination::Level => "level",
+            OperatorDestination::Feedback => "feedback",
+            OperatorDestination::WavetablePosition => "wavetable_position",
+        }
+    }
+}
+
+impl Default for ModOffsets {
+    fn default() -> Self {
+        Self([0.0; NUM_DESTINATIONS])
+    }
+}
+
+impl ModOffsets {
+    /// The destination parameter's value after the offset has been added to the normalized
+    /// version of `value`, the parameter's current smoothed value.
+    pub fn apply(&self, destination: Destination, param: &FloatParam, value: f32) -> f32 {
+        let offset = self.0[destination.index()];
+        if offset == 0.0 {
+            value
+        } else {
+            param.preview_plain((param.preview_normalized(value) + offset).clamp(0.0, 1.0))
+        }
+    }
+
+    /// Add an offset in normalized parameter values to a destination, on top of the mod matrix's
+    /// offsets.
+    pub fn add(&mut self, destination: Destination, offset: f32) {
+        self.0[destination.index()] += offset;
+    }
+}
+
+impl Modulator {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            lfo_phases: [0.0; NUM_LFOS],
+            lfo_values: [[0.0; BLOCK_SIZE]; NUM_LFOS],
+            amounts: [[0.0; BLOCK_SIZE]; MOD_SLOTS],
+            routes: [None; MOD_SLOTS],
+        }
+    }
+
+    /// Change the sample rate without resetting the LFOs' phases.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Resolve the persisted routes for the next block. If the editor is changing the routes at
+    /// the same time then the previous block's routes are kept.
+    pub fn update_routes(&mut self, params: &AmSynthParams) {
+        if let Ok(routes) = params.mod_routes.try_read() {
+            for (resolved, route) in self.routes.iter_mut().zip(routes.iter()) {
+                *resolved = route.as_ref().and_then(|route| {
+                    Some((
+                        route.source,
+                        Destination::from_param_id(&route.destination)?,
+                    ))
+                });
+            }
+        }
+    }
+
+    /// Whether any routes are active. The voices don't need to compute their offsets otherwise.
+    pub fn is_active(&self) -> bool {
+        self.routes.iter().any(|route| route.is_some())
+    }
+
+    /// Render the next `len` samples of the LFOs and the slots' amounts. This needs to be called
+    /// exactly once per block, as the smoothers are shared by every voice.
+    pub fn advance(&mut self, params: &AmSynthParams, smoothers: &mut Smoothers, len: usize) {
+        let mut rate = [0.0; BLOCK_SIZE];
+        for (lfo, lfo_params) in params.lfos.iter().enumerate() {
+            let shape = lfo_params.shape.value();
+            smoothers.next_block(&lfo_params.rate, &mut rate, len);
+            for (value, rate) in self.lfo_values[lfo].iter_mut().zip(rate).take(len) {
+                *value = shape.value(self.lfo_phases[lfo]);
+
+                let phase = self.lfo_phases[lfo] + rate / self.sample_rate;
+                self.lfo_phases[lfo] = phase - phase.floor();
+            }
+        }
+
+        for (amounts, slot) in self.amounts.iter_mut().zip(params.mod_slots.iter()) {
+            smoothers.next_block(&slot.amount, amounts, len);
+        }
+    }
+
+    /// Compute a voice's offsets for a sample in the current block from the values of the voice's
+    /// global and mod envelopes and its velocity.
+    pub fn offsets(
+        &self,
+        sample: usize,
+        envelope: f32,
+        mod_envelope: f32,
+        velocity: f32,
+    ) -> ModOffsets {
+        let mut offsets = ModOffsets::default();
+        for (slot, route) in self.routes.iter().enumerate() {
+            let Some((source, destination)) = route else {
+                continue;
+            };
+
+            let value = match source {
+                ModSource::Lfo1 => self.lfo_values[0][sample],
+                ModSource::Lfo2 => self.lfo_values[1][sample],
+                ModSource::Envelope => envelope,
+                ModSource::Velocity => velocity,
+                ModSource::ModEnvelope => mod_envelope,
+            };
+            offsets.0[destination.index()] += value * self.amounts[slot][sample];
+        }
+
+        offsets
+    }
+}
+
+impl ModMatrix {
+    pub fn new(params: Arc<AmSynthParams>) -> Self {
+        let destinations = params
+            .param_map()
+            .into_iter()
+            .filter_map(|(param_id, param_ptr, _)| {
+                Some((param_ptr, Destination::from_param_id(&param_id)?))
+            })
+            .collect();
+
+        Self {
+            params,
+            destinations,
+            ranges: Default::default(),
+        }
+    }
+
+    /// Publish the ranges covered by the active voices' offsets. Called by the audio thread at the
+    /// end of a block while the editor is open.
+    pub fn publish<'a>(&self, voices: impl Iterator<Item = &'a ModOffsets> + Clone) {
+        for (destination, (min, max)) in self.ranges.iter().enumerate() {
+            let offsets = voices.clone().map(|offsets| offsets.0[destination]);
+            min.store(offsets.clone().fold(0.0, f32::min), Ordering::Relaxed);
+            max.store(offsets.fold(0.0, f32::max), Ordering::Relaxed);
+        }
+    }
+
+    /// Route a source to a destination in the first free slot. Returns `false` if every slot is in
+    /// use. Routing a source to a destination it's already routed to does nothing.
+    pub fn assign(&self, source: ModSource, destination: Destination) -> bool {
+        let route = ModRoute {
+            source,
+            destination: destination.param_id(),
+        };
+
+        let mut routes = self.params.mod_routes.write().unwrap();
+        if routes.contains(&Some(route.clone())) {
+            return true;
+        }
+        match routes.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(route);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&self, slot: usize) {
+        self.params.mod_routes.write().unwrap()[slot] = None;
+    }
+
+    /// The occupied slots with a description of their routes, for the editor's mod matrix.
+    pub fn route_names(&self) -> Vec<(usize, String)> {
+        self.params
+            .mod_routes
+            .read()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, route)| {
+                let route = route.as_ref()?;
+                let destination = Destination::from_param_id(&route.destination)
+                    .map_or(route.destination.as_str(), |destination| {
+                        destination.param(&self.params).name()
+                    });
+
+                Some((slot, format!("{} to {}", route.source.name(), destination)))
+            })
+            .collect()
+    }
+
+    fn destination(&self, param: ParamPtr) -> Option<Destination> {
+        self.destinations
+            .iter()
+            .find(|(param_ptr, _)| *param_ptr == param)
+            .map(|(_, destination)| *destination)
+    }
+}
+
+impl ModulationProvider for ModMatrix {
+    fn modulation_range(&self, param: ParamPtr) -> Option<(f32, f32)> {
+        let destination = self.destination(param)?;
+        let param_id = destination.param_id();
+        let is_routed = self
+            .params
+            .mod_routes
+            .read()
+            .unwrap()
+            .iter()
+            .flatten()
+            .any(|route| route.destination == param_id);
+        if !is_routed {
+            return None;
+        }
+
+        let (min, max) = &self.ranges[destination.index()];
+        let value = unsafe { param.modulated_normalized_value() };
+        Some((
+            (value + min.load(Ordering::Relaxed)).clamp(0.0, 1.0),
+            (value + max.load(Ordering::Relaxed)).clamp(0.0, 1.0),
+        ))
+    }
+
+    fn sources(&self) -> Vec<String> {
+        ModSource::ALL
+            .iter()
+            .map(|source| source.name().to_owned())
+            .collect()
+    }
+
+    fn assign(&self, source: usize, param: ParamPtr) -> bool {
+        match (ModSource::ALL.get(source), self.destination(param)) {
+            (Some(source), Some(destination)) => ModMatrix::assign(self, *source, destination),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn destination_ids() {
+        let params = AmSynthParams::default();
+        let param_ids: Vec<String> = params
+            .param_map()
+            .into_iter()
+            .map(|(param_id, _, _)| param_id)
+            .collect();
+
+        let destinations: Vec<Destination> = param_ids
+            .iter()
+            .filter_map(|param_id| Destination::from_param_id(param_id))
+            .collect();
+        assert_eq!(destinations.len(), NUM_DESTINATIONS);
+        for destination in destinations {
+            assert!(param_ids.contains(&destination.param_id()));
+            assert_eq!(
+                Destination::from_param_id(&destination.param_id()),
+                Some(destination)
+            );
+        }
+
+        assert_eq!(Destination::from_param_id("modulator_filter_cutoff"), None);
+        assert_eq!(
+            Destination::from_param_id("global_filter_cutoff"),
+            Some(Destination::GlobalFilterCutoff)
+        );
+        assert_eq!(
+            Destination::from_param_id("modulator_level"),
+            Some(Destination::Operator(1, OperatorDestination::Level))
+        );
+    }
+
+    #[test]
+    fn routes_and_ranges() {
+        let params = Arc::new(AmSynthParams::default());
+        let mod_matrix = ModMatrix::new(params.clone());
+        let level = params.carrier.level.as_ptr();
+
+        // Unrouted parameters and parameters that can't be modulated have no range
+        assert_eq!(mod_matrix.modulation_range(level), None);
+        assert!(!ModulationProvider::assign(
+            &mod_matrix,
+            0,
+            params.algorithm.as_ptr()
+        ));
+
+        assert!(ModulationProvider::assign(&mod_matrix, 3, level));
+        assert!(ModulationProvider::assign(&mod_matrix, 3, level));
+        assert_eq!(
+            mod_matrix.route_names(),
+            vec![(0, String::from("Velocity to Op 1 Level"))]
+        );
+
+        let mut smoothers = Smoothers::new(&params, 44100.0);
+        let mut modulator = Modulator::new(44100.0);
+        modulator.update_routes(&params);
+        modulator.advance(&params, &mut smoothers, 1);
+        let offsets = [
+            modulator.offsets(0, 0.0, 0.0, 1.0),
+            modulator.offsets(0, 0.0, 0.0, 0.5),
+        ];
+        assert_eq!(
+            offsets[0].apply(
+                Destination::Operator(0, OperatorDestination::Level),
+                &params.carrier.level,
+                params.carrier.level.value()
+            ),
+            1.0
+        );
+        mod_matrix.publish(offsets.iter());
+
+        // The level defaults to its maximum, so the offsets are clamped
+        assert_eq!(mod_matrix.modulation_range(level), Some((1.0, 1.0)));
+        let (min, max) =
+            &mod_matrix.ranges[Destination::Operator(0, OperatorDestination::Level).index()];
+        assert_eq!(
+            (min.load(Ordering::Relaxed), max.load(Ordering::Relaxed)),
+            (0.0, 0.5)
+        );
+
+        mod_matrix.clear(0);
+        assert!(mod_matrix.route_names().is_empty());
+        assert_eq!(mod_matrix.modulation_range(level), None);
+    }
+}
@@ -1,51 +1,252 @@
 use nih_plug::prelude::*;
-use std::sync::Arc;
+use nih_plug_vizia::ViziaState;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
 
+use crate::algorithm::{Algorithm, MAX_OPERATORS};
+use crate::arpeggiator::ArpParams;
+use crate::envelope::{Breakpoints, EnvelopeCurves, EnvelopeMode};
+use crate::midi_learn::MidiMappings;
+use crate::modulation::{LfoParams, ModRoutes, ModSlotParams, MOD_SLOTS, NUM_LFOS};
+use crate::synth::wavetable::WavetableSource;
+use crate::velocity::{VelocityCurve, VelocityTable};
+
+/// The ID prefixes of the operators' nested parameters, indexed by operator number. These need to
+/// match the `#[nested]` attributes in [`AmSynthParams`].
+pub const OPERATOR_ID_PREFIXES: [&str; MAX_OPERATORS] =
+    ["carrier", "modulator", "op3", "op4", "op5", "op6"];
+
+/// The smoothing time in milliseconds that every smoothed parameter starts out with.
+const DEFAULT_SMOOTHING_TIME: f32 = 10.0;
+
+/// The synth engine used to generate an operator's signal.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OscillatorEngine {
+    Sine,
+    Wavetable,
+    Sample,
+}
+
+/// How the sample playback engine plays its sample.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleLoopMode {
+    /// Play the sample once.
+    #[name = "One-Shot"]
+    OneShot,
+    /// Loop between the loop start and loop end.
+    Loop,
+}
+
+/// The MPE zone the plugin responds to. The zone's master channel carries the messages for every
+/// note, and every other channel is a member channel with a single note and its own pitch bend,
+/// pressure, and slide.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpeZone {
+    /// The master channel is channel 1.
+    Lower,
+    /// The master channel is channel 16.
+    Upper,
+}
+
+/// What a note's pressure modulates, from MPE member channels, polyphonic aftertouch, or CLAP note
+/// expressions.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PressureTarget {
+    #[name = "Mod Depth"]
+    ModDepth,
+    Amplitude,
+}
+
+/// One of a voice's envelopes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeTarget {
+    /// The envelope that's applied to the voice's output.
+    Global,
+    /// The envelope that's only used as a modulation source.
+    Mod,
+    /// The envelope for the operator with this index.
+    Operator(usize),
+}
+
+/// The parameters of one of a voice's envelopes, see [`AmSynthParams::envelope()`]. These are
+/// separate fields in the parameter structs so the parameters keep their original IDs.
+pub struct EnvelopeParams<'a> {
+    pub mode: &'a EnumParam<EnvelopeMode>,
+    pub delay: &'a FloatParam,
+    pub attack: &'a FloatParam,
+    pub hold: &'a FloatParam,
+    pub decay: &'a FloatParam,
+    pub sustain: &'a FloatParam,
+    pub release: &'a FloatParam,
+    pub attack_curve: &'a FloatParam,
+    pub decay_curve: &'a FloatParam,
+    pub release_curve: &'a FloatParam,
+}
+
+/// The parameters for a single operator. These are nested in [`AmSynthParams`] once per operator.
 #[derive(Params)]
-pub struct AmSynthParams {
-    #[id = "carrier_freq"]
-    pub carrier_freq: FloatParam,
+pub struct OperatorParams {
+    #[id = "engine"]
+    pub engine: EnumParam<OscillatorEngine>,
+    #[id = "wavetable_position"]
+    pub wavetable_position: FloatParam,
+    #[persist = "wavetable"]
+    pub wavetable: RwLock<WavetableSource>,
 
-    #[id = "modulator_freq"]
-    pub modulator_freq: FloatParam,
+    /// Whether the operator follows the played note. Keyboard tracking operators play the note's
+    /// frequency times the ratio, and other operators play the fixed frequency.
+    #[id = "keyboard"]
+    pub keyboard: BoolParam,
+    #[id = "ratio"]
+    pub ratio: FloatParam,
+    #[id = "freq"]
+    pub freq: FloatParam,
+    /// The operator's output level when it's heard, or its modulation amount when it modulates
+    /// another operator.
+    #[id = "level"]
+    pub level: FloatParam,
+    /// How much of the operator's output is fed back into its own phase, or into its amplitude
+    /// for the sample engine.
+    #[id = "feedback"]
+    pub feedback: FloatParam,
+
+    #[id = "envelope_mode"]
+    pub envelope_mode: EnumParam<EnvelopeMode>,
+    #[id = "delay"]
+    pub delay: FloatParam,
+    #[id = "attack"]
+    pub attack: FloatParam,
+    #[id = "hold"]
+    pub hold: FloatParam,
+    #[id = "decay"]
+    pub decay: FloatParam,
+    #[id = "sustain"]
+    pub sustain: FloatParam,
+    #[id = "release"]
+    pub release: FloatParam,
+    #[id = "attack_curve"]
+    pub attack_curve: FloatParam,
+    #[id = "decay_curve"]
+    pub decay_curve: FloatParam,
+    #[id = "release_curve"]
+    pub release_curve: FloatParam,
+    /// The envelope's shape in the multi-stage mode.
+    #[persist = "breakpoints"]
+    pub breakpoints: RwLock<Breakpoints>,
+}
+
+#[derive(Params)]
+pub struct AmSynthParams {
+    /// The editor state, saved together with the parameter state so the custom scaling can be
+    /// restored.
+    #[persist = "editor-state"]
+    pub editor_state: Arc<ViziaState>,
+    /// The MIDI CCs bound to parameters through MIDI learn.
+    #[persist = "midi-mappings"]
+    pub midi_mappings: RwLock<MidiMappings>,
+    /// The mod matrix's routes. Their amounts are the `mod_slots` parameters.
+    #[persist = "mod-routes"]
+    pub mod_routes: RwLock<ModRoutes>,
 
     #[id = "mod_depth"]
     pub mod_depth: FloatParam,
 
-    // Carrier Envelope
-    #[id = "carrier_attack"]
-    pub carrier_attack: FloatParam,
-    #[id = "carrier_decay"]
-    pub carrier_decay: FloatParam,
-    #[id = "carrier_sustain"]
-    pub carrier_sustain: FloatParam,
-    #[id = "carrier_release"]
-    pub carrier_release: FloatParam,
-
-    // Modulator Envelope
-    #[id = "modulator_attack"]
-    pub modulator_attack: FloatParam,
-    #[id = "modulator_decay"]
-    pub modulator_decay: FloatParam,
-    #[id = "modulator_sustain"]
-    pub modulator_sustain: FloatParam,
-    #[id = "modulator_release"]
-    pub modulator_release: FloatParam,
+    // Operators
+    #[id = "algorithm"]
+    pub algorithm: EnumParam<Algorithm>,
+    #[id = "operator_count"]
+    pub operator_count: IntParam,
+
+    /// The first two operators keep the carrier's and the modulator's parameter IDs.
+    #[nested(id_prefix = "carrier", group = "Operator 1")]
+    pub carrier: OperatorParams,
+    #[nested(id_prefix = "modulator", group = "Operator 2")]
+    pub modulator: OperatorParams,
+    #[nested(id_prefix = "op3", group = "Operator 3")]
+    pub op3: OperatorParams,
+    #[nested(id_prefix = "op4", group = "Operator 4")]
+    pub op4: OperatorParams,
+    #[nested(id_prefix = "op5", group = "Operator 5")]
+    pub op5: OperatorParams,
+    #[nested(id_prefix = "op6", group = "Operator 6")]
+    pub op6: OperatorParams,
+
+    // Sample Engine
+    /// The path to the sample used by the sample playback engine, if one has been loaded.
+    #[persist = "sample"]
+    pub sample: RwLock<Option<PathBuf>>,
+    #[id = "sample_root_key"]
+    pub sample_root_key: IntParam,
+    #[id = "sample_loop_mode"]
+    pub sample_loop_mode: EnumParam<SampleLoopMode>,
+    #[id = "sample_loop_start"]
+    pub sample_loop_start: FloatParam,
+    #[id = "sample_loop_end"]
+    pub sample_loop_end: FloatParam,
+    #[id = "sample_crossfade"]
+    pub sample_crossfade: FloatParam,
 
     // Global Envelope
+    #[id = "global_envelope_mode"]
+    pub global_envelope_mode: EnumParam<EnvelopeMode>,
+    #[id = "global_delay"]
+    pub global_delay: FloatParam,
     #[id = "global_attack"]
     pub global_attack: FloatParam,
+    #[id = "global_hold"]
+    pub global_hold: FloatParam,
     #[id = "global_decay"]
     pub global_decay: FloatParam,
     #[id = "global_sustain"]
     pub global_sustain: FloatParam,
     #[id = "global_release"]
     pub global_release: FloatParam,
+    #[id = "global_attack_curve"]
+    pub global_attack_curve: FloatParam,
+    #[id = "global_decay_curve"]
+    pub global_decay_curve: FloatParam,
+    #[id = "global_release_curve"]
+    pub global_release_curve: FloatParam,
+    #[persist = "global-breakpoints"]
+    pub global_breakpoints: RwLock<Breakpoints>,
+
+    // Mod Envelope
+    #[id = "mod_env_mode"]
+    pub mod_env_mode: EnumParam<EnvelopeMode>,
+    #[id = "mod_env_delay"]
+    pub mod_env_delay: FloatParam,
+    #[id = "mod_env_attack"]
+    pub mod_env_attack: FloatParam,
+    #[id = "mod_env_hold"]
+    pub mod_env_hold: FloatParam,
+    #[id = "mod_env_decay"]
+    pub mod_env_decay: FloatParam,
+    #[id = "mod_env_sustain"]
+    pub mod_env_sustain: FloatParam,
+    #[id = "mod_env_release"]
+    pub mod_env_release: FloatParam,
+    #[id = "mod_env_attack_curve"]
+    pub mod_env_attack_curve: FloatParam,
+    #[id = "mod_env_decay_curve"]
+    pub mod_env_decay_curve: FloatParam,
+    #[id = "mod_env_release_curve"]
+    pub mod_env_release_curve: FloatParam,
+    #[persist = "mod-env-breakpoints"]
+    pub mod_env_breakpoints: RwLock<Breakpoints>,
+
+    /// Whether every envelope's stage times follow the host's tempo. The times are set as they
+    /// would be at [`SYNC_TEMPO`][crate::envelope::SYNC_TEMPO].
+    #[id = "envelope_sync"]
+    pub envelope_sync: BoolParam,
 
     #[id = "envelope_bypass"]
     pub envelope_bypass: BoolParam,
 
     // Carrier Filter
+    /// The carrier filter is applied to the operators that are heard, and the modulator filter to
+    /// the operators that only modulate other operators, before their modulation is applied.
+    #[id = "carrier_filter_enabled"]
+    pub carrier_filter_enabled: BoolParam,
     #[id = "carrier_filter_type"]
     pub carrier_filter_type: BoolParam,
     #[id = "carrier_filter_cutoff"]
@@ -54,6 +255,8 @@ pub struct AmSynthParams {
     pub carrier_filter_resonance: FloatParam,
 
     // Modulator Filter
+    #[id = "modulator_filter_enabled"]
+    pub modulator_filter_enabled: BoolParam,
     #[id = "modulator_filter_type"]
     pub modulator_filter_type: BoolParam,
     #[id = "modulator_filter_cutoff"]
@@ -62,6 +265,9 @@ pub struct AmSynthParams {
     pub modulator_filter_resonance: FloatParam,
 
     // Global Filter
+    /// The global filter is applied to every voice's output.
+    #[id = "global_filter_enabled"]
+    pub global_filter_enabled: BoolParam,
     #[id = "global_filter_type"]
     pub global_filter_type: BoolParam,
     #[id = "global_filter_cutoff"]
@@ -69,76 +275,295 @@ pub struct AmSynthParams {
     #[id = "global_filter_resonance"]
     pub global_filter_resonance: FloatParam,
 
-    // Neue Parameter
-    #[id = "carrier_keyboard"]
-    pub carrier_keyboard: BoolParam,
-
-    #[id = "modulator_keyboard"]
-    pub modulator_keyboard: BoolParam,
-
     #[id = "tuning"]
     pub tuning: FloatParam,
+    /// How long every smoothed parameter takes to reach a new value, in milliseconds.
+    #[id = "smoothing_time"]
+    pub smoothing_time: FloatParam,
+
+    // Velocity
+    #[id = "velocity_curve"]
+    pub velocity_curve: EnumParam<VelocityCurve>,
+    /// The points of the user velocity curve.
+    #[persist = "velocity-table"]
+    pub velocity_table: RwLock<VelocityTable>,
+    /// How much the velocity changes the voice's level. At 0 every note plays at full level.
+    #[id = "velocity_sensitivity"]
+    pub velocity_sensitivity: FloatParam,
+    /// How far the velocity moves the mod depth, in normalized parameter values.
+    #[id = "velocity_mod_depth"]
+    pub velocity_mod_depth: FloatParam,
+    /// How much the velocity scales the envelopes of the operators that are only used as
+    /// modulators. At 0 the envelopes are not changed.
+    #[id = "velocity_modulator_envelope"]
+    pub velocity_modulator_envelope: FloatParam,
+    /// How far the velocity moves the global filter's cutoff, in normalized parameter values.
+    #[id = "velocity_cutoff"]
+    pub velocity_cutoff: FloatParam,
+
+    // MPE and note expressions
+    /// Whether pitch bend, channel pressure, and CC 74 on the zone's member channels only apply to
+    /// the note on that channel.
+    #[id = "mpe_enabled"]
+    pub mpe_enabled: BoolParam,
+    #[id = "mpe_zone"]
+    pub mpe_zone: EnumParam<MpeZone>,
+    /// The member channels' pitch bend range in semitones.
+    #[id = "mpe_bend_range"]
+    pub mpe_bend_range: IntParam,
+    #[id = "pressure_target"]
+    pub pressure_target: EnumParam<PressureTarget>,
+    /// How much of the target's range a note's pressure covers.
+    #[id = "pressure_amount"]
+    pub pressure_amount: FloatParam,
+    /// How far a note's slide moves the global filter's cutoff in either direction, in normalized
+    /// parameter values.
+    #[id = "slide_amount"]
+    pub slide_amount: FloatParam,
+
+    #[nested(group = "Arpeggiator")]
+    pub arp: ArpParams,
+
+    // Modulation
+    #[nested(array, group = "LFO")]
+    pub lfos: [LfoParams; NUM_LFOS],
+    #[nested(array, group = "Mod Slot")]
+    pub mod_slots: [ModSlotParams; MOD_SLOTS],
+}
+
+impl MpeZone {
+    /// The zone's master channel, zero-based.
+    pub fn master_channel(self) -> u8 {
+        match self {
+            MpeZone::Lower => 0,
+            MpeZone::Upper => 15,
+        }
+    }
 }
 
 impl Default for AmSynthParams {
     fn default() -> Self {
         Self {
-            carrier_freq: FloatParam::new(
-                "Carrier Freq",
-                440.0,
+            editor_state: crate::editor::default_state(),
+            midi_mappings: RwLock::new(MidiMappings::new()),
+            mod_routes: RwLock::new(Default::default()),
+
+            mod_depth: FloatParam::new("Mod Depth", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(linear_smoothing()),
+
+            algorithm: EnumParam::new("Algorithm", Algorithm::Stack),
+            operator_count: IntParam::new(
+                "Operators",
+                2,
+                IntRange::Linear {
+                    min: 2,
+                    max: MAX_OPERATORS as i32,
+                },
+            ),
+
+            // The carrier and the modulator keep the frequency ranges they had before there were
+            // more operators so existing automation and presets map to the same frequencies
+            carrier: OperatorParams::new(1, 440.0, freq_range(20.0, 20000.0, -2.0), true),
+            modulator: OperatorParams::new(2, 2.0, freq_range(0.1, 1000.0, -2.0), false),
+            op3: OperatorParams::new(3, 440.0, freq_range(0.1, 20000.0, -2.5), true),
+            op4: OperatorParams::new(4, 440.0, freq_range(0.1, 20000.0, -2.5), true),
+            op5: OperatorParams::new(5, 440.0, freq_range(0.1, 20000.0, -2.5), true),
+            op6: OperatorParams::new(6, 440.0, freq_range(0.1, 20000.0, -2.5), true),
+
+            sample: RwLock::new(None),
+            sample_root_key: IntParam::new(
+                "Sample Root Key",
+                60,
+                IntRange::Linear { min: 0, max: 127 },
+            )
+            .with_value_to_string(formatters::v2s_i32_note_formatter())
+            .with_string_to_value(formatters::s2v_i32_note_formatter()),
+            sample_loop_mode: EnumParam::new("Sample Loop Mode", SampleLoopMode::OneShot),
+            sample_loop_start: FloatParam::new(
+                "Sample Loop Start",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(1))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            sample_loop_end: FloatParam::new(
+                "Sample Loop End",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(1))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            sample_crossfade: FloatParam::new(
+                "Sample Crossfade",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 0.5 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            // Envelope parameters
+            global_envelope_mode: EnumParam::new("Global Envelope Mode", EnvelopeMode::Dahdsr),
+            global_delay: FloatParam::new(
+                "Global Delay",
+                0.0,
                 FloatRange::Skewed {
-                    min: 20.0,
-                    max: 20000.0,
-                    factor: FloatRange::skew_factor(-2.0),
+                    min: 0.0,
+                    max: 1.0,
+                    factor: 0.5,
                 },
             )
-            .with_unit(" Hz")
-            .with_value_to_string(formatters::v2s_f32_hz_then_khz(2))
-            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            global_attack: FloatParam::new(
+                "Global Attack",
+                0.01,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            global_hold: FloatParam::new(
+                "Global Hold",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            global_decay: FloatParam::new(
+                "Global Decay",
+                0.1,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            global_sustain: FloatParam::new(
+                "Global Sustain",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(linear_smoothing()),
+            global_release: FloatParam::new(
+                "Global Release",
+                0.1,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            global_attack_curve: curve_param(
+                String::from("Global Attack Curve"),
+                EnvelopeCurves::default().attack,
+            ),
+            global_decay_curve: curve_param(
+                String::from("Global Decay Curve"),
+                EnvelopeCurves::default().decay,
+            ),
+            global_release_curve: curve_param(
+                String::from("Global Release Curve"),
+                EnvelopeCurves::default().release,
+            ),
+            global_breakpoints: RwLock::new(Breakpoints::default()),
 
-            modulator_freq: FloatParam::new(
-                "Modulator Freq",
-                2.0,
+            mod_env_mode: EnumParam::new("Mod Env Mode", EnvelopeMode::Dahdsr),
+            mod_env_delay: FloatParam::new(
+                "Mod Env Delay",
+                0.0,
                 FloatRange::Skewed {
-                    min: 0.1,
-                    max: 1000.0,
-                    factor: FloatRange::skew_factor(-2.0),
+                    min: 0.0,
+                    max: 1.0,
+                    factor: 0.5,
                 },
             )
-            .with_unit(" Hz")
-            .with_value_to_string(formatters::v2s_f32_hz_then_khz(2))
-            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
-
-            mod_depth: FloatParam::new("Mod Depth", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
-
-            // Envelope parameters (for carrier, modulator, and global)
-            carrier_attack: FloatParam::new("Carrier Attack", 0.01, FloatRange::Skewed { min: 0.001, max: 1.0, factor: 0.5 })
-                .with_unit(" s"),
-            carrier_decay: FloatParam::new("Carrier Decay", 0.1, FloatRange::Skewed { min: 0.001, max: 1.0, factor: 0.5 })
-                .with_unit(" s"),
-            carrier_sustain: FloatParam::new("Carrier Sustain", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
-            carrier_release: FloatParam::new("Carrier Release", 0.1, FloatRange::Skewed { min: 0.001, max: 1.0, factor: 0.5 })
-                .with_unit(" s"),
-
-            modulator_attack: FloatParam::new("Modulator Attack", 0.01, FloatRange::Skewed { min: 0.001, max: 1.0, factor: 0.5 })
-                .with_unit(" s"),
-            modulator_decay: FloatParam::new("Modulator Decay", 0.1, FloatRange::Skewed { min: 0.001, max: 1.0, factor: 0.5 })
-                .with_unit(" s"),
-            modulator_sustain: FloatParam::new("Modulator Sustain", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
-            modulator_release: FloatParam::new("Modulator Release", 0.1, FloatRange::Skewed { min: 0.001, max: 1.0, factor: 0.5 })
-                .with_unit(" s"),
-
-            global_attack: FloatParam::new("Global Attack", 0.01, FloatRange::Skewed { min: 0.001, max: 1.0, factor: 0.5 })
-                .with_unit(" s"),
-            global_decay: FloatParam::new("Global Decay", 0.1, FloatRange::Skewed { min: 0.001, max: 1.0, factor: 0.5 })
-                .with_unit(" s"),
-            global_sustain: FloatParam::new("Global Sustain", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
-            global_release: FloatParam::new("Global Release", 0.1, FloatRange::Skewed { min: 0.001, max: 1.0, factor: 0.5 })
-                .with_unit(" s"),
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            mod_env_attack: FloatParam::new(
+                "Mod Env Attack",
+                0.01,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            mod_env_hold: FloatParam::new(
+                "Mod Env Hold",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            mod_env_decay: FloatParam::new(
+                "Mod Env Decay",
+                0.5,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            mod_env_sustain: FloatParam::new(
+                "Mod Env Sustain",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(linear_smoothing()),
+            mod_env_release: FloatParam::new(
+                "Mod Env Release",
+                0.1,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            mod_env_attack_curve: curve_param(
+                String::from("Mod Env Attack Curve"),
+                EnvelopeCurves::default().attack,
+            ),
+            mod_env_decay_curve: curve_param(
+                String::from("Mod Env Decay Curve"),
+                EnvelopeCurves::default().decay,
+            ),
+            mod_env_release_curve: curve_param(
+                String::from("Mod Env Release Curve"),
+                EnvelopeCurves::default().release,
+            ),
+            mod_env_breakpoints: RwLock::new(Breakpoints::default()),
+
+            envelope_sync: BoolParam::new("Envelope Tempo Sync", false),
             envelope_bypass: BoolParam::new("Envelope Bypass", false),
             // Filter parameters (for carrier, modulator, and global)
-            carrier_filter_type: BoolParam::new("Carrier Filter Type", true)
-                .with_value_to_string(Arc::new(|v| String::from(if v { "Moog" } else { "Roland" }))),
+            carrier_filter_enabled: BoolParam::new("Carrier Filter", false),
+            carrier_filter_type: BoolParam::new("Carrier Filter Type", true).with_value_to_string(
+                Arc::new(|v| String::from(if v { "Moog" } else { "Roland" })),
+            ),
             carrier_filter_cutoff: FloatParam::new(
                 "Carrier Filter Cutoff",
                 1000.0,
@@ -150,11 +575,20 @@ impl Default for AmSynthParams {
             )
             .with_unit(" Hz")
             .with_value_to_string(formatters::v2s_f32_hz_then_khz(2))
-            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
-            carrier_filter_resonance: FloatParam::new("Carrier Filter Resonance", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+            .with_smoother(logarithmic_smoothing()),
+            carrier_filter_resonance: FloatParam::new(
+                "Carrier Filter Resonance",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(linear_smoothing()),
 
+            modulator_filter_enabled: BoolParam::new("Modulator Filter", false),
             modulator_filter_type: BoolParam::new("Modulator Filter Type", true)
-                .with_value_to_string(Arc::new(|v| String::from(if v { "Moog" } else { "Roland" }))),
+                .with_value_to_string(Arc::new(|v| {
+                    String::from(if v { "Moog" } else { "Roland" })
+                })),
             modulator_filter_cutoff: FloatParam::new(
                 "Modulator Filter Cutoff",
                 1000.0,
@@ -166,11 +600,19 @@ impl Default for AmSynthParams {
             )
             .with_unit(" Hz")
             .with_value_to_string(formatters::v2s_f32_hz_then_khz(2))
-            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
-            modulator_filter_resonance: FloatParam::new("Modulator Filter Resonance", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+            .with_smoother(logarithmic_smoothing()),
+            modulator_filter_resonance: FloatParam::new(
+                "Modulator Filter Resonance",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(linear_smoothing()),
 
-            global_filter_type: BoolParam::new("Global Filter Type", true)
-                .with_value_to_string(Arc::new(|v| String::from(if v { "Moog" } else { "Roland" }))),
+            global_filter_enabled: BoolParam::new("Global Filter", false),
+            global_filter_type: BoolParam::new("Global Filter Type", true).with_value_to_string(
+                Arc::new(|v| String::from(if v { "Moog" } else { "Roland" })),
+            ),
             global_filter_cutoff: FloatParam::new(
                 "Global Filter Cutoff",
                 1000.0,
@@ -182,12 +624,15 @@ impl Default for AmSynthParams {
             )
             .with_unit(" Hz")
             .with_value_to_string(formatters::v2s_f32_hz_then_khz(2))
-            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
-            global_filter_resonance: FloatParam::new("Global Filter Resonance", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+            .with_smoother(logarithmic_smoothing()),
+            global_filter_resonance: FloatParam::new(
+                "Global Filter Resonance",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(linear_smoothing()),
 
-            // Neue Parameter initialisieren
-            carrier_keyboard: BoolParam::new("Carrier Keyboard", true),
-            modulator_keyboard: BoolParam::new("Modulator Keyboard", false),
             tuning: FloatParam::new(
                 "Tuning",
                 440.0,
@@ -198,7 +643,381 @@ impl Default for AmSynthParams {
             )
             .with_unit(" Hz")
             .with_value_to_string(formatters::v2s_f32_hz_then_khz(2))
-            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+            .with_smoother(logarithmic_smoothing()),
+            smoothing_time: FloatParam::new(
+                "Smoothing Time",
+                DEFAULT_SMOOTHING_TIME,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 200.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            velocity_curve: EnumParam::new("Velocity Curve", VelocityCurve::Linear),
+            velocity_table: RwLock::new(VelocityTable::default()),
+            velocity_sensitivity: FloatParam::new(
+                "Velocity Sensitivity",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_smoother(linear_smoothing()),
+            velocity_mod_depth: FloatParam::new(
+                "Velocity to Mod Depth",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_smoother(linear_smoothing()),
+            velocity_modulator_envelope: FloatParam::new(
+                "Velocity to Modulator Envelope",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_smoother(linear_smoothing()),
+            velocity_cutoff: FloatParam::new(
+                "Velocity to Cutoff",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_smoother(linear_smoothing()),
+
+            mpe_enabled: BoolParam::new("MPE", false),
+            mpe_zone: EnumParam::new("MPE Zone", MpeZone::Lower),
+            mpe_bend_range: IntParam::new(
+                "MPE Bend Range",
+                48,
+                IntRange::Linear { min: 1, max: 96 },
+            )
+            .with_unit(" st"),
+            pressure_target: EnumParam::new("Pressure Target", PressureTarget::ModDepth),
+            pressure_amount: FloatParam::new(
+                "Pressure Amount",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_smoother(linear_smoothing()),
+            slide_amount: FloatParam::new(
+                "Slide Amount",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_smoother(linear_smoothing()),
+
+            arp: ArpParams::default(),
+
+            lfos: std::array::from_fn(|lfo| LfoParams::new(lfo + 1)),
+            mod_slots: std::array::from_fn(|slot| ModSlotParams::new(slot + 1)),
         }
     }
 }
+
+impl OperatorParams {
+    pub fn new(number: usize, freq: f32, freq_range: FloatRange, keyboard: bool) -> Self {
+        Self {
+            engine: EnumParam::new(format!("Op {number} Engine"), OscillatorEngine::Sine),
+            wavetable_position: FloatParam::new(
+                format!("Op {number} Wavetable Position"),
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_smoother(linear_smoothing()),
+            wavetable: RwLock::new(WavetableSource::default()),
+
+            keyboard: BoolParam::new(format!("Op {number} Keyboard"), keyboard),
+            ratio: FloatParam::new(
+                format!("Op {number} Ratio"),
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.125,
+                    max: 16.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_value_to_string(formatters::v2s_f32_rounded(3))
+            .with_smoother(logarithmic_smoothing()),
+            freq: FloatParam::new(format!("Op {number} Freq"), freq, freq_range)
+                .with_unit(" Hz")
+                .with_value_to_string(formatters::v2s_f32_hz_then_khz(2))
+                .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+                .with_smoother(logarithmic_smoothing()),
+            level: FloatParam::new(
+                format!("Op {number} Level"),
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(linear_smoothing()),
+            feedback: FloatParam::new(
+                format!("Op {number} Feedback"),
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_smoother(linear_smoothing()),
+
+            envelope_mode: EnumParam::new(
+                format!("Op {number} Envelope Mode"),
+                EnvelopeMode::Dahdsr,
+            ),
+            delay: FloatParam::new(
+                format!("Op {number} Delay"),
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            attack: FloatParam::new(
+                format!("Op {number} Attack"),
+                0.01,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            hold: FloatParam::new(
+                format!("Op {number} Hold"),
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            decay: FloatParam::new(
+                format!("Op {number} Decay"),
+                0.1,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            sustain: FloatParam::new(
+                format!("Op {number} Sustain"),
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(linear_smoothing()),
+            release: FloatParam::new(
+                format!("Op {number} Release"),
+                0.1,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 1.0,
+                    factor: 0.5,
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(linear_smoothing()),
+            attack_curve: curve_param(
+                format!("Op {number} Attack Curve"),
+                EnvelopeCurves::default().attack,
+            ),
+            decay_curve: curve_param(
+                format!("Op {number} Decay Curve"),
+                EnvelopeCurves::default().decay,
+            ),
+            release_curve: curve_param(
+                format!("Op {number} Release Curve"),
+                EnvelopeCurves::default().release,
+            ),
+            breakpoints: RwLock::new(Breakpoints::default()),
+        }
+    }
+}
+
+impl AmSynthParams {
+    /// The parameters for every operator, indexed by operator number.
+    pub fn operators(&self) -> [&OperatorParams; MAX_OPERATORS] {
+        [
+            &self.carrier,
+            &self.modulator,
+            &self.op3,
+            &self.op4,
+            &self.op5,
+            &self.op6,
+        ]
+    }
+
+    pub fn operator(&self, operator: usize) -> &OperatorParams {
+        self.operators()[operator]
+    }
+
+    /// The persisted multi-stage shape for one of a voice's envelopes.
+    pub fn breakpoints(&self, target: EnvelopeTarget) -> &RwLock<Breakpoints> {
+        match target {
+            EnvelopeTarget::Global => &self.global_breakpoints,
+            EnvelopeTarget::Mod => &self.mod_env_breakpoints,
+            EnvelopeTarget::Operator(operator) => &self.operator(operator).breakpoints,
+        }
+    }
+
+    /// The parameters of one of a voice's envelopes.
+    pub fn envelope(&self, target: EnvelopeTarget) -> EnvelopeParams<'_> {
+        match target {
+            EnvelopeTarget::Global => EnvelopeParams {
+                mode: &self.global_envelope_mode,
+                delay: &self.global_delay,
+                attack: &self.global_attack,
+                hold: &self.global_hold,
+                decay: &self.global_decay,
+                sustain: &self.global_sustain,
+                release: &self.global_release,
+                attack_curve: &self.global_attack_curve,
+                decay_curve: &self.global_decay_curve,
+                release_curve: &self.global_release_curve,
+            },
+            EnvelopeTarget::Mod => EnvelopeParams {
+                mode: &self.mod_env_mode,
+                delay: &self.mod_env_delay,
+                attack: &self.mod_env_attack,
+                hold: &self.mod_env_hold,
+                decay: &self.mod_env_decay,
+                sustain: &self.mod_env_sustain,
+                release: &self.mod_env_release,
+                attack_curve: &self.mod_env_attack_curve,
+                decay_curve: &self.mod_env_decay_curve,
+                release_curve: &self.mod_env_release_curve,
+            },
+            EnvelopeTarget::Operator(operator) => {
+                let operator = self.operator(operator);
+                EnvelopeParams {
+                    mode: &operator.envelope_mode,
+                    delay: &operator.delay,
+                    attack: &operator.attack,
+                    hold: &operator.hold,
+                    decay: &operator.decay,
+                    sustain: &operator.sustain,
+                    release: &operator.release,
+                    attack_curve: &operator.attack_curve,
+                    decay_curve: &operator.decay_curve,
+                    release_curve: &operator.release_curve,
+                }
+            }
+        }
+    }
+
+    /// The persisted wavetable source for an operator.
+    pub fn wavetable_source(&self, operator: usize) -> &RwLock<WavetableSource> {
+        &self.operator(operator).wavetable
+    }
+}
+
+/// Linear smoothing for levels, depths, and times. The time is replaced by the global smoothing
+/// time, see [`crate::smoothing`].
+pub fn linear_smoothing() -> SmoothingStyle {
+    SmoothingStyle::Linear(DEFAULT_SMOOTHING_TIME)
+}
+
+/// Logarithmic smoothing for frequencies, so a change takes equally long in every octave. The time
+/// is replaced by the global smoothing time, see [`crate::smoothing`].
+pub fn logarithmic_smoothing() -> SmoothingStyle {
+    SmoothingStyle::Logarithmic(DEFAULT_SMOOTHING_TIME)
+}
+
+/// A skewed range for an operator's frequency parameter.
+fn freq_range(min: f32, max: f32, skew: f32) -> FloatRange {
+    FloatRange::Skewed {
+        min,
+        max,
+        factor: FloatRange::skew_factor(skew),
+    }
+}
+
+/// A parameter for the shape of an envelope segment. See [`EnvelopeCurves`] for the range.
+fn curve_param(name: String, default: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Linear {
+            min: -1.0,
+            max: 1.0,
+        },
+    )
+    .with_smoother(linear_smoothing())
+    .with_value_to_string(v2s_curve())
+    .with_string_to_value(s2v_curve())
+}
+
+/// Formats an envelope curve as `Exp`, `Linear`, or `Log` followed by the curve's strength.
+fn v2s_curve() -> Arc<dyn Fn(f32) -> String + Send + Sync> {
+    Arc::new(|value| {
+        let percentage = (value.abs() * 100.0).round();
+        if percentage == 0.0 {
+            String::from("Linear")
+        } else if value < 0.0 {
+            format!("Exp {percentage:.0}%")
+        } else {
+            format!("Log {percentage:.0}%")
+        }
+    })
+}
+
+/// Parses the strings produced by [`v2s_curve()`]. Plain numbers are percentages, with negative
+/// numbers for exponential curves.
+fn s2v_curve() -> Arc<dyn Fn(&str) -> Option<f32> + Send + Sync> {
+    Arc::new(|string| {
+        let string = string.trim().to_lowercase();
+        if string.starts_with("lin") {
+            return Some(0.0);
+        }
+
+        let (sign, amount) = if let Some(amount) = string.strip_prefix("exp") {
+            (-1.0, amount)
+        } else if let Some(amount) = string.strip_prefix("log") {
+            (1.0, amount)
+        } else {
+            (1.0, string.as_str())
+        };
+        let amount: f32 = amount
+            .trim()
+            .trim_end_matches('%')
+            .trim_end()
+            .parse()
+            .ok()?;
+
+        Some(sign * amount / 100.0)
+    })
+}
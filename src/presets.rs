@@ -0,0 +1,476 @@
+use nih_plug::prelude::{Param, ParamPtr, Params};
+use nih_plug::wrapper::state::{ParamValue, PluginState};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::params::AmSynthParams;
+
+/// The presets that are included with the plugin.
+const FACTORY_PRESETS: [&str; 5] = [
+    include_str!("../presets/init.json"),
+    include_str!("../presets/bell.json"),
+    include_str!("../presets/pluck_bass.json"),
+    include_str!("../presets/ring_lead.json"),
+    include_str!("../presets/tremolo_pad.json"),
+];
+
+/// The persisted fields that belong to the editor instead of the sound. These are not saved in
+/// presets, and loading a preset keeps their current values.
+const EDITOR_FIELDS: [&str; 2] = ["editor-state", "midi-mappings"];
+
+/// The name of the file in the user preset directory that lists the favourite presets.
+const FAVOURITES_FILE: &str = "favourites.txt";
+
+/// A named snapshot of the plugin's state. Presets are stored as JSON files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The plugin state without the editor's fields. Parameters that are missing from the state
+    /// are set to their default values when the preset is loaded.
+    pub state: PluginState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PresetSource {
+    Factory,
+    User,
+}
+
+/// A preset in the [`PresetLibrary`], and where it came from.
+#[derive(Debug, Clone)]
+pub struct LibraryPreset {
+    pub source: PresetSource,
+    pub preset: Preset,
+}
+
+/// Which presets the preset browser lists.
+#[derive(Debug, Clone, Default)]
+pub struct PresetFilter {
+    /// Only list presets whose name, author, category or tags contain this text, ignoring case.
+    pub search: String,
+    pub category: Option<String>,
+    pub tag: Option<String>,
+    pub favourites_only: bool,
+}
+
+/// The factory presets and the presets from the user preset directory, sorted by name with the
+/// factory presets first. The favourites are stored in the user preset directory.
+pub struct PresetLibrary {
+    presets: Vec<LibraryPreset>,
+    /// The [keys][LibraryPreset::key()] of the favourite presets.
+    favourites: BTreeSet<String>,
+    /// The directory user presets are stored in. User presets can't be saved without it.
+    directory: Option<PathBuf>,
+}
+
+impl Preset {
+    /// Create a preset from the plugin's current state, as returned by
+    /// [`GuiContext::get_state()`][nih_plug::prelude::GuiContext::get_state()]. The editor's
+    /// fields are left out.
+    pub fn from_state(
+        name: String,
+        author: String,
+        category: String,
+        tags: Vec<String>,
+        mut state: PluginState,
+    ) -> Self {
+        state
+            .fields
+            .retain(|key, _| !EDITOR_FIELDS.contains(&key.as_str()));
+
+        Self {
+            name,
+            author,
+            category,
+            tags,
+            state,
+        }
+    }
+
+    /// The state that loads this preset when it's passed to
+    /// [`GuiContext::set_state()`][nih_plug::prelude::GuiContext::set_state()]. Every parameter
+    /// and field is set, falling back to the defaults for the values that are not in the preset,
+    /// so the result does not depend on the previous sound. The editor's fields are taken from
+    /// `current_state`.
+    pub fn restore_state(&self, current_state: &PluginState) -> PluginState {
+        let mut state = default_state();
+        state.version.clone_from(&current_state.version);
+
+        for (param_id, value) in &self.state.params {
+            // Values keep the type of the default value, since untyped JSON numbers can be
+            // deserialized as the wrong variant
+            if let Some(default_value) = state.params.get_mut(param_id) {
+                *default_value = match (&*default_value, value) {
+                    (ParamValue::F32(_), ParamValue::I32(value)) => ParamValue::F32(*value as f32),
+                    (ParamValue::I32(_), ParamValue::F32(value)) => {
+                        ParamValue::I32(value.round() as i32)
+                    }
+                    _ => value.clone(),
+                };
+            }
+        }
+        for (key, value) in &self.state.fields {
+            if !EDITOR_FIELDS.contains(&key.as_str()) {
+                state.fields.insert(key.clone(), value.clone());
+            }
+        }
+        for key in EDITOR_FIELDS {
+            if let Some(value) = current_state.fields.get(key) {
+                state.fields.insert(key.to_owned(), value.clone());
+            }
+        }
+
+        state
+    }
+
+    /// Whether the preset's name, author, category or one of its tags contains `search`, which
+    /// needs to be lowercase.
+    fn matches_search(&self, search: &str) -> bool {
+        [&self.name, &self.author, &self.category]
+            .into_iter()
+            .chain(&self.tags)
+            .any(|text| text.to_lowercase().contains(search))
+    }
+}
+
+impl LibraryPreset {
+    /// A key that identifies this preset in the favourites list.
+    pub fn key(&self) -> String {
+        match self.source {
+            PresetSource::Factory => format!("factory/{}", self.preset.name),
+            PresetSource::User => format!("user/{}", self.preset.name),
+        }
+    }
+}
+
+impl PresetLibrary {
+    /// Load the factory presets, and the user presets and favourites from `directory`. Presets
+    /// that can't be read are skipped.
+    pub fn load(directory: Option<PathBuf>) -> Self {
+        let mut presets: Vec<LibraryPreset> = FACTORY_PRESETS
+            .into_iter()
+            .filter_map(|json| match serde_json::from_str(json) {
+                Ok(preset) => Some(LibraryPreset {
+                    source: PresetSource::Factory,
+                    preset,
+                }),
+                Err(err) => {
+                    nih_plug::nih_debug_assert_failure!("Invalid factory preset: {err}");
+                    None
+                }
+            })
+            .collect();
+
+        let mut favourites = BTreeSet::new();
+        if let Some(directory) = &directory {
+            presets.extend(read_user_presets(directory));
+            if let Ok(contents) = std::fs::read_to_string(directory.join(FAVOURITES_FILE)) {
+                favourites = contents
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(String::from)
+                    .collect();
+            }
+        }
+
+        let mut library = Self {
+            presets,
+            favourites,
+            directory,
+        };
+        library.sort();
+
+        library
+    }
+
+    pub fn presets(&self) -> &[LibraryPreset] {
+        &self.presets
+    }
+
+    /// Every category used by a preset, sorted alphabetically.
+    pub fn categories(&self) -> Vec<String> {
+        let categories: BTreeSet<&String> = self
+            .presets
+            .iter()
+            .map(|entry| &entry.preset.category)
+            .filter(|category| !category.is_empty())
+            .collect();
+
+        categories.into_iter().cloned().collect()
+    }
+
+    /// Every tag used by a preset, sorted alphabetically.
+    pub fn tags(&self) -> Vec<String> {
+        let tags: BTreeSet<&String> = self
+            .presets
+            .iter()
+            .flat_map(|entry| &entry.preset.tags)
+            .collect();
+
+        tags.into_iter().cloned().collect()
+    }
+
+    /// The indices of the presets that pass the filter.
+    pub fn filter(&self, filter: &PresetFilter) -> Vec<usize> {
+        let search = filter.search.trim().to_lowercase();
+
+        self.presets
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                filter
+                    .category
+                    .as_ref()
+                    .is_none_or(|category| entry.preset.category == *category)
+                    && filter
+                        .tag
+                        .as_ref()
+                        .is_none_or(|tag| entry.preset.tags.contains(tag))
+                    && (!filter.favourites_only || self.favourites.contains(&entry.key()))
+                    && entry.preset.matches_search(&search)
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    pub fn is_favourite(&self, idx: usize) -> bool {
+        self.favourites.contains(&self.presets[idx].key())
+    }
+
+    /// Add the preset to or remove it from the favourites, and write the favourites to the user
+    /// preset directory.
+    pub fn toggle_favourite(&mut self, idx: usize) -> Result<(), String> {
+        let key = self.presets[idx].key();
+        if !self.favourites.remove(&key) {
+            self.favourites.insert(key);
+        }
+
+        let directory = self.directory()?;
+        let contents: String = self
+            .favourites
+            .iter()
+            .map(|key| format!("{key}\n"))
+            .collect();
+        std::fs::create_dir_all(directory)
+            .and_then(|_| std::fs::write(directory.join(FAVOURITES_FILE), contents))
+            .map_err(|err| format!("Could not save the favourites: {err}"))
+    }
+
+    /// Write a preset to the user preset directory and add it to the library, replacing the user
+    /// preset with the same name if there is one. Returns the preset's new index.
+    pub fn save(&mut self, preset: Preset) -> Result<usize, String> {
+        if preset.name.trim().is_empty() {
+            return Err(String::from("The preset needs a name"));
+        }
+
+        let directory = self.directory()?;
+        let path = directory.join(format!("{}.json", file_name(&preset.name)));
+        let json = serde_json::to_string_pretty(&preset)
+            .map_err(|err| format!("Could not serialize the preset: {err}"))?;
+        std::fs::create_dir_all(directory)
+            .and_then(|_| std::fs::write(&path, json))
+            .map_err(|err| format!("Could not save '{}': {err}", path.display()))?;
+
+        let name = preset.name.clone();
+        self.presets
+            .retain(|entry| !(entry.source == PresetSource::User && entry.preset.name == name));
+        self.presets.push(LibraryPreset {
+            source: PresetSource::User,
+            preset,
+        });
+        self.sort();
+
+        Ok(self
+            .presets
+            .iter()
+            .position(|entry| entry.source == PresetSource::User && entry.preset.name == name)
+            .expect("The preset was just added"))
+    }
+
+    fn directory(&self) -> Result<&Path, String> {
+        self.directory
+            .as_deref()
+            .ok_or_else(|| String::from("There is no user preset directory"))
+    }
+
+    fn sort(&mut self) {
+        self.presets
+            .sort_by_cached_key(|entry| (entry.source, entry.preset.name.to_lowercase()));
+    }
+}
+
+/// The state of a plugin with default parameter values.
+fn default_state() -> PluginState {
+    let params = AmSynthParams::default();
+    let param_values = params
+        .param_map()
+        .into_iter()
+        .map(|(param_id, param_ptr, _)| {
+            // Enums are stored by index, since none of the variants have stable IDs
+            let value = unsafe {
+                match param_ptr {
+                    ParamPtr::FloatParam(p) => ParamValue::F32((*p).unmodulated_plain_value()),
+                    ParamPtr::IntParam(p) => ParamValue::I32((*p).unmodulated_plain_value()),
+                    ParamPtr::BoolParam(p) => ParamValue::Bool((*p).unmodulated_plain_value()),
+                    ParamPtr::EnumParam(p) => ParamValue::I32((*p).unmodulated_plain_value()),
+                }
+            };
+
+            (param_id, value)
+        })
+        .collect();
+
+    PluginState {
+        version: String::new(),
+        params: param_values,
+        fields: params.serialize_fields(),
+    }
+}
+
+fn read_user_presets(directory: &Path) -> Vec<LibraryPreset> {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .filter_map(|path| {
+            let preset = std::fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|json| serde_json::from_str(&json).map_err(|err| err.to_string()));
+            match preset {
+                Ok(preset) => Some(LibraryPreset {
+                    source: PresetSource::User,
+                    preset,
+                }),
+                Err(err) => {
+                    nih_plug::nih_error!("Could not load the preset '{}': {err}", path.display());
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// A file name for a preset, with characters that are not allowed in file names replaced.
+fn file_name(preset_name: &str) -> String {
+    preset_name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_library() -> PresetLibrary {
+        let mut library = PresetLibrary::load(None);
+        library.favourites.insert(String::from("factory/Bell"));
+
+        library
+    }
+
+    #[test]
+    fn factory_presets_are_valid() {
+        let library = PresetLibrary::load(None);
+        assert_eq!(library.presets().len(), FACTORY_PRESETS.len());
+
+        let defaults = default_state();
+        for entry in library.presets() {
+            for param_id in entry.preset.state.params.keys() {
+                assert!(
+                    defaults.params.contains_key(param_id),
+                    "'{}' sets the unknown parameter '{param_id}'",
+                    entry.preset.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn filters() {
+        let library = test_library();
+        let names = |filter: PresetFilter| -> Vec<&str> {
+            library
+                .filter(&filter)
+                .into_iter()
+                .map(|idx| library.presets()[idx].preset.name.as_str())
+                .collect()
+        };
+
+        assert_eq!(names(PresetFilter::default()).len(), FACTORY_PRESETS.len());
+        assert_eq!(
+            names(PresetFilter {
+                search: String::from("BELL"),
+                ..Default::default()
+            }),
+            ["Bell"]
+        );
+        assert_eq!(
+            names(PresetFilter {
+                category: Some(String::from("Bass")),
+                ..Default::default()
+            }),
+            ["Pluck Bass"]
+        );
+        assert_eq!(
+            names(PresetFilter {
+                favourites_only: true,
+                ..Default::default()
+            }),
+            ["Bell"]
+        );
+        assert!(names(PresetFilter {
+            tag: Some(String::from("bright")),
+            ..Default::default()
+        })
+        .contains(&"Bell"));
+    }
+
+    #[test]
+    fn restore_fills_in_defaults() {
+        let library = PresetLibrary::load(None);
+        let bell = library
+            .presets()
+            .iter()
+            .find(|entry| entry.preset.name == "Bell")
+            .unwrap();
+
+        let mut current_state = default_state();
+        current_state.version = String::from("1.2.3");
+        current_state
+            .params
+            .insert(String::from("tuning"), ParamValue::F32(432.0));
+        current_state.fields.insert(
+            String::from("midi-mappings"),
+            String::from("{\"mod_depth\":1}"),
+        );
+
+        let state = bell.preset.restore_state(&current_state);
+        assert_eq!(state.version, "1.2.3");
+        assert!(matches!(state.params["tuning"], ParamValue::F32(tuning) if tuning == 440.0));
+        assert!(matches!(state.params["operator_count"], ParamValue::I32(2)));
+        assert!(matches!(state.params["modulator_ratio"], ParamValue::F32(ratio) if ratio == 3.5));
+        assert_eq!(state.fields["midi-mappings"], "{\"mod_depth\":1}");
+    }
+}
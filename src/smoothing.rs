@@ -0,0 +1,181 @@
+//! The parameters' smoothers. nih-plug's smoothers take their time from the [`SmoothingStyle`]
+//! a parameter is declared with, which cannot change afterwards, while every smoothed parameter
+//! here follows the global smoothing time parameter. The audio thread therefore owns a separate
+//! [`Smoother`] for every parameter. The parameters only declare whether they're smoothed linearly
+//! or logarithmically, and a parameter's smoother is retargeted with the current smoothing time
+//! whenever the parameter's value changes. Changing the smoothing time doesn't affect the
+//! smoothers that are already moving. MIDI learn moves the smoothers of the parameters bound to a
+//! CC the same way, so CCs take effect on the audio thread even without the editor.
+
+use nih_plug::prelude::*;
+
+use crate::params::AmSynthParams;
+
+pub struct Smoothers {
+    /// Every float parameter's smoother, sorted by the parameter's address so they can be found
+    /// without allocating.
+    smoothers: Vec<(usize, ParamSmoother)>,
+    sample_rate: f32,
+    /// The smoothing time for new targets, in milliseconds.
+    time: f32,
+}
+
+/// A parameter's smoother and the parameter's value when it was last read.
+struct ParamSmoother {
+    smoother: Smoother<f32>,
+    /// The smoother is retargeted when the parameter's value differs from this. Targets set by
+    /// [`Smoothers::set_target()`] are kept until then.
+    value: f32,
+    /// Whether the smoother jumps to the parameter's value the next time it's read.
+    snap: bool,
+}
+
+impl Smoothers {
+    /// Create the smoothers for `params`. These need to be the parameters that are read from
+    /// afterwards, as the smoothers are found by the parameters' addresses.
+    pub fn new(params: &AmSynthParams, sample_rate: f32) -> Self {
+        let mut smoothers: Vec<_> = params
+            .param_map()
+            .into_iter()
+            .filter_map(|(_, param, _)| match param {
+                ParamPtr::FloatParam(param) => Some((
+                    param as usize,
+                    ParamSmoother {
+                        smoother: Smoother::none(),
+                        value: 0.0,
+                        snap: true,
+                    },
+                )),
+                _ => None,
+            })
+            .collect();
+        smoothers.sort_unstable_by_key(|(address, _)| *address);
+
+        Self {
+            smoothers,
+            sample_rate,
+            time: params.smoothing_time.value(),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Set the smoothing time in milliseconds for the parameters' next changes.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// Jump to every parameter's value the next time it's read.
+    pub fn snap(&mut self) {
+        for (_, smoother) in &mut self.smoothers {
+            smoother.snap = true;
+        }
+    }
+
+    /// The parameter's smoothed value after `steps` samples.
+    pub fn next_step(&mut self, param: &FloatParam, steps: u32) -> f32 {
+        match self.smoother(param) {
+            Some(smoother) => smoother.next_step(steps),
+            None => param.value(),
+        }
+    }
+
+    /// Fill the first `len` values of `values` with the parameter's smoothed values.
+    pub fn next_block(&mut self, param: &FloatParam, values: &mut [f32], len: usize) {
+        match self.smoother(param) {
+            Some(smoother) => smoother.next_block(values, len),
+            None => values[..len].fill(param.value()),
+        }
+    }
+
+    /// Move a parameter's smoother to a new plain value without changing the parameter. The
+    /// smoother moves to the parameter's value again once the parameter changes.
+    pub fn set_target(&mut self, param: &FloatParam, value: f32) {
+        let (sample_rate, time) = (self.sample_rate, self.time);
+        if let Some(smoother) = self.find(param) {
+            smoother.retarget(param, sample_rate, time, value);
+        }
+    }
+
+    fn smoother(&mut self, param: &FloatParam) -> Option<&Smoother<f32>> {
+        self.find(param).map(|smoother| &smoother.smoother)
+    }
+
+    /// Find the parameter's smoother, and retarget it if the parameter changed since it was last
+    /// read.
+    fn find(&mut self, param: &FloatParam) -> Option<&mut ParamSmoother> {
+        let (sample_rate, time) = (self.sample_rate, self.time);
+        let address = param as *const FloatParam as usize;
+        let index = self
+            .smoothers
+            .binary_search_by_key(&address, |(address, _)| *address)
+            .ok();
+        nih_debug_assert!(index.is_some(), "No smoother for '{}'", param.name());
+        let smoother = &mut self.smoothers[index?].1;
+
+        let value = param.value();
+        if smoother.snap {
+            smoother.smoother.reset(value);
+            smoother.snap = false;
+        } else if value != smoother.value {
+            smoother.retarget(param, sample_rate, time, value);
+        }
+        smoother.value = value;
+
+        Some(smoother)
+    }
+}
+
+impl ParamSmoother {
+    /// Start moving to `target` over `time` milliseconds, with the curve the parameter was declared
+    /// with.
+    fn retarget(&mut self, param: &FloatParam, sample_rate: f32, time: f32, target: f32) {
+        self.smoother.style = match param.smoothed.style {
+            SmoothingStyle::Linear(_) => SmoothingStyle::Linear(time),
+            SmoothingStyle::Logarithmic(_) => SmoothingStyle::Logarithmic(time),
+            _ => SmoothingStyle::None,
+        };
+        self.smoother.set_target(sample_rate, target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smoothers_start_at_the_parameters_values() {
+        let params = AmSynthParams::default();
+        let mut smoothers = Smoothers::new(&params, 1000.0);
+        assert_eq!(smoothers.next_step(&params.mod_depth, 1), 0.5);
+        assert_eq!(smoothers.next_step(&params.carrier.freq, 1), 440.0);
+    }
+
+    #[test]
+    fn changes_take_the_smoothing_time() {
+        let params = AmSynthParams::default();
+        let mut smoothers = Smoothers::new(&params, 1000.0);
+        smoothers.set_time(100.0);
+
+        // The smoothers start at zero, so this is the same as the mod depth changing from zero to
+        // its default value
+        for (_, smoother) in &mut smoothers.smoothers {
+            smoother.snap = false;
+        }
+        assert!((smoothers.next_step(&params.mod_depth, 50) - 0.25).abs() < 1e-6);
+        assert_eq!(smoothers.next_step(&params.mod_depth, 50), 0.5);
+    }
+
+    #[test]
+    fn targets_are_kept_until_the_parameter_changes() {
+        let params = AmSynthParams::default();
+        let mut smoothers = Smoothers::new(&params, 1000.0);
+        smoothers.set_time(10.0);
+        smoothers.set_target(&params.mod_depth, 0.2);
+        assert!((smoothers.next_step(&params.mod_depth, 5) - 0.35).abs() < 1e-6);
+        assert_eq!(smoothers.next_step(&params.mod_depth, 5), 0.2);
+        assert_eq!(smoothers.next_step(&params.mod_depth, 5), 0.2);
+    }
+}
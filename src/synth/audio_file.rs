@@ -0,0 +1,91 @@
+use std::path::Path;
+
+/// Read the first channel of a WAV or FLAC file. Returns the samples and the file's sample rate.
+pub fn read_mono(path: &Path) -> Result<(Vec<f32>, f32), String> {
+    let is_flac = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("flac"));
+    let result = if is_flac {
+        read_flac(path).map_err(|err| err.to_string())
+    } else {
+        read_wav(path).map_err(|err| err.to_string())
+    };
+
+    match result {
+        Ok((samples, _)) if samples.is_empty() => {
+            Err(format!("'{}' does not contain any samples", path.display()))
+        }
+        Ok(result) => Ok(result),
+        Err(err) => Err(format!("Could not read '{}': {err}", path.display())),
+    }
+}
+
+fn read_wav(path: &Path) -> Result<(Vec<f32>, f32), hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .step_by(channels)
+            .collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .step_by(channels)
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+
+    Ok((samples, spec.sample_rate as f32))
+}
+
+fn read_flac(path: &Path) -> Result<(Vec<f32>, f32), claxon::Error> {
+    let mut reader = claxon::FlacReader::open(path)?;
+    let info = reader.streaminfo();
+    let channels = info.channels as usize;
+    let scale = (1i64 << (info.bits_per_sample - 1)) as f32;
+
+    let samples = reader
+        .samples()
+        .step_by(channels)
+        .map(|sample| sample.map(|sample| sample as f32 / scale))
+        .collect::<Result<_, _>>()?;
+
+    Ok((samples, info.sample_rate as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_wav_first_channel() {
+        let path =
+            std::env::temp_dir().join(format!("am_synth_audio_file_{}.wav", std::process::id()));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for n in 0..100 {
+            writer.write_sample(n as i16 * 100).unwrap();
+            writer.write_sample(-1000i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let (samples, sample_rate) = read_mono(&path).unwrap();
+        assert_eq!(sample_rate, 48000.0);
+        assert_eq!(samples.len(), 100);
+        assert_eq!(samples[0], 0.0);
+        assert_eq!(samples[10], 1000.0 / 32768.0);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_mono(&path).is_err());
+    }
+}
@@ -1 +1,4 @@
+mod audio_file;
+pub mod sample;
 pub mod sine;
+pub mod wavetable;
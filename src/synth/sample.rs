@@ -0,0 +1,259 @@
+use std::path::Path;
+
+use super::audio_file;
+
+/// A mono sample loaded from a WAV or FLAC file.
+pub struct Sample {
+    sample_rate: f32,
+    data: Vec<f32>,
+}
+
+/// The loop region in samples. The crossfade blends the end of the loop with the audio right
+/// before the loop start, so the loop point doesn't click.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopRegion {
+    pub start: usize,
+    pub end: usize,
+    pub crossfade: usize,
+}
+
+/// Plays back a [`Sample`] at a pitch relative to the sample's root key.
+pub struct SamplePlayer {
+    /// The playback position in the sample's samples.
+    position: f64,
+    /// The playback speed relative to the sample's original pitch.
+    pitch_ratio: f64,
+    sample_rate: f32,
+}
+
+impl Sample {
+    /// An empty sample, used until a file has been loaded. This plays back silence.
+    pub fn empty() -> Self {
+        Self {
+            sample_rate: 44100.0,
+            data: Vec::new(),
+        }
+    }
+
+    /// Load a WAV or FLAC file's first channel. This should never be called from the audio thread.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let (data, sample_rate) = audio_file::read_mono(path)?;
+
+        Ok(Self { sample_rate, data })
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Compute a loop region from the normalized loop start, loop end, and crossfade parameters.
+    /// The crossfade is relative to the loop's length, and it's limited by the amount of audio
+    /// before the loop start.
+    pub fn loop_region(&self, start: f32, end: f32, crossfade: f32) -> LoopRegion {
+        let len = self.data.len();
+        let start = ((start.clamp(0.0, 1.0) * len as f32) as usize).min(len.saturating_sub(1));
+        let end = ((end.clamp(0.0, 1.0) * len as f32) as usize).clamp(start + 1, len.max(1));
+        let crossfade = ((crossfade.clamp(0.0, 1.0) * (end - start) as f32) as usize).min(start);
+
+        LoopRegion {
+            start,
+            end,
+            crossfade,
+        }
+    }
+
+    /// Linearly interpolate the sample at a fractional position.
+    fn read(&self, position: f64) -> f32 {
+        let idx = position as usize;
+        let t = (position - idx as f64) as f32;
+        let current = self.data[idx.min(self.data.len() - 1)];
+        let next = self.data[(idx + 1).min(self.data.len() - 1)];
+
+        current + (next - current) * t
+    }
+}
+
+impl SamplePlayer {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            position: 0.0,
+            pitch_ratio: 1.0,
+            sample_rate,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Set the playback pitch. The sample plays back at its original speed when `freq` is equal
+    /// to the root key's frequency.
+    pub fn set_frequency(&mut self, freq: f32, root_freq: f32) {
+        self.pitch_ratio = freq as f64 / root_freq as f64;
+    }
+
+    /// Restart playback from the start of the sample.
+    pub fn reset(&mut self) {
+        self.position = 0.0;
+    }
+
+    /// Whether a one-shot sample has finished playing.
+    pub fn is_finished(&self, sample: &Sample) -> bool {
+        self.position >= sample.len() as f64
+    }
+
+    /// Generate the next sample. The sample is played once if `loop_region` is `None`, and it
+    /// loops between the region's start and end otherwise.
+    pub fn generate(&mut self, sample: &Sample, loop_region: Option<LoopRegion>) -> f32 {
+        if sample.is_empty() {
+            return 0.0;
+        }
+
+        let increment = self.pitch_ratio * (sample.sample_rate / self.sample_rate) as f64;
+        match loop_region {
+            Some(LoopRegion {
+                start,
+                end,
+                crossfade,
+            }) => {
+                let loop_length = (end - start) as f64;
+                let mut output = sample.read(self.position);
+
+                let crossfade_start = (end - crossfade) as f64;
+                if crossfade > 0 && self.position >= crossfade_start {
+                    let t = ((self.position - crossfade_start) / crossfade as f64) as f32;
+                    let pre_loop = sample.read(self.position - loop_length);
+                    output += (pre_loop - output) * t;
+                }
+
+                self.position += increment;
+                while self.position >= end as f64 {
+                    self.position -= loop_length;
+                }
+
+                output
+            }
+            None => {
+                if self.is_finished(sample) {
+                    return 0.0;
+                }
+
+                let output = sample.read(self.position);
+                self.position += increment;
+
+                output
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sample containing a rising ramp, so the output directly shows the playback position.
+    fn ramp(len: usize) -> Sample {
+        Sample {
+            sample_rate: 44100.0,
+            data: (0..len).map(|n| n as f32).collect(),
+        }
+    }
+
+    #[test]
+    fn root_key_plays_at_original_speed() {
+        let sample = ramp(100);
+        let mut player = SamplePlayer::new(44100.0);
+        player.set_frequency(261.63, 261.63);
+
+        for n in 0..100 {
+            assert_eq!(player.generate(&sample, None), n as f32);
+        }
+    }
+
+    #[test]
+    fn pitch_and_sample_rate_conversion() {
+        let sample = ramp(100);
+
+        // An octave up plays twice as fast
+        let mut player = SamplePlayer::new(44100.0);
+        player.set_frequency(880.0, 440.0);
+        player.generate(&sample, None);
+        assert_eq!(player.generate(&sample, None), 2.0);
+
+        // And a sample recorded at 44.1 kHz plays at half speed per sample at 88.2 kHz
+        let mut player = SamplePlayer::new(88200.0);
+        player.set_frequency(440.0, 440.0);
+        player.generate(&sample, None);
+        assert_eq!(player.generate(&sample, None), 0.5);
+    }
+
+    #[test]
+    fn one_shot_stops() {
+        let sample = ramp(10);
+        let mut player = SamplePlayer::new(44100.0);
+        for _ in 0..10 {
+            player.generate(&sample, None);
+        }
+
+        assert!(player.is_finished(&sample));
+        assert_eq!(player.generate(&sample, None), 0.0);
+
+        player.reset();
+        assert!(!player.is_finished(&sample));
+    }
+
+    #[test]
+    fn loops_between_start_and_end() {
+        let sample = ramp(100);
+        let region = sample.loop_region(0.2, 0.5, 0.0);
+        assert_eq!(
+            region,
+            LoopRegion {
+                start: 20,
+                end: 50,
+                crossfade: 0
+            }
+        );
+
+        let mut player = SamplePlayer::new(44100.0);
+        for n in 0..50 {
+            assert_eq!(player.generate(&sample, Some(region)), n as f32);
+        }
+        for n in 20..50 {
+            assert_eq!(player.generate(&sample, Some(region)), n as f32);
+        }
+    }
+
+    #[test]
+    fn crossfade_is_continuous() {
+        let sample = ramp(100);
+        let region = sample.loop_region(0.5, 1.0, 0.2);
+        assert_eq!(region.crossfade, 10);
+
+        // The ramp jumps back by the loop length at the loop point without a crossfade, but with
+        // the crossfade the output should move smoothly towards the loop start instead
+        let mut player = SamplePlayer::new(44100.0);
+        let output: Vec<f32> = (0..200)
+            .map(|_| player.generate(&sample, Some(region)))
+            .collect();
+        for window in output.windows(2).skip(1) {
+            assert!((window[1] - window[0]).abs() <= 5.0, "{window:?}");
+        }
+    }
+
+    #[test]
+    fn loop_region_is_clamped() {
+        let sample = ramp(100);
+        let region = sample.loop_region(0.9, 0.1, 1.0);
+        assert!(region.start < region.end && region.end <= 100);
+        assert!(region.crossfade <= region.start);
+
+        assert!(Sample::empty().is_empty());
+        let mut player = SamplePlayer::new(44100.0);
+        assert_eq!(player.generate(&Sample::empty(), None), 0.0);
+    }
+}
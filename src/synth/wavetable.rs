@@ -0,0 +1,511 @@
+use realfft::num_complex::Complex;
+use realfft::RealFftPlanner;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+
+use super::audio_file;
+
+/// The number of samples in a single wavetable frame. This matches the frame size used by Serum
+/// and most other wavetable synths, so their tables can be imported as is.
+pub const FRAME_SIZE: usize = 2048;
+/// The maximum number of frames in a wavetable. Longer files are truncated.
+pub const MAX_FRAMES: usize = 256;
+/// The number of band-limited versions of every frame. The first level contains all
+/// `FRAME_SIZE / 2` harmonics, and every next level contains half the harmonics of the previous
+/// level. The last level is a pure sine wave.
+const NUM_MIP_LEVELS: usize = FRAME_SIZE.trailing_zeros() as usize;
+
+/// The built-in wavetables that are always available without importing anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BuiltinWavetable {
+    /// Morphs from sine to triangle to square to saw.
+    #[default]
+    BasicShapes,
+    /// A pulse wave with the pulse width narrowing from 50% to 5%.
+    Pwm,
+    /// Adds one harmonic of a saw wave per frame, starting from a sine.
+    HarmonicSweep,
+}
+
+/// Where a wavetable's data comes from. This is stored in the plugin's state, so user tables can
+/// either be saved by path or embedded entirely in the state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum WavetableSource {
+    Builtin(BuiltinWavetable),
+    /// A WAV file that's loaded again when the plugin state gets restored.
+    File(PathBuf),
+    /// A WAV file's frames stored directly in the plugin state. `samples` contains a multiple of
+    /// [`FRAME_SIZE`] samples.
+    Embedded {
+        name: String,
+        samples: Vec<f32>,
+    },
+}
+
+/// A band-limited wavetable with one or more frames. The oscillator morphs between these frames
+/// based on its position.
+pub struct Wavetable {
+    name: String,
+    num_frames: usize,
+    /// The frames for each mip level. Every frame contains `FRAME_SIZE + 1` samples, with the last
+    /// sample being a copy of the first to simplify interpolation.
+    mip_levels: Vec<Vec<f32>>,
+}
+
+pub struct WavetableOscillator {
+    /// The current phase in `[0, 1)`.
+    phase: f32,
+    phase_delta: f32,
+    sample_rate: f32,
+    frequency: f32,
+    /// The position in the wavetable in `[0, 1]`, morphs between the table's frames.
+    position: f32,
+}
+
+impl Default for WavetableSource {
+    fn default() -> Self {
+        WavetableSource::Builtin(BuiltinWavetable::default())
+    }
+}
+
+impl BuiltinWavetable {
+    pub const ALL: [BuiltinWavetable; 3] = [
+        BuiltinWavetable::BasicShapes,
+        BuiltinWavetable::Pwm,
+        BuiltinWavetable::HarmonicSweep,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuiltinWavetable::BasicShapes => "Basic Shapes",
+            BuiltinWavetable::Pwm => "PWM",
+            BuiltinWavetable::HarmonicSweep => "Harmonic Sweep",
+        }
+    }
+}
+
+impl WavetableSource {
+    /// Read an audio file and store its frames so they can be embedded in the plugin state.
+    pub fn embed(path: &Path) -> Result<Self, String> {
+        Ok(WavetableSource::Embedded {
+            name: file_name(path),
+            samples: read_wav_frames(path)?,
+        })
+    }
+
+    /// The name shown for this source in the GUI.
+    pub fn name(&self) -> String {
+        match self {
+            WavetableSource::Builtin(builtin) => String::from(builtin.name()),
+            WavetableSource::File(path) => file_name(path),
+            WavetableSource::Embedded { name, .. } => name.clone(),
+        }
+    }
+}
+
+impl Wavetable {
+    /// Build the wavetable for a source. This allocates and performs a lot of FFTs, so it should
+    /// never be called from the audio thread.
+    pub fn from_source(source: &WavetableSource) -> Result<Self, String> {
+        match source {
+            WavetableSource::Builtin(builtin) => Ok(Self::builtin(*builtin)),
+            WavetableSource::File(path) => {
+                Ok(Self::from_frames(file_name(path), &read_wav_frames(path)?))
+            }
+            WavetableSource::Embedded { name, samples } => {
+                if samples.is_empty() || !samples.len().is_multiple_of(FRAME_SIZE) {
+                    return Err(format!("Embedded wavetable '{name}' has an invalid length"));
+                }
+
+                Ok(Self::from_frames(name.clone(), samples))
+            }
+        }
+    }
+
+    /// Build one of the built-in wavetables.
+    pub fn builtin(builtin: BuiltinWavetable) -> Self {
+        let harmonics = FRAME_SIZE / 2;
+        let spectra: Vec<Vec<Complex<f32>>> = match builtin {
+            BuiltinWavetable::BasicShapes => vec![
+                sine_spectrum(|k| if k == 1 { 1.0 } else { 0.0 }),
+                sine_spectrum(|k| match k % 4 {
+                    1 => 8.0 / (PI * PI * (k * k) as f32),
+                    3 => -8.0 / (PI * PI * (k * k) as f32),
+                    _ => 0.0,
+                }),
+                sine_spectrum(|k| {
+                    if k % 2 == 1 {
+                        4.0 / (PI * k as f32)
+                    } else {
+                        0.0
+                    }
+                }),
+                sine_spectrum(saw_harmonic),
+            ],
+            BuiltinWavetable::Pwm => (0..8)
+                .map(|frame| {
+                    let width = 0.5 - (0.45 * frame as f32 / 7.0);
+                    let mut spectrum = vec![Complex::new(0.0, 0.0); harmonics + 1];
+                    for (k, bin) in spectrum.iter_mut().enumerate().take(harmonics).skip(1) {
+                        // A pulse wave is a sum of cosines, this keeps the DC offset at zero
+                        let amplitude = 2.0 / (PI * k as f32) * (PI * k as f32 * width).sin();
+                        *bin = Complex::new(amplitude * FRAME_SIZE as f32 / 2.0, 0.0);
+                    }
+
+                    spectrum
+                })
+                .collect(),
+            BuiltinWavetable::HarmonicSweep => (1..=16)
+                .map(|num_harmonics| {
+                    sine_spectrum(|k| {
+                        if k <= num_harmonics {
+                            saw_harmonic(k)
+                        } else {
+                            0.0
+                        }
+                    })
+                })
+                .collect(),
+        };
+
+        Self::from_spectra(String::from(builtin.name()), spectra)
+    }
+
+    /// Build a wavetable from one or more frames of [`FRAME_SIZE`] samples each.
+    pub fn from_frames(name: String, samples: &[f32]) -> Self {
+        let mut planner = RealFftPlanner::<f32>::new();
+        let r2c = planner.plan_fft_forward(FRAME_SIZE);
+
+        let mut input = r2c.make_input_vec();
+        let spectra = samples
+            .chunks_exact(FRAME_SIZE)
+            .take(MAX_FRAMES)
+            .map(|frame| {
+                input.copy_from_slice(frame);
+                let mut spectrum = r2c.make_output_vec();
+                r2c.process(&mut input, &mut spectrum).unwrap();
+
+                spectrum
+            })
+            .collect();
+
+        Self::from_spectra(name, spectra)
+    }
+
+    /// Build the mip levels from a spectrum for every frame. The DC offset gets removed, and the
+    /// table is normalized so the loudest frame peaks at 1.0.
+    fn from_spectra(name: String, mut spectra: Vec<Vec<Complex<f32>>>) -> Self {
+        let mut planner = RealFftPlanner::<f32>::new();
+        let c2r = planner.plan_fft_inverse(FRAME_SIZE);
+
+        let num_frames = spectra.len();
+        let mut mip_levels: Vec<Vec<f32>> = (0..NUM_MIP_LEVELS)
+            .map(|_| Vec::with_capacity(num_frames * (FRAME_SIZE + 1)))
+            .collect();
+        let mut scratch = c2r.make_input_vec();
+        let mut output = c2r.make_output_vec();
+        for spectrum in &mut spectra {
+            spectrum[0] = Complex::new(0.0, 0.0);
+            spectrum[FRAME_SIZE / 2] = Complex::new(0.0, 0.0);
+
+            for (level, samples) in mip_levels.iter_mut().enumerate() {
+                let max_harmonic = (FRAME_SIZE / 2) >> level;
+                for (k, bin) in scratch.iter_mut().enumerate() {
+                    *bin = if k <= max_harmonic {
+                        spectrum[k]
+                    } else {
+                        Complex::new(0.0, 0.0)
+                    };
+                }
+
+                c2r.process(&mut scratch, &mut output).unwrap();
+                samples.extend(output.iter().map(|sample| sample / FRAME_SIZE as f32));
+                samples.push(output[0] / FRAME_SIZE as f32);
+            }
+        }
+
+        let peak = mip_levels[0]
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        if peak > 0.0 {
+            for sample in mip_levels.iter_mut().flatten() {
+                *sample /= peak;
+            }
+        }
+
+        Self {
+            name,
+            num_frames,
+            mip_levels,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    /// Pick the mip level with the most harmonics that still don't alias at this phase increment,
+    /// which is expressed in cycles per sample.
+    fn mip_level(&self, phase_delta: f32) -> usize {
+        let max_harmonics = 0.5 / phase_delta.max(f32::EPSILON);
+        let level = ((FRAME_SIZE / 2) as f32 / max_harmonics).log2().ceil();
+
+        (level.max(0.0) as usize).min(NUM_MIP_LEVELS - 1)
+    }
+
+    /// Linearly interpolate a frame at `phase` in `[0, 1)`.
+    fn sample(&self, level: usize, frame: usize, phase: f32) -> f32 {
+        let start = frame * (FRAME_SIZE + 1);
+        let samples = &self.mip_levels[level][start..start + FRAME_SIZE + 1];
+
+        let position = phase * FRAME_SIZE as f32;
+        let idx = (position as usize).min(FRAME_SIZE - 1);
+        let t = position - idx as f32;
+
+        samples[idx] + (samples[idx + 1] - samples[idx]) * t
+    }
+}
+
+impl WavetableOscillator {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            phase: 0.0,
+            phase_delta: 0.0,
+            sample_rate,
+            frequency: 440.0,
+            position: 0.0,
+        }
+    }
+
+    pub fn set_frequency(&mut self, freq: f32) {
+        self.frequency = freq;
+        self.update_phase_delta();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_phase_delta();
+    }
+
+    /// Set the position in the wavetable, `0.0` is the first frame and `1.0` is the last frame.
+    pub fn set_position(&mut self, position: f32) {
+        self.position = position.clamp(0.0, 1.0);
+    }
+
+    fn update_phase_delta(&mut self) {
+        self.phase_delta = self.frequency / self.sample_rate;
+    }
+
+    pub fn generate(&mut self, table: &Wavetable) -> f32 {
+        self.generate_with_phase_offset(table, 0.0)
+    }
+
+    /// Generate the next sample with the phase shifted by `offset` cycles. This is used for phase
+    /// feedback.
+    pub fn generate_with_phase_offset(&mut self, table: &Wavetable, offset: f32) -> f32 {
+        let level = table.mip_level(self.phase_delta);
+        let phase = (self.phase + offset).rem_euclid(1.0);
+
+        // Morph between the two frames surrounding the current position
+        let frame_position = self.position * (table.num_frames - 1) as f32;
+        let frame = (frame_position as usize).min(table.num_frames - 1);
+        let next_frame = (frame + 1).min(table.num_frames - 1);
+        let t = frame_position - frame as f32;
+
+        let current = table.sample(level, frame, phase);
+        let next = table.sample(level, next_frame, phase);
+        let sample = current + (next - current) * t;
+
+        self.phase += self.phase_delta;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+        }
+        sample
+    }
+}
+
+/// Build a spectrum containing only sine components, with `amplitude(k)` giving the amplitude of
+/// the `k`th harmonic.
+fn sine_spectrum(amplitude: impl Fn(usize) -> f32) -> Vec<Complex<f32>> {
+    (0..=FRAME_SIZE / 2)
+        .map(|k| {
+            if k == 0 {
+                Complex::new(0.0, 0.0)
+            } else {
+                Complex::new(0.0, -amplitude(k) * FRAME_SIZE as f32 / 2.0)
+            }
+        })
+        .collect()
+}
+
+/// The amplitude of the `k`th harmonic of a rising saw wave.
+fn saw_harmonic(k: usize) -> f32 {
+    let amplitude = 2.0 / (PI * k as f32);
+    if k % 2 == 1 {
+        amplitude
+    } else {
+        -amplitude
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("Wavetable"))
+}
+
+/// Read a WAV or FLAC file's first channel as wavetable frames. Files with a multiple of
+/// [`FRAME_SIZE`] samples are treated as multi-frame tables, and anything else is treated as a
+/// single cycle that gets resampled to one frame.
+fn read_wav_frames(path: &Path) -> Result<Vec<f32>, String> {
+    let (samples, _) = audio_file::read_mono(path)?;
+
+    if samples.len().is_multiple_of(FRAME_SIZE) {
+        Ok(samples.into_iter().take(MAX_FRAMES * FRAME_SIZE).collect())
+    } else {
+        Ok((0..FRAME_SIZE)
+            .map(|idx| {
+                let position = idx as f32 * samples.len() as f32 / FRAME_SIZE as f32;
+                let idx = position as usize;
+                let t = position - idx as f32;
+                let next = samples[(idx + 1) % samples.len()];
+
+                samples[idx] + (next - samples[idx]) * t
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Count the number of rising zero crossings in one second of output.
+    fn measure_frequency(
+        oscillator: &mut WavetableOscillator,
+        table: &Wavetable,
+        sample_rate: f32,
+    ) -> f32 {
+        let mut previous = oscillator.generate(table);
+        let mut crossings = 0;
+        for _ in 1..sample_rate as usize {
+            let sample = oscillator.generate(table);
+            if previous < 0.0 && sample >= 0.0 {
+                crossings += 1;
+            }
+            previous = sample;
+        }
+
+        crossings as f32
+    }
+
+    #[test]
+    fn frequency_accuracy() {
+        let table = Wavetable::builtin(BuiltinWavetable::BasicShapes);
+        for position in [0.0, 1.0] {
+            for frequency in [55.0, 440.0, 3520.0] {
+                let mut oscillator = WavetableOscillator::new(44100.0);
+                oscillator.set_frequency(frequency);
+                oscillator.set_position(position);
+
+                let measured = measure_frequency(&mut oscillator, &table, 44100.0);
+                assert!(
+                    (measured - frequency).abs() <= 1.0,
+                    "Expected {frequency} Hz, measured {measured} Hz"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn first_frame_is_sine() {
+        let table = Wavetable::builtin(BuiltinWavetable::BasicShapes);
+        let mut oscillator = WavetableOscillator::new(44100.0);
+        oscillator.set_frequency(441.0);
+
+        // The table is normalized as a whole, so the sine frame is quieter than the square frame
+        let output: Vec<f32> = (0..1000).map(|_| oscillator.generate(&table)).collect();
+        let amplitude = output
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!(amplitude > 0.5);
+        for (n, sample) in output.iter().enumerate() {
+            let expected = (2.0 * PI * n as f32 / 100.0).sin() * amplitude;
+            assert!((sample - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn mip_levels_are_band_limited() {
+        let table = Wavetable::builtin(BuiltinWavetable::BasicShapes);
+        for frequency in [20.0f32, 440.0, 5000.0, 15000.0] {
+            let phase_delta = frequency / 44100.0;
+            let harmonics = (FRAME_SIZE / 2) >> table.mip_level(phase_delta);
+            assert!(
+                harmonics as f32 * frequency <= 22050.0,
+                "{frequency} Hz aliases"
+            );
+            assert!(harmonics == FRAME_SIZE / 2 || harmonics as f32 * 2.0 * frequency > 22050.0);
+        }
+    }
+
+    #[test]
+    fn normalized_peak() {
+        for builtin in BuiltinWavetable::ALL {
+            let table = Wavetable::builtin(builtin);
+            let peak = table.mip_levels[0]
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            assert!(
+                (peak - 1.0).abs() < 1e-4,
+                "{} peaks at {peak}",
+                builtin.name()
+            );
+        }
+    }
+
+    #[test]
+    fn import_single_cycle_and_multi_frame() {
+        let dir = std::env::temp_dir().join(format!("am_synth_wavetable_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let write_wav = |name: &str, num_samples: usize, cycle_length: usize| {
+            let path = dir.join(name);
+            let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+            for n in 0..num_samples {
+                let phase = (n % cycle_length) as f32 / cycle_length as f32;
+                writer
+                    .write_sample(((2.0 * PI * phase).sin() * 16000.0) as i16)
+                    .unwrap();
+            }
+            writer.finalize().unwrap();
+
+            path
+        };
+
+        let single_cycle = write_wav("single.wav", 600, 600);
+        let source = WavetableSource::embed(&single_cycle).unwrap();
+        let table = Wavetable::from_source(&source).unwrap();
+        assert_eq!(table.num_frames(), 1);
+        assert_eq!(table.name(), "single");
+
+        let multi_frame = write_wav("multi.wav", FRAME_SIZE * 4, FRAME_SIZE);
+        let table = Wavetable::from_source(&WavetableSource::File(multi_frame)).unwrap();
+        assert_eq!(table.num_frames(), 4);
+
+        assert!(Wavetable::from_source(&WavetableSource::File(dir.join("missing.wav"))).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
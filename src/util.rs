@@ -1,3 +1,41 @@
+use std::path::PathBuf;
+
 pub fn midi_note_to_freq(note: u8, tuning: f32) -> f32 {
     tuning * 2.0f32.powf((note as f32 - 69.0) / 12.0)
 }
+
+/// The directory user themes and presets are stored in, `am-synth` in the platform's configuration
+/// directory. Returns `None` if that directory could not be determined.
+pub fn config_dir() -> Option<PathBuf> {
+    let config_dir = if cfg!(target_os = "windows") {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(std::env::var_os("HOME")?).join("Library/Application Support")
+    } else {
+        match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(config_home) if !config_home.is_empty() => PathBuf::from(config_home),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        }
+    };
+
+    Some(config_dir.join("am-synth"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concert_pitch() {
+        assert_eq!(midi_note_to_freq(69, 440.0), 440.0);
+        assert_eq!(midi_note_to_freq(69, 432.0), 432.0);
+    }
+
+    #[test]
+    fn octaves_and_semitones() {
+        assert!((midi_note_to_freq(81, 440.0) - 880.0).abs() < 1e-3);
+        assert!((midi_note_to_freq(57, 440.0) - 220.0).abs() < 1e-3);
+        assert!((midi_note_to_freq(60, 440.0) - 261.6256).abs() < 1e-3);
+        assert!((midi_note_to_freq(0, 440.0) - 8.175_799).abs() < 1e-4);
+    }
+}
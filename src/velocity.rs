@@ -0,0 +1,236 @@
+use nih_plug::prelude::Enum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The maximum number of points in a user velocity curve.
+pub const MAX_VELOCITY_POINTS: usize = 16;
+
+/// How a note's velocity is shaped before it's used for the voice's level and modulation.
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VelocityCurve {
+    Linear,
+    /// Loud notes are easier to reach.
+    Soft,
+    /// Loud notes take harder playing.
+    Hard,
+    /// Less sensitive at both ends and more sensitive in the middle.
+    #[name = "S-Curve"]
+    SCurve,
+    /// The curve drawn by the [`VelocityTable`].
+    User,
+}
+
+/// A user velocity curve with up to [`MAX_VELOCITY_POINTS`] points connected by straight lines.
+/// Every point maps an input velocity to an output velocity, both between 0 and 1, and velocities
+/// before the first point or after the last point take that point's output.
+///
+/// Tables are written as comma separated `input output` pairs, e.g. `0 0, 0.5 0.8, 1 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<(f32, f32)>", into = "Vec<(f32, f32)>")]
+pub struct VelocityTable {
+    /// Only the first `len` points are used, sorted by their input. This is a fixed size array so
+    /// the audio thread can copy tables without allocating.
+    points: [(f32, f32); MAX_VELOCITY_POINTS],
+    len: usize,
+}
+
+impl VelocityCurve {
+    /// Shape a velocity between 0 and 1. `table` is only used by the user curve.
+    pub fn apply(self, velocity: f32, table: &VelocityTable) -> f32 {
+        let velocity = velocity.clamp(0.0, 1.0);
+        match self {
+            VelocityCurve::Linear => velocity,
+            VelocityCurve::Soft => velocity.sqrt(),
+            VelocityCurve::Hard => velocity * velocity,
+            VelocityCurve::SCurve => velocity * velocity * (3.0 - 2.0 * velocity),
+            VelocityCurve::User => table.apply(velocity),
+        }
+    }
+}
+
+impl Default for VelocityTable {
+    /// A linear curve.
+    fn default() -> Self {
+        Self::new(&[(0.0, 0.0), (1.0, 1.0)])
+    }
+}
+
+impl VelocityTable {
+    /// Create a table from its points. Points beyond [`MAX_VELOCITY_POINTS`] are dropped, the
+    /// points' values are clamped between 0 and 1, and the points are sorted by their input.
+    pub fn new(points: &[(f32, f32)]) -> Self {
+        let len = points.len().min(MAX_VELOCITY_POINTS);
+        let mut clamped_points = [(0.0, 0.0); MAX_VELOCITY_POINTS];
+        for (clamped, (input, output)) in clamped_points.iter_mut().zip(&points[..len]) {
+            *clamped = (input.clamp(0.0, 1.0), output.clamp(0.0, 1.0));
+        }
+        clamped_points[..len].sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        Self {
+            points: clamped_points,
+            len,
+        }
+    }
+
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.points[..self.len]
+    }
+
+    /// The output velocity for an input velocity. A table without points is linear.
+    pub fn apply(&self, velocity: f32) -> f32 {
+        let points = self.points();
+        let (Some(first), Some(last)) = (points.first(), points.last()) else {
+            return velocity;
+        };
+        if velocity <= first.0 {
+            return first.1;
+        }
+        if velocity >= last.0 {
+            return last.1;
+        }
+
+        // The first point that's past the velocity ends the line the velocity is on
+        let end = points
+            .iter()
+            .position(|(input, _)| *input > velocity)
+            .unwrap_or(points.len() - 1);
+        let (start_input, start_output) = points[end - 1];
+        let (end_input, end_output) = points[end];
+        let t = (velocity - start_input) / (end_input - start_input);
+
+        start_output + (end_output - start_output) * t
+    }
+}
+
+impl From<Vec<(f32, f32)>> for VelocityTable {
+    fn from(points: Vec<(f32, f32)>) -> Self {
+        Self::new(&points)
+    }
+}
+
+impl From<VelocityTable> for Vec<(f32, f32)> {
+    fn from(table: VelocityTable) -> Self {
+        table.points().to_vec()
+    }
+}
+
+impl fmt::Display for VelocityTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, (input, output)) in self.points().iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{input} {output}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for VelocityTable {
+    type Err = String;
+
+    /// Parse the format written by the [`Display`][fmt::Display] implementation.
+    fn from_str(string: &str) -> Result<Self, String> {
+        let mut points = Vec::new();
+        for (idx, point) in string.split(',').enumerate() {
+            let numbers = point
+                .split_whitespace()
+                .map(|token| {
+                    token
+                        .parse::<f32>()
+                        .map_err(|_| format!("'{token}' is not a number"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            match numbers[..] {
+                [input, output] => points.push((input, output)),
+                _ => {
+                    return Err(format!(
+                        "Point {} needs an input and an output velocity",
+                        idx + 1
+                    ))
+                }
+            }
+        }
+
+        if points.len() > MAX_VELOCITY_POINTS {
+            return Err(format!(
+                "Velocity curves can have at most {MAX_VELOCITY_POINTS} points"
+            ));
+        }
+
+        Ok(Self::new(&points))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves() {
+        let table = VelocityTable::default();
+        for curve in [
+            VelocityCurve::Linear,
+            VelocityCurve::Soft,
+            VelocityCurve::Hard,
+            VelocityCurve::SCurve,
+            VelocityCurve::User,
+        ] {
+            assert_eq!(curve.apply(0.0, &table), 0.0, "{curve:?}");
+            assert_eq!(curve.apply(1.0, &table), 1.0, "{curve:?}");
+
+            let values: Vec<f32> = (0..=100)
+                .map(|n| curve.apply(n as f32 / 100.0, &table))
+                .collect();
+            assert!(
+                values.windows(2).all(|pair| pair[0] <= pair[1]),
+                "{curve:?} is not monotonic"
+            );
+        }
+
+        assert_eq!(VelocityCurve::Linear.apply(0.25, &table), 0.25);
+        assert!(VelocityCurve::Soft.apply(0.25, &table) > 0.25);
+        assert!(VelocityCurve::Hard.apply(0.25, &table) < 0.25);
+        assert!(VelocityCurve::SCurve.apply(0.25, &table) < 0.25);
+        assert!(VelocityCurve::SCurve.apply(0.75, &table) > 0.75);
+        assert_eq!(VelocityCurve::User.apply(0.25, &table), 0.25);
+    }
+
+    #[test]
+    fn user_table() {
+        let table = VelocityTable::new(&[(0.8, 1.0), (0.2, 0.2), (0.5, 0.8)]);
+        assert_eq!(table.points(), [(0.2, 0.2), (0.5, 0.8), (0.8, 1.0)]);
+
+        assert_eq!(table.apply(0.0), 0.2);
+        assert_eq!(table.apply(0.2), 0.2);
+        assert!((table.apply(0.35) - 0.5).abs() < 1e-6);
+        assert_eq!(table.apply(0.5), 0.8);
+        assert!((table.apply(0.65) - 0.9).abs() < 1e-6);
+        assert_eq!(table.apply(1.0), 1.0);
+
+        assert_eq!(VelocityTable::new(&[]).apply(0.3), 0.3);
+    }
+
+    #[test]
+    fn parse_velocity_table() {
+        let table: VelocityTable = "0 0.1, 0.5 0.8, 1 1".parse().unwrap();
+        assert_eq!(table.points(), [(0.0, 0.1), (0.5, 0.8), (1.0, 1.0)]);
+
+        for table in [table, VelocityTable::default()] {
+            assert_eq!(table.to_string().parse(), Ok(table));
+            let json = serde_json::to_string(&table).unwrap();
+            assert_eq!(serde_json::from_str::<VelocityTable>(&json).unwrap(), table);
+        }
+
+        let too_many = vec!["0.5 0.5"; MAX_VELOCITY_POINTS + 1].join(", ");
+        for invalid in ["", "1", "1 x", "0 0 0", too_many.as_str()] {
+            assert!(
+                invalid.parse::<VelocityTable>().is_err(),
+                "'{invalid}' was parsed"
+            );
+        }
+    }
+}